@@ -367,7 +367,7 @@ class GraphClient:
         url = f"{self.url}/{self.graph_id}/graph-diff"
         r = requests.post(
             url,
-            data=self.diff.to_bytes(),
+            data=self.diff.to_legacy_bytes(),
             headers={"Authorization": self.auth_token},
         )
         if r.status_code >= 300:
@@ -0,0 +1,46 @@
+import uuid
+
+import pytest
+
+from drisk_api.drisk_api import PyGraphDiff
+
+
+def test_new_or_updated_nodes_uuid_keys():
+    """The nodes are keyed by uuid.UUID objects."""
+    a, b = uuid.uuid4(), uuid.uuid4()
+    diff = PyGraphDiff()
+    diff.add_node(a.bytes, {"label": "a"})
+    diff.add_node(b.bytes, {"label": "b"})
+
+    nodes = diff.new_or_updated_nodes_uuid()
+    assert all(isinstance(key, uuid.UUID) for key in nodes)
+    assert nodes == {a: {"label": "a"}, b: {"label": "b"}}
+    assert {str(key) for key in nodes} == set(diff.new_or_updated_nodes())
+
+
+def test_edges_as_list():
+    """Edges are listed as flat tuples."""
+    a, b, c = [uuid.uuid4().bytes for _ in range(3)]
+    diff = PyGraphDiff()
+    diff.add_edge(a, b, 1.0)
+    diff.add_edge(a, c, 2.0)
+    diff.delete_edge(b, c)
+    assert sorted(diff.edges_as_list()) == sorted([(a, b, 1.0), (a, c, 2.0)])
+    assert diff.deleted_edges_as_list() == [(b, c)]
+
+
+def test_edges_as_dataframe():
+    """The edge list can be loaded into a pandas DataFrame."""
+    pd = pytest.importorskip("pandas")
+    a, b, c = [uuid.uuid4().bytes for _ in range(3)]
+    diff = PyGraphDiff()
+    diff.add_edge(a, b, 1.0)
+    diff.add_edge(a, c, 2.0)
+
+    df = pd.DataFrame.from_records(
+        diff.edges_as_list(), columns=["from", "to", "weight"]
+    )
+    assert len(df) == 2
+    assert set(df["to"]) == {b, c}
+    assert (df["from"] == a).all()
+    assert df["weight"].sum() == 3.0
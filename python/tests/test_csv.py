@@ -0,0 +1,116 @@
+import os
+import uuid
+
+import pytest
+
+from drisk_api.drisk_api import PyGraphDiff
+
+FIXTURES = os.path.join(os.path.dirname(__file__), "fixtures")
+
+A = "6f1c1d4e-1f5a-4c8e-9b7a-2c1e5b0f3a10"
+B = "0b3e7d2a-5c4f-4a1b-8d9e-7f6a5b4c3d21"
+C = "c2d4e6f8-0a1b-4c3d-9e5f-6a7b8c9d0e32"
+
+
+def read_fixture(name):
+    """Return the text of a fixture file."""
+    with open(os.path.join(FIXTURES, name)) as f:
+        return f.read()
+
+
+def fixture_diff():
+    """Return the diff loaded from the CSV fixtures."""
+    return PyGraphDiff.from_csv(
+        read_fixture("nodes.csv"), read_fixture("edges.csv")
+    )
+
+
+def test_from_csv_fixtures():
+    """The fixtures load with quoted fields and optional colors."""
+    diff = fixture_diff()
+    nodes = diff.new_or_updated_nodes()
+    assert sorted(nodes) == sorted([A, B, C])
+    assert nodes[A] == {
+        "label": "Alpha, the first",
+        "url": "https://example.com/a",
+        "red": 255,
+        "green": 0,
+        "blue": 0,
+        "size": 1.5,
+    }
+    assert nodes[B] == {"label": "Beta"}
+    assert nodes[C] == {
+        "label": 'Gamma "quoted"',
+        "red": 0,
+        "green": 128,
+        "blue": 255,
+    }
+    # a missing weight defaults to 1
+    assert diff.new_or_updated_edges() == {A: {B: 0.5}, B: {C: 1.0}}
+
+
+def test_from_csv_optional_columns():
+    """Only the id, from and to columns are required."""
+    nodes = "id\n{}\n{}\n".format(A, B)
+    edges = "to,from\n{},{}\n".format(B, A)
+    diff = PyGraphDiff.from_csv(nodes, edges)
+    assert diff.new_or_updated_nodes() == {A: {}, B: {}}
+    assert diff.edges_as_list() == [(uuid.UUID(A).bytes, uuid.UUID(B).bytes, 1.0)]
+
+
+def test_from_csv_malformed_rows():
+    """Malformed input raises a ValueError naming the csv and line."""
+    nodes = read_fixture("nodes.csv")
+    edges = "from,to,weight\n{},{},1\n{},{}\n".format(A, B, B, C)
+    message = "edges csv line 3: expected 3 fields, found 2"
+    with pytest.raises(ValueError, match=message):
+        PyGraphDiff.from_csv(nodes, edges)
+    with pytest.raises(ValueError, match="edges csv is missing the to column"):
+        PyGraphDiff.from_csv(nodes, "from,weight\n")
+    message = 'nodes csv line 2: invalid id "not-a-uuid"'
+    with pytest.raises(ValueError, match=message):
+        PyGraphDiff.from_csv("id\nnot-a-uuid\n", "from,to\n")
+    message = 'nodes csv line 3: invalid color_red "300"'
+    nodes = "id,color_red\n{},1\n{},300\n".format(A, B)
+    with pytest.raises(ValueError, match=message):
+        PyGraphDiff.from_csv(nodes, "from,to\n")
+
+
+def test_to_csv_round_trip():
+    """A diff exported to CSV loads back to an equal diff."""
+    a, b, c = uuid.UUID(A).bytes, uuid.UUID(B).bytes, uuid.UUID(C).bytes
+    diff = PyGraphDiff()
+    diff.add_node(
+        a,
+        {
+            "label": "Alpha, the first",
+            "size": 1.5,
+            "red": 1,
+            "green": 2,
+            "blue": 3,
+            "alpha": 128,
+            "show_label": False,
+            "kind": "person",
+            "scores": [1, 2.5, None],
+            "meta": {"note": 'say "hi"'},
+        },
+    )
+    diff.add_node(b, {"label": None, "url": None})
+    diff.delete_node(c)
+    diff.add_edge(a, b, 0.25)
+    diff.delete_edge(b, a)
+
+    nodes_csv, edges_csv = diff.to_csv()
+    assert nodes_csv.splitlines()[0] == (
+        "id,label,url,color_red,color_green,color_blue,size,"
+        "color_alpha,show_label,extra,cleared,status"
+    )
+    restored = PyGraphDiff.from_csv(nodes_csv, edges_csv)
+    assert restored == diff
+    assert restored.to_csv() == (nodes_csv, edges_csv)
+
+
+def test_from_csv_round_trips_fixtures():
+    """The fixtures survive a CSV round trip."""
+    diff = fixture_diff()
+    assert PyGraphDiff.from_csv(*diff.to_csv()) == diff
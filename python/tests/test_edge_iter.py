@@ -0,0 +1,44 @@
+import uuid
+
+import pytest
+
+from drisk_api.drisk_api import PyGraphDiff, PyGraphDiffU64
+
+
+def test_iter_edges_yields_every_edge():
+    """The iterator yields every edge across chunks."""
+    diff = PyGraphDiffU64()
+    # more edges than the iterator copies at a time
+    for from_id in range(300):
+        for to_id in range(10):
+            diff.add_edge(from_id, to_id, float(to_id))
+    edges = list(diff.iter_edges())
+    assert len(edges) == 3000
+    assert sorted(edges) == sorted(diff.edges_as_list())
+
+
+def test_iter_edges_of_uuid_diff():
+    """The iterator of a UUID diff yields the ids as bytes."""
+    a, b = uuid.uuid4().bytes, uuid.uuid4().bytes
+    diff = PyGraphDiff()
+    diff.add_edge(a, b, 0.5)
+    assert list(diff.iter_edges()) == [(a, b, 0.5)]
+    assert list(PyGraphDiff().iter_edges()) == []
+
+
+def test_iter_edges_raises_on_changes():
+    """The iterator raises if the edges change during iteration."""
+    diff = PyGraphDiffU64()
+    diff.add_edge(1, 2, 1.0)
+    diff.add_edge(2, 3, 1.0)
+    edges = diff.iter_edges()
+    next(edges)
+    diff.add_edge(3, 4, 1.0)
+    with pytest.raises(RuntimeError, match="changed during iteration"):
+        next(edges)
+
+    # node updates leave the edges unchanged
+    edges = diff.iter_edges()
+    next(edges)
+    diff.add_node(5, {"label": "five"})
+    assert len(list(edges)) == 2
@@ -0,0 +1,55 @@
+import json
+import uuid
+
+import pytest
+
+from drisk_api.drisk_api import PyGraphDiff
+
+
+def test_context_manager_result():
+    """A clean with block keeps the finalized bytes."""
+    a, b = uuid.uuid4().bytes, uuid.uuid4().bytes
+    with PyGraphDiff() as diff:
+        assert diff.result is None
+        diff.add_node(a, {"label": "a"})
+        diff.add_node(b, {"label": "b"})
+        diff.add_edge(a, b, 1.0)
+    assert diff.result == diff.finalize()
+    assert PyGraphDiff.from_bytes(diff.result) == diff
+
+
+def test_context_manager_raises_on_inconsistency():
+    """An inconsistent diff raises on exit and keeps no result."""
+    a, b = str(uuid.uuid4()), str(uuid.uuid4())
+    diff = PyGraphDiff.from_json(
+        json.dumps(
+            {
+                "nodes": {"newOrUpdated": {}, "deleted": [b]},
+                "edges": {"newOrUpdated": {a: {b: 1.0}}, "deleted": {}},
+            }
+        )
+    )
+    with pytest.raises(ValueError, match="inconsistent"):
+        with diff:
+            pass
+    assert diff.result is None
+    with pytest.raises(ValueError, match="inconsistent"):
+        diff.finalize()
+
+
+def test_context_manager_keeps_no_result_on_error():
+    """A with block that raises keeps no result."""
+    diff = PyGraphDiff()
+    with pytest.raises(KeyError):
+        with diff:
+            diff.add_node(uuid.uuid4().bytes, {"label": "a"})
+            raise KeyError("a")
+    assert diff.result is None
+
+
+def test_finalize_returns_bytes():
+    """Finalize returns the bytes of a consistent diff."""
+    a = uuid.uuid4().bytes
+    diff = PyGraphDiff()
+    diff.add_node(a, {"label": "a"})
+    assert PyGraphDiff.from_bytes(diff.finalize()) == diff
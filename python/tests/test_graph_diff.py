@@ -0,0 +1,150 @@
+import uuid
+
+import pytest
+
+from drisk_api.drisk_api import PyGraphDiff
+
+
+def ids(n):
+    """Return `n` random UUIDs as bytes."""
+    return [uuid.uuid4().bytes for _ in range(n)]
+
+
+def test_add_nodes_and_edges_batch():
+    """Batch adds give the same diff as adding each node and edge."""
+    nodes = {
+        id: {"label": str(i), "size": float(i)} for i, id in enumerate(ids(50))
+    }
+    source, *targets = list(nodes)
+    edges = {source: {target: float(i) for i, target in enumerate(targets)}}
+
+    batch = PyGraphDiff()
+    batch.add_nodes(nodes)
+    batch.add_edges(edges)
+
+    incremental = PyGraphDiff()
+    for id, update in nodes.items():
+        incremental.add_node(id, update)
+    for target, weight in edges[source].items():
+        incremental.add_edge(source, target, weight)
+
+    assert batch.new_or_updated_nodes() == incremental.new_or_updated_nodes()
+    assert batch.new_or_updated_edges() == incremental.new_or_updated_edges()
+    assert batch.num_nodes() == 50
+    assert batch.num_edges() == 49
+
+
+def test_add_nodes_is_all_or_nothing():
+    """A batch with an invalid node update adds no nodes."""
+    a, b = ids(2)
+    diff = PyGraphDiff()
+    with pytest.raises(TypeError):
+        diff.add_nodes({a: {"label": "a"}, b: "not a dict"})
+    assert diff.num_nodes() == 0
+
+
+def test_empty_diff():
+    """An empty diff is falsy and has length zero."""
+    diff = PyGraphDiff()
+    assert diff.is_empty()
+    assert not diff
+    assert len(diff) == 0
+
+
+def test_populated_diff():
+    """The length of a diff counts node and edge changes."""
+    a, b, c = ids(3)
+    diff = PyGraphDiff()
+    diff.add_node(a, {"label": "a"})
+    diff.add_edge(a, b, 1.0)
+    diff.delete_edge(a, c)
+    assert not diff.is_empty()
+    assert diff
+    assert len(diff) == 3
+
+
+def test_eq():
+    """Diffs with the same changes are equal regardless of insertion order."""
+    a, b = ids(2)
+    diff = PyGraphDiff()
+    diff.add_node(a, {"label": "a"})
+    diff.add_edge(a, b, 1.0)
+    same = PyGraphDiff()
+    same.add_edge(a, b, 1.0)
+    same.add_node(a, {"label": "a"})
+    assert diff == same
+
+    other = PyGraphDiff()
+    other.add_node(a, {"label": "a"})
+    other.add_edge(a, b, 2.0)
+    assert diff != other
+    assert diff != PyGraphDiff()
+
+
+def test_repr():
+    """The repr counts new and deleted nodes and edges."""
+    a, b, c, d = ids(4)
+    diff = PyGraphDiff()
+    assert repr(diff) == "PyGraphDiff(nodes=0 new/0 del, edges=0 new/0 del)"
+    diff.add_node(a, {"label": "a"})
+    diff.add_node(b, {"label": "b"})
+    diff.delete_node(c)
+    diff.add_edge(a, b, 1.0)
+    diff.delete_edge(a, d)
+    assert repr(diff) == "PyGraphDiff(nodes=2 new/1 del, edges=1 new/1 del)"
+
+
+def test_from_components():
+    """A diff built from components equals the one built incrementally."""
+    a, b, c, d = ids(4)
+    diff = PyGraphDiff.from_components(
+        {a: {"label": "a"}, b: {"label": "b"}},
+        [c],
+        {a: {b: 1.0}},
+        {a: [d]},
+    )
+
+    incremental = PyGraphDiff()
+    incremental.add_node(a, {"label": "a"})
+    incremental.add_node(b, {"label": "b"})
+    incremental.delete_node(c)
+    incremental.add_edge(a, b, 1.0)
+    incremental.delete_edge(a, d)
+    assert diff == incremental
+
+
+def test_from_components_rejects_inconsistent():
+    """Inconsistent components raise a ValueError."""
+    a, b = ids(2)
+    with pytest.raises(ValueError, match="deleted"):
+        PyGraphDiff.from_components({}, [b], {a: {b: 1.0}}, {})
+    with pytest.raises(ValueError, match="both updated and deleted"):
+        PyGraphDiff.from_components({a: {"label": "a"}}, [a], {}, {})
+
+
+def test_num_edges_counts_fan_out():
+    """Edges from a single source are counted one by one."""
+    source, *targets = ids(101)
+    diff = PyGraphDiff()
+    for target in targets:
+        diff.add_edge(source, target, 1.0)
+    assert diff.num_edges() == 100
+
+    for target in targets[:10]:
+        diff.delete_edge(source, target)
+    assert diff.num_edges() == 100
+    assert len(diff.edges_as_list()) == 90
+    assert len(diff.deleted_edges_as_list()) == 10
+
+
+def test_num_nodes():
+    """Each node is counted once, whether updated or deleted."""
+    a, b = ids(2)
+    diff = PyGraphDiff()
+    diff.add_node(a, {"label": "a"})
+    diff.add_node(a, {"url": "https://example.com"})
+    diff.delete_node(b)
+    assert diff.num_nodes() == 2
+    diff.delete_node(a)
+    assert diff.num_nodes() == 2
+    assert diff.new_or_updated_nodes() == {}
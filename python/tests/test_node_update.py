@@ -0,0 +1,46 @@
+import uuid
+
+import pytest
+
+from drisk_api.drisk_api import PyGraphDiff
+
+
+def test_color_channels_out_of_range():
+    """Out of range color channels raise a ValueError naming the channel."""
+    diff = PyGraphDiff()
+    for value in [-1, 256, 300, 2**63, 2**100]:
+        with pytest.raises(ValueError, match="red must be between 0 and 255"):
+            diff.add_node(uuid.uuid4().bytes, {"red": value})
+    with pytest.raises(ValueError, match="alpha must be between 0 and 255"):
+        diff.add_node(uuid.uuid4().bytes, {"alpha": -(2**70)})
+    assert diff.num_nodes() == 0
+
+    node = uuid.uuid4().bytes
+    diff.add_node(node, {"red": 0, "green": 128, "blue": 255})
+    assert diff.num_nodes() == 1
+
+
+def test_invalid_sizes():
+    """Negative, NaN and infinite sizes raise a ValueError."""
+    diff = PyGraphDiff()
+    for size in [-1.0, float("nan"), float("inf"), float("-inf")]:
+        with pytest.raises(ValueError, match="size must be a finite non-negative"):
+            diff.add_node(uuid.uuid4().bytes, {"size": size})
+    diff.add_node(uuid.uuid4().bytes, {"size": 0.0})
+    assert diff.num_nodes() == 1
+
+
+def test_add_node_strict_rejects_unknown_keys():
+    """Unknown keys raise in add_node_strict and are not node fields otherwise."""
+    diff = PyGraphDiff()
+    update = {"labl": "x", "colour": 1, "label": "y"}
+    message = "unrecognized node update keys: colour, labl"
+    with pytest.raises(ValueError, match=message):
+        diff.add_node_strict(uuid.uuid4().bytes, update)
+    assert diff.num_nodes() == 0
+
+    diff.add_node_strict(uuid.uuid4().bytes, {"label": "x"})
+    loose = uuid.uuid4().bytes
+    diff.add_node(loose, {"labl": "x"})
+    assert diff.num_nodes() == 2
+    assert "label" not in diff.new_or_updated_nodes()[str(uuid.UUID(bytes=loose))]
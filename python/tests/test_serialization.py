@@ -0,0 +1,42 @@
+import json
+import pickle
+import uuid
+
+from drisk_api.drisk_api import PyGraphDiff
+
+
+def sample_diff():
+    """Return a diff with new, updated and deleted nodes and edges."""
+    a, b, c, d = [uuid.uuid4().bytes for _ in range(4)]
+    diff = PyGraphDiff()
+    diff.add_node(a, {"label": "a", "url": "https://example.com", "red": 10})
+    diff.add_node(b, {"size": 2.5, "show_label": True})
+    diff.delete_node(c)
+    diff.add_edge(a, b, 0.5)
+    diff.delete_edge(b, d)
+    return diff
+
+
+def test_pickle_round_trip():
+    """Unpickling a diff reproduces its nodes and edges."""
+    diff = sample_diff()
+    restored = pickle.loads(pickle.dumps(diff))
+    assert isinstance(restored, PyGraphDiff)
+    assert restored.new_or_updated_nodes() == diff.new_or_updated_nodes()
+    assert restored.deleted_nodes() == diff.deleted_nodes()
+    assert restored.new_or_updated_edges() == diff.new_or_updated_edges()
+    assert restored.deleted_edges() == diff.deleted_edges()
+
+
+def test_json_round_trip():
+    """A JSON round trip gives the same diff as the bytes path."""
+    diff = sample_diff()
+    text = diff.to_json()
+    parsed = json.loads(text)
+    for id in parsed["nodes"]["newOrUpdated"]:
+        assert str(uuid.UUID(id)) == id
+
+    from_json = PyGraphDiff.from_json(text)
+    from_bytes = PyGraphDiff.from_bytes(diff.to_bytes())
+    assert from_json == diff
+    assert from_json == from_bytes
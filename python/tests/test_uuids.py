@@ -0,0 +1,61 @@
+import os
+import uuid
+
+import pytest
+
+from drisk_api.drisk_api import PyGraphDiff, PyGraphDiffU64
+
+
+def uuid7_bytes():
+    """Return random version 7 UUID bytes."""
+    raw = bytearray(os.urandom(16))
+    raw[6] = (raw[6] & 0x0F) | 0x70
+    raw[8] = (raw[8] & 0x3F) | 0x80
+    return bytes(raw)
+
+
+def test_strict_accepts_v4():
+    """Strict diffs accept version 4 UUIDs."""
+    a, b = uuid.uuid4().bytes, uuid.uuid4().bytes
+    diff = PyGraphDiff(strict_uuids=True)
+    assert diff.strict_uuids
+    diff.add_node(a, {"label": "a"})
+    diff.add_edge(a, b, 1.0)
+    assert diff.edges_as_list() == [(a, b, 1.0)]
+
+
+def test_strict_accepts_v7():
+    """Strict diffs accept version 7 UUIDs."""
+    a, b = uuid7_bytes(), uuid7_bytes()
+    assert uuid.UUID(bytes=a).version == 7
+    diff = PyGraphDiff(strict_uuids=True)
+    diff.add_nodes({a: {"label": "a"}, b: {"label": "b"}})
+    diff.add_edges({a: {b: 0.5}})
+    diff.delete_edge(a, b)
+    diff.delete_node(b)
+
+
+def test_strict_rejects_arbitrary_buffer():
+    """Strict diffs reject other versions and variants."""
+    buffer = bytes(range(16))
+    diff = PyGraphDiff(strict_uuids=True)
+    with pytest.raises(ValueError, match="version 4 or 7"):
+        diff.add_node(buffer, {"label": "a"})
+    with pytest.raises(ValueError, match="version 4 or 7"):
+        diff.add_edge(uuid.uuid4().bytes, buffer, 1.0)
+    # right version, but the NCS variant
+    raw = bytearray(uuid.uuid4().bytes)
+    raw[8] &= 0x7F
+    with pytest.raises(ValueError, match="RFC 4122"):
+        diff.delete_node(bytes(raw))
+    assert diff.edges_as_list() == []
+
+
+def test_not_strict_by_default():
+    """Diffs accept any 16 bytes unless strict."""
+    buffer = bytes(range(16))
+    diff = PyGraphDiff()
+    assert not diff.strict_uuids
+    diff.add_node(buffer, {"label": "a"})
+    # integer ids are never checked
+    PyGraphDiffU64(strict_uuids=True).add_node(3, {"label": "a"})
@@ -0,0 +1,37 @@
+import uuid
+
+import pytest
+
+from drisk_api.drisk_api import PyGraphDiff, PyGraphDiffF64
+
+
+def test_f64_weights_keep_precision():
+    """F64 diffs keep the full precision of Python floats."""
+    a, b = uuid.uuid4().bytes, uuid.uuid4().bytes
+    weight = 0.1 + 1e-12
+    diff = PyGraphDiffF64()
+    diff.add_edge(a, b, weight)
+    assert diff.edges_as_list() == [(a, b, weight)]
+
+    restored = PyGraphDiffF64.from_bytes(diff.to_bytes())
+    assert restored.edges_as_list() == [(a, b, weight)]
+    assert restored == diff
+
+
+def test_f32_weights_are_narrowed():
+    """The default diff narrows weights to f32."""
+    a, b = uuid.uuid4().bytes, uuid.uuid4().bytes
+    diff = PyGraphDiff()
+    diff.add_edge(a, b, 0.1 + 1e-12)
+    [(_, _, weight)] = PyGraphDiff.from_bytes(diff.to_bytes()).edges_as_list()
+    assert weight != 0.1 + 1e-12
+    assert weight == pytest.approx(0.1)
+
+
+def test_from_bytes_rejects_other_weight_type():
+    """Bytes of an f64 diff are not read as an f32 diff."""
+    a, b = uuid.uuid4().bytes, uuid.uuid4().bytes
+    diff = PyGraphDiffF64()
+    diff.add_edge(a, b, 1.0)
+    with pytest.raises(Exception, match="weight type"):
+        PyGraphDiff.from_bytes(diff.to_bytes())
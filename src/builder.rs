@@ -0,0 +1,137 @@
+use crate::{diff::GraphDiff, error::GraphDiffError};
+use alloc::vec::Vec;
+use core::{fmt::Debug, hash::Hash, ops::AddAssign};
+
+enum Operation<Id, T, W> {
+    AddNode(Id, T),
+    DeleteNode(Id),
+    AddEdge(Id, Id, W),
+    DeleteEdge(Id, Id),
+}
+
+/// Records operations on a `GraphDiff` and checks referential integrity when built.
+///
+/// Unlike `GraphDiff::delete_node`, deleting a node in the builder does not silently remove
+/// the edges connected to it. Instead `build` fails if any edge still references a deleted
+/// node once all operations have been replayed.
+/// ```
+/// use drisk_api::GraphDiffBuilder;
+///
+/// let mut builder = GraphDiffBuilder::<u32, u32>::new();
+/// builder
+///     .add_node(1, 10)
+///     .add_edge(1, 2, 1.0)
+///     .delete_node(2);
+/// assert!(builder.build().is_err());
+/// ```
+pub struct GraphDiffBuilder<Id, T, W = f32> {
+    operations: Vec<Operation<Id, T, W>>,
+}
+
+impl<Id, T, W> Default for GraphDiffBuilder<Id, T, W> {
+    fn default() -> GraphDiffBuilder<Id, T, W> {
+        GraphDiffBuilder {
+            operations: Vec::new(),
+        }
+    }
+}
+
+impl<Id, T, W> GraphDiffBuilder<Id, T, W>
+where
+    Id: Hash + Eq + Copy + Debug,
+    T: Default + AddAssign,
+    W: Copy + PartialEq,
+{
+    pub fn new() -> GraphDiffBuilder<Id, T, W> {
+        GraphDiffBuilder::default()
+    }
+
+    /// Record adding or updating a node.
+    pub fn add_node(&mut self, id: Id, update: T) -> &mut Self {
+        self.operations.push(Operation::AddNode(id, update));
+        self
+    }
+
+    /// Record deleting a node.
+    pub fn delete_node(&mut self, id: Id) -> &mut Self {
+        self.operations.push(Operation::DeleteNode(id));
+        self
+    }
+
+    /// Record adding an edge.
+    pub fn add_edge(&mut self, from: Id, to: Id, weight: W) -> &mut Self {
+        self.operations.push(Operation::AddEdge(from, to, weight));
+        self
+    }
+
+    /// Record deleting an edge.
+    pub fn delete_edge(&mut self, from: Id, to: Id) -> &mut Self {
+        self.operations.push(Operation::DeleteEdge(from, to));
+        self
+    }
+
+    /// Replay the recorded operations in order and return the resulting diff.
+    ///
+    /// Fails if an edge is added to a node deleted earlier, or if the final diff has an edge
+    /// referencing a deleted node.
+    pub fn build(self) -> Result<GraphDiff<Id, T, W>, GraphDiffError> {
+        let mut diff = GraphDiff::default();
+        for operation in self.operations {
+            match operation {
+                Operation::AddNode(id, update) => diff.add_or_update_node(&id, update),
+                Operation::DeleteNode(id) => diff.delete_node_unchecked(id),
+                Operation::AddEdge(from, to, weight) => diff.add_edge(&from, &to, weight)?,
+                Operation::DeleteEdge(from, to) => diff.delete_edge(&from, &to),
+            }
+        }
+        GraphDiff::try_from_diffs(diff.nodes, diff.edges)
+    }
+}
+
+// the tests use `NodeUpdate`, which needs `std`
+#[cfg(all(test, feature = "std"))]
+mod tests {
+
+    use super::*;
+    use crate::node_update::NodeUpdate;
+
+    #[test]
+    fn test_build_consistent() {
+        let mut builder = GraphDiffBuilder::<usize, NodeUpdate>::new();
+        builder
+            .add_node(0, NodeUpdate::default())
+            .add_node(1, NodeUpdate::default())
+            .add_edge(0, 1, 1.0)
+            .delete_node(2)
+            .delete_node(1)
+            .add_node(1, NodeUpdate::default());
+        let diff = builder.build().unwrap();
+
+        let mut expected = GraphDiff::<usize, NodeUpdate>::new();
+        expected.add_node(&0);
+        expected.add_node(&1);
+        expected.add_edge(&0, &1, 1.0).unwrap();
+        expected.delete_node(2);
+        assert_eq!(diff, expected);
+    }
+
+    #[test]
+    fn test_build_inconsistent() {
+        let mut builder = GraphDiffBuilder::<usize, NodeUpdate>::new();
+        builder.add_edge(0, 1, 1.0).delete_node(1);
+        assert!(matches!(
+            builder.build(),
+            Err(GraphDiffError::InconsistentEdge { from, to }) if from == "0" && to == "1"
+        ));
+
+        let mut builder = GraphDiffBuilder::<usize, NodeUpdate>::new();
+        builder.delete_node(1).add_edge(0, 1, 1.0);
+        assert!(matches!(
+            builder.build(),
+            Err(GraphDiffError::EndpointDeleted {
+                from: false,
+                to: true
+            })
+        ));
+    }
+}
@@ -1,63 +1,1057 @@
-use crate::diff::{EdgeDiff, GraphDiff, NodeDiff};
-use hashbrown::{HashMap, HashSet};
+use crate::{
+    diff::{DiffMeta, EdgeDiff, GraphDiff, HashMap, HashSet, NodeDiff},
+    error::GraphDiffError,
+    node_update::NodeUpdate,
+};
+use bincode::Options;
 use serde::{Deserialize, Serialize};
-use std::{hash::Hash, ops::AddAssign};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    hash::Hash,
+    io::Write,
+    ops::AddAssign,
+};
 
 /*
  * GraphDiff (de-)serialization
  */
 
-type SlimDiff<Id> = (
+/// Magic bytes at the start of every serialized `GraphDiff`.
+pub const MAGIC: &[u8; 4] = b"DGDF";
+
+/// Version of the byte format written by `graph_diff_to_bytes`.
+///
+/// The version byte is followed by the `TypeTag`s of the id and weight types, or `0` for
+/// bytes written without them, a `DiffMeta` section and the diff encoded with bincode through
+/// its serde representation. The `DiffMeta` section is prefixed with its length, readers skip
+/// metadata they cannot decode, so fields can be added to `DiffMeta` without a new version.
+/// Bytes without a header are the format of drisk_api 0.0.9, see `graph_diff_to_bytes_legacy`.
+pub const FORMAT_VERSION: u8 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + 3;
+
+/// Identifies a node id or edge weight type in the header of serialized bytes.
+///
+/// Lets `bytes_to_graph_diff_tagged` reject bytes written by `graph_diff_to_bytes_tagged`
+/// with different id or weight types rather than misinterpreting them. The other functions
+/// write and read untagged bytes and work with any id and weight type. Tag `0` marks an
+/// untagged type, tags from 128 up are free for user-defined types.
+pub trait TypeTag {
+    const TAG: u8;
+}
+
+macro_rules! impl_type_tag {
+    ($($ty: ty => $tag: expr),* $(,)?) => {
+        $(impl TypeTag for $ty {
+            const TAG: u8 = $tag;
+        })*
+    };
+}
+
+impl_type_tag!(
+    u8 => 1,
+    u16 => 2,
+    u32 => 3,
+    u64 => 4,
+    usize => 5,
+    i8 => 6,
+    i16 => 7,
+    i32 => 8,
+    i64 => 9,
+    isize => 10,
+    f32 => 16,
+    f64 => 17,
+    uuid::Uuid => 32,
+);
+
+/// Tag written in place of a `TypeTag` by the untagged functions, never checked by readers.
+const UNTAGGED: u8 = 0;
+
+/// Error returned when bytes do not start with a valid header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FormatError {
+    /// The bytes do not start with `MAGIC`.
+    InvalidMagic,
+    /// The bytes end within the header or the `DiffMeta` section.
+    Truncated,
+    /// The format version is not supported by this version of the library.
+    UnsupportedVersion(u8),
+    /// The bytes were written with a different id type.
+    IdTypeMismatch { expected: u8, found: u8 },
+    /// The bytes were written with a different weight type.
+    WeightTypeMismatch { expected: u8, found: u8 },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::InvalidMagic => write!(f, "bytes are not a serialized graph diff"),
+            FormatError::Truncated => write!(f, "truncated graph diff header"),
+            FormatError::UnsupportedVersion(version) => {
+                write!(f, "unsupported graph diff format version {}", version)
+            }
+            FormatError::IdTypeMismatch { expected, found } => {
+                write!(f, "expected id type tag {}, found {}", expected, found)
+            }
+            FormatError::WeightTypeMismatch { expected, found } => {
+                write!(f, "expected weight type tag {}, found {}", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Check the header of serialized bytes against the expected id and weight tags and return
+/// the payload following the header.
+fn strip_header(bytes: &[u8], expected: [u8; 2]) -> Result<&[u8], FormatError> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(FormatError::InvalidMagic);
+    }
+    match bytes.get(MAGIC.len()..HEADER_LEN) {
+        Some(&[FORMAT_VERSION, id, weight]) => {
+            check_tags(expected, [id, weight])?;
+            Ok(&bytes[HEADER_LEN..])
+        }
+        Some(&[version, ..]) => Err(FormatError::UnsupportedVersion(version)),
+        _ => match bytes.get(MAGIC.len()) {
+            Some(&version) if version != FORMAT_VERSION => {
+                Err(FormatError::UnsupportedVersion(version))
+            }
+            _ => Err(FormatError::Truncated),
+        },
+    }
+}
+
+/// Compare id and weight tags, skipping those that are untagged on either side.
+fn check_tags(expected: [u8; 2], found: [u8; 2]) -> Result<(), FormatError> {
+    let differs = |expected, found| expected != UNTAGGED && found != UNTAGGED && expected != found;
+    if differs(expected[0], found[0]) {
+        return Err(FormatError::IdTypeMismatch {
+            expected: expected[0],
+            found: found[0],
+        });
+    }
+    if differs(expected[1], found[1]) {
+        return Err(FormatError::WeightTypeMismatch {
+            expected: expected[1],
+            found: found[1],
+        });
+    }
+    Ok(())
+}
+
+/// Payload of the headerless format of drisk_api 0.0.9, which the dRISK Edge server reads
+/// and writes.
+type SlimDiff<Id, W> = (
     HashMap<Id, String>, // JSON new node properties (serde field skip)
     HashSet<Id>,         // deleted node ids
-    EdgeDiff<Id>,        // EdgeDiff
+    EdgeDiff<Id, W>,     // EdgeDiff
 );
 
 /// Serialize a `GraphDiff` to a byte vector.
-pub fn graph_diff_to_bytes<Id, T>(
-    diff: &GraphDiff<Id, T>,
-) -> Result<Vec<u8>, Box<dyn std::error::Error>>
+///
+/// The bytes start with `MAGIC`, the `FORMAT_VERSION` byte and two untagged type tags,
+/// followed by the `DiffMeta` of the diff prefixed with its length as a little endian `u64`,
+/// and the nodes and edges.
+///
+/// With the `ordered` feature the nodes and edges are written in a fixed order, so equal
+/// diffs give equal bytes. The bytes do not keep the insertion order the iterators of
+/// `GraphDiff` follow under the feature, and `graph_diff_to_bytes_canonical` gives bytes in
+/// id order for ids that are `Ord`.
+pub fn graph_diff_to_bytes<Id, T>(diff: &GraphDiff<Id, T>) -> Result<Vec<u8>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize,
+    T: AddAssign + Default + Serialize,
+{
+    let mut bytes = Vec::new();
+    graph_diff_to_writer(diff, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Serialize a `GraphDiff` with any weight type to a byte vector tagged with its id and
+/// weight types.
+///
+/// Same format as `graph_diff_to_bytes`, with the `TypeTag`s of `Id` and `W` in the header.
+/// `bytes_to_graph_diff_tagged` then rejects the bytes when read with other types.
+pub fn graph_diff_to_bytes_tagged<Id, T, W>(
+    diff: &GraphDiff<Id, T, W>,
+) -> Result<Vec<u8>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize + TypeTag,
+    T: AddAssign + Default + Serialize,
+    W: Copy + PartialEq + Serialize + TypeTag,
+{
+    let mut bytes = Vec::new();
+    write_graph_diff(diff, &mut bytes, [Id::TAG, W::TAG])?;
+    Ok(bytes)
+}
+
+/// Serialize a `GraphDiff` to the headerless byte format of drisk_api 0.0.9.
+///
+/// This is the format the dRISK Edge server reads, with each node update encoded as a JSON
+/// string. It has no header, so the `DiffMeta` of the diff is not written. Read it back with
+/// `bytes_to_graph_diff`.
+pub fn graph_diff_to_bytes_legacy<Id, T>(diff: &GraphDiff<Id, T>) -> Result<Vec<u8>, GraphDiffError>
 where
     Id: Copy + Eq + Hash + Serialize,
     T: AddAssign + Default + Serialize,
 {
-    // make use of serde skip fields
     let mut json_map: HashMap<Id, String> = HashMap::new();
-    for (k, v) in diff.new_or_updated_nodes() {
-        let json_str = serde_json::to_string(v)?;
-        json_map.insert(*k, json_str);
+    for (id, update) in diff.iter_new_or_updated_nodes() {
+        let json = serde_json::to_string(update).map_err(GraphDiffError::serialize)?;
+        json_map.insert(*id, json);
     }
-    Ok(bincode::serialize(&(
-        json_map,
-        diff.deleted_nodes(),
-        diff.edges(),
-    ))?)
+    bincode::serialize(&(json_map, diff.deleted_nodes(), diff.edges()))
+        .map_err(GraphDiffError::serialize)
+}
+
+/// Serialize a `GraphDiff` incrementally to a writer.
+///
+/// Produces the same bytes as `graph_diff_to_bytes` without buffering them in memory.
+pub fn graph_diff_to_writer<Id, T, Out>(
+    diff: &GraphDiff<Id, T>,
+    writer: Out,
+) -> Result<(), GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize,
+    T: AddAssign + Default + Serialize,
+    Out: Write,
+{
+    write_graph_diff(diff, writer, [UNTAGGED; 2])
+}
+
+/// Write a `GraphDiff` to an async writer as a frame: the length of its
+/// `graph_diff_to_bytes` output as a little endian `u64`, followed by those bytes.
+///
+/// Frames let several diffs be sent over one connection, read them back with
+/// `graph_diff_from_async_reader`.
+#[cfg(feature = "tokio")]
+pub async fn graph_diff_to_async_writer<Id, T, W, Out>(
+    diff: &GraphDiff<Id, T, W>,
+    mut writer: Out,
+) -> Result<(), GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize,
+    T: AddAssign + Default + Serialize,
+    W: Copy + PartialEq + Serialize,
+    Out: tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::AsyncWriteExt;
+
+    let mut bytes = Vec::new();
+    write_graph_diff(diff, &mut bytes, [UNTAGGED; 2])?;
+    let len = (bytes.len() as u64).to_le_bytes();
+    (writer.write_all(&len).await).map_err(GraphDiffError::serialize)?;
+    (writer.write_all(&bytes).await).map_err(GraphDiffError::serialize)
+}
+
+/// Read a frame written by `graph_diff_to_async_writer` from an async reader.
+///
+/// Returns `None` if the reader is at its end, and fails if it ends within a frame.
+#[cfg(feature = "tokio")]
+pub async fn graph_diff_from_async_reader<Id, T, W, In>(
+    mut reader: In,
+) -> Result<Option<GraphDiff<Id, T, W>>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + for<'de> Deserialize<'de>,
+    for<'a> T: AddAssign + Default + Deserialize<'a> + Serialize,
+    W: Copy + PartialEq + for<'de> Deserialize<'de>,
+    In: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+
+    let truncated = || GraphDiffError::deserialize("truncated frame");
+    let mut len = [0; 8];
+    let mut read = 0;
+    while read < len.len() {
+        match reader.read(&mut len[read..]).await {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(truncated()),
+            Ok(n) => read += n,
+            Err(e) => return Err(GraphDiffError::deserialize(e)),
+        }
+    }
+    let len = u64::from_le_bytes(len);
+    // not preallocated, the length has not been checked yet
+    let mut bytes = Vec::new();
+    let body = (&mut reader).take(len).read_to_end(&mut bytes).await;
+    body.map_err(GraphDiffError::deserialize)?;
+    if (bytes.len() as u64) < len {
+        return Err(truncated());
+    }
+    read_graph_diff(&bytes, [UNTAGGED; 2]).map(Some)
+}
+
+/// Write the header with the given id and weight tags, the `DiffMeta` section and the diff.
+fn write_graph_diff<Id, T, W, Out>(
+    diff: &GraphDiff<Id, T, W>,
+    mut writer: Out,
+    tags: [u8; 2],
+) -> Result<(), GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize,
+    T: AddAssign + Default + Serialize,
+    W: Copy + PartialEq + Serialize,
+    Out: Write,
+{
+    write_header(&mut writer, diff.meta(), tags)?;
+    #[cfg(feature = "ordered")]
+    {
+        let bytes = sorted_graph_diff(diff)?;
+        writer.write_all(&bytes).map_err(GraphDiffError::serialize)
+    }
+    #[cfg(not(feature = "ordered"))]
+    bincode::serialize_into(writer, diff).map_err(GraphDiffError::serialize)
+}
+
+/// Bincode of a `GraphDiff` with the entries of each map and set sorted by their own
+/// bincode, so the bytes do not depend on hash map iteration order. Readers see the same
+/// format, only the entry order differs.
+#[cfg(feature = "ordered")]
+fn sorted_graph_diff<Id, T, W>(diff: &GraphDiff<Id, T, W>) -> Result<Vec<u8>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize,
+    T: AddAssign + Default + Serialize,
+    W: Copy + PartialEq + Serialize,
+{
+    let mut out = Vec::new();
+    let nodes = diff.new_or_updated_nodes().iter();
+    write_sorted(nodes.map(|entry| encode_entry(&entry)), &mut out)?;
+    write_sorted_rest(diff, &mut out)?;
+    Ok(out)
+}
+
+/// Write the fields of a `GraphDiff` after its new or updated nodes, sorted as in
+/// `sorted_graph_diff`.
+#[cfg(feature = "ordered")]
+fn write_sorted_rest<Id, T, W>(
+    diff: &GraphDiff<Id, T, W>,
+    out: &mut Vec<u8>,
+) -> Result<(), GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize,
+    T: AddAssign + Default + Serialize,
+    W: Copy + PartialEq + Serialize,
+{
+    let deleted = diff.deleted_nodes().iter();
+    write_sorted(deleted.map(|entry| encode_entry(&entry)), out)?;
+    write_sorted(
+        diff.new_or_updated_edges().iter().map(|(from, to)| {
+            let mut entry = encode_entry(from)?;
+            write_sorted(to.iter().map(|entry| encode_entry(&entry)), &mut entry)?;
+            Ok(entry)
+        }),
+        out,
+    )?;
+    write_sorted(
+        diff.deleted_edges().iter().map(|(from, to)| {
+            let mut entry = encode_entry(from)?;
+            write_sorted(to.iter().map(|entry| encode_entry(&entry)), &mut entry)?;
+            Ok(entry)
+        }),
+        out,
+    )
+}
+
+#[cfg(feature = "ordered")]
+fn encode_entry(entry: &impl Serialize) -> Result<Vec<u8>, GraphDiffError> {
+    bincode::serialize(entry).map_err(GraphDiffError::serialize)
+}
+
+/// Write encoded map or set entries in sorted order the way bincode writes a map or set:
+/// their number followed by the entries.
+#[cfg(feature = "ordered")]
+fn write_sorted(
+    entries: impl Iterator<Item = Result<Vec<u8>, GraphDiffError>>,
+    out: &mut Vec<u8>,
+) -> Result<(), GraphDiffError> {
+    let mut entries = entries.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_unstable();
+    out.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+    entries
+        .iter()
+        .for_each(|entry| out.extend_from_slice(entry));
+    Ok(())
+}
+
+/// Write the header with the given id and weight tags and the `DiffMeta` section.
+fn write_header<Out: Write>(
+    mut writer: Out,
+    meta: Option<&DiffMeta>,
+    tags: [u8; 2],
+) -> Result<(), GraphDiffError> {
+    let meta = bincode::serialize(&meta).map_err(GraphDiffError::serialize)?;
+    writer
+        .write_all(MAGIC)
+        .and_then(|_| writer.write_all(&[FORMAT_VERSION, tags[0], tags[1]]))
+        .and_then(|_| writer.write_all(&(meta.len() as u64).to_le_bytes()))
+        .and_then(|_| writer.write_all(&meta))
+        .map_err(GraphDiffError::serialize)
+}
+
+/// Split the `DiffMeta` section off a payload. Trailing bytes of the section, written by
+/// later versions, are ignored, and a section that cannot be decoded reads as no metadata.
+fn strip_meta(payload: &[u8]) -> Result<(Option<DiffMeta>, &[u8]), GraphDiffError> {
+    let (len, payload) = payload
+        .split_first_chunk::<8>()
+        .ok_or(FormatError::Truncated)?;
+    let len = usize::try_from(u64::from_le_bytes(*len)).map_err(|_| FormatError::Truncated)?;
+    if payload.len() < len {
+        return Err(FormatError::Truncated.into());
+    }
+    let (meta, payload) = payload.split_at(len);
+    Ok((bincode::deserialize(meta).unwrap_or(None), payload))
+}
+
+/// Serialize a `GraphDiff` to bytes that depend only on its contents.
+///
+/// Same format as `graph_diff_to_bytes`, but nodes and edges are written in id order, so
+/// equal diffs give equal bytes regardless of hash map iteration order. Useful for hashing
+/// diffs, e.g. as cache keys. This relies on `T` serializing deterministically, as
+/// `NodeUpdate` does.
+pub fn graph_diff_to_bytes_canonical<Id, T>(
+    diff: &GraphDiff<Id, T>,
+) -> Result<Vec<u8>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Ord + Serialize,
+    T: AddAssign + Default + Serialize,
+{
+    let (nodes, edges) = (diff.nodes(), diff.edges());
+    let canonical = CanonicalDiff {
+        nodes: CanonicalNodeDiff {
+            new_or_updated: nodes.get_new_or_updated().iter().collect(),
+            deleted: nodes.get_deleted().iter().collect(),
+        },
+        edges: CanonicalEdgeDiff {
+            new_or_updated: edges
+                .get_new_or_updated()
+                .iter()
+                .map(|(from, to)| (from, to.iter().collect()))
+                .collect(),
+            deleted: edges
+                .get_deleted()
+                .iter()
+                .map(|(from, to)| (from, to.iter().collect()))
+                .collect(),
+        },
+    };
+    let mut bytes = Vec::new();
+    write_header(&mut bytes, diff.meta(), [UNTAGGED; 2])?;
+    bincode::serialize_into(&mut bytes, &canonical).map_err(GraphDiffError::serialize)?;
+    Ok(bytes)
+}
+
+/// Serialize a `GraphDiff` to bytes with the deleted nodes and edges in id order.
+///
+/// Same format as `graph_diff_to_bytes`. Diffs that only delete nodes and edges give equal
+/// bytes when they are equal, new or updated nodes and edges are still written in hash map
+/// order. Use `graph_diff_to_bytes_canonical` to sort those as well.
+pub fn graph_diff_to_bytes_sorted_deletions<Id, T, W>(
+    diff: &GraphDiff<Id, T, W>,
+) -> Result<Vec<u8>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Ord + Serialize,
+    T: AddAssign + Default + Serialize,
+    W: Copy + PartialEq + Serialize,
+{
+    let deleted_nodes: BTreeSet<_> = diff.iter_deleted_nodes().collect();
+    let deleted_edges: BTreeMap<_, BTreeSet<_>> = diff
+        .deleted_edges()
+        .iter()
+        .map(|(from, to)| (from, to.iter().collect()))
+        .collect();
+    // fields in the order of the serde representation of `GraphDiff`
+    let sorted = (
+        diff.new_or_updated_nodes(),
+        deleted_nodes,
+        diff.new_or_updated_edges(),
+        deleted_edges,
+    );
+    let mut bytes = Vec::new();
+    write_header(&mut bytes, diff.meta(), [UNTAGGED; 2])?;
+    bincode::serialize_into(&mut bytes, &sorted).map_err(GraphDiffError::serialize)?;
+    Ok(bytes)
+}
+
+/// Sorted view of a `GraphDiff` with the same serde representation.
+#[derive(Serialize)]
+struct CanonicalDiff<'a, Id, T, W> {
+    nodes: CanonicalNodeDiff<'a, Id, T>,
+    edges: CanonicalEdgeDiff<'a, Id, W>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CanonicalNodeDiff<'a, Id, T> {
+    new_or_updated: BTreeMap<&'a Id, &'a T>,
+    deleted: BTreeSet<&'a Id>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CanonicalEdgeDiff<'a, Id, W> {
+    new_or_updated: BTreeMap<&'a Id, BTreeMap<&'a Id, &'a W>>,
+    deleted: BTreeMap<&'a Id, BTreeSet<&'a Id>>,
 }
 
 /// Deserialize a `GraphDiff` from a byte slice.
-pub fn bytes_to_graph_diff<Id, T>(
+///
+/// Bytes that do not start with `MAGIC` are read as the headerless format of drisk_api
+/// 0.0.9, see `graph_diff_to_bytes_legacy`. Fails with `GraphDiffError::Format` if the bytes
+/// are neither. Type tags in the header are not checked, use `bytes_to_graph_diff_tagged`
+/// for that and for weight types other than `f32`.
+pub fn bytes_to_graph_diff<Id, T>(bytes: &[u8]) -> Result<GraphDiff<Id, T>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + for<'de> Deserialize<'de>,
+    for<'a> T: AddAssign + Default + Deserialize<'a> + Serialize,
+{
+    read_graph_diff(bytes, [UNTAGGED; 2])
+}
+
+/// Deserialize a `GraphDiff` from a byte slice, checking the type tags in the header.
+///
+/// Like `bytes_to_graph_diff`, but fails with `FormatError::IdTypeMismatch` or
+/// `FormatError::WeightTypeMismatch` if the bytes were written by
+/// `graph_diff_to_bytes_tagged` with other id or weight types. Untagged bytes are read
+/// without the check, and headerless bytes only with `f32` weights.
+pub fn bytes_to_graph_diff_tagged<Id, T, W>(
     bytes: &[u8],
-) -> Result<GraphDiff<Id, T>, Box<dyn std::error::Error>>
+) -> Result<GraphDiff<Id, T, W>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + for<'de> Deserialize<'de> + TypeTag,
+    for<'a> T: AddAssign + Default + Deserialize<'a> + Serialize,
+    W: Copy + PartialEq + for<'de> Deserialize<'de> + TypeTag,
+{
+    read_graph_diff(bytes, [Id::TAG, W::TAG])
+}
+
+fn read_graph_diff<Id, T, W>(
+    bytes: &[u8],
+    expected: [u8; 2],
+) -> Result<GraphDiff<Id, T, W>, GraphDiffError>
 where
     Id: Copy + Eq + Hash + for<'de> Deserialize<'de>,
     for<'a> T: AddAssign + Default + Deserialize<'a> + Serialize,
+    W: Copy + PartialEq + for<'de> Deserialize<'de>,
+{
+    if !bytes.starts_with(MAGIC) {
+        // a headerless payload starts with the length of its node map, which is never as
+        // large as the magic read as a little endian `u64`
+        check_tags(expected, [UNTAGGED, f32::TAG])?;
+        return slim_diff_to_graph_diff(bytes).map_err(|_| FormatError::InvalidMagic.into());
+    }
+    let (meta, payload) = strip_meta(strip_header(bytes, expected)?)?;
+    let mut diff: GraphDiff<Id, T, W> =
+        bincode::deserialize(payload).map_err(GraphDiffError::deserialize)?;
+    diff.set_meta(meta);
+    Ok(diff)
+}
+
+/// Deserialize the headerless `SlimDiff` payload of drisk_api 0.0.9.
+fn slim_diff_to_graph_diff<Id, T, W>(payload: &[u8]) -> Result<GraphDiff<Id, T, W>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + for<'de> Deserialize<'de>,
+    for<'a> T: AddAssign + Default + Deserialize<'a>,
+    W: Copy + PartialEq + for<'de> Deserialize<'de>,
 {
-    let deserialized: SlimDiff<Id> = bincode::deserialize(bytes)?;
+    let deserialized: SlimDiff<Id, W> =
+        bincode::deserialize(payload).map_err(GraphDiffError::deserialize)?;
     let mut new_or_updated: HashMap<Id, T> = HashMap::new();
     for (id, json) in deserialized.0 {
-        new_or_updated.insert(id, serde_json::from_str::<T>(&json)?);
+        let update = serde_json::from_str::<T>(&json).map_err(GraphDiffError::deserialize)?;
+        new_or_updated.insert(id, update);
+    }
+    Ok(GraphDiff::from_diffs(
+        NodeDiff::new(new_or_updated, deserialized.1),
+        deserialized.2,
+    ))
+}
+
+/// Serialize a `GraphDiff` into chunks of at most `max_bytes` bytes each.
+///
+/// Every chunk is a regular serialized diff holding part of the nodes and edges, readable
+/// with `bytes_to_graph_diff`. Chunks of a consistent diff are consistent on their own and
+/// can be combined with `+=` in any order, see `graph_diff_from_chunks`. Fails if a single
+/// node update or edge does not fit into `max_bytes`.
+pub fn graph_diff_to_chunks<Id, T>(
+    diff: &GraphDiff<Id, T>,
+    max_bytes: usize,
+) -> Result<Vec<Vec<u8>>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize,
+    T: AddAssign + Clone + Default + Serialize,
+{
+    let mut chunks = Chunks::new(max_bytes);
+    for (id, update) in diff.iter_new_or_updated_nodes() {
+        let len = serialized_len(&(id, update))?;
+        chunks
+            .reserve(|_| len)?
+            .add_or_update_node(id, update.clone());
+    }
+    for id in diff.iter_deleted_nodes() {
+        let len = serialized_len(id)?;
+        chunks.reserve(|_| len)?.delete_node_unchecked(*id);
+    }
+    // edges are grouped by `from` node, which takes a length and the id per chunk
+    let from_len = |from: &Id| Ok::<_, GraphDiffError>(serialized_len(from)? + 8);
+    for (from, to, weight) in diff.iter_new_or_updated_edges() {
+        let (len, from_len) = (serialized_len(&(to, weight))?, from_len(from)?);
+        let grouped = |chunk: &GraphDiff<Id, T>| chunk.outgoing(from).is_some();
+        let chunk = chunks.reserve(|chunk| len + if grouped(chunk) { 0 } else { from_len })?;
+        chunk.add_edge(from, to, *weight)?;
+    }
+    for (from, to) in diff.iter_deleted_edges() {
+        let (len, from_len) = (serialized_len(to)?, from_len(from)?);
+        let grouped = |chunk: &GraphDiff<Id, T>| chunk.outgoing_deleted(from).is_some();
+        let chunk = chunks.reserve(|chunk| len + if grouped(chunk) { 0 } else { from_len })?;
+        chunk.delete_edge(from, to);
+    }
+    chunks.finish()
+}
+
+/// Deserialize chunks written by `graph_diff_to_chunks` and combine them into one diff.
+pub fn graph_diff_from_chunks<Id, T, C>(chunks: C) -> Result<GraphDiff<Id, T>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + for<'de> Deserialize<'de>,
+    for<'a> T: AddAssign + Default + Deserialize<'a> + Serialize,
+    C: IntoIterator,
+    C::Item: AsRef<[u8]>,
+{
+    let mut diff = GraphDiff::new();
+    for chunk in chunks {
+        diff += bytes_to_graph_diff(chunk.as_ref())?;
+    }
+    Ok(diff)
+}
+
+fn serialized_len(value: &impl Serialize) -> Result<usize, GraphDiffError> {
+    let len = bincode::serialized_size(value).map_err(GraphDiffError::serialize)?;
+    Ok(len as usize)
+}
+
+impl<Id: Copy + Eq + Hash + Serialize> GraphDiff<Id, NodeUpdate> {
+    /// Approximate length of `graph_diff_to_bytes(self)`, without serializing the diff.
+    ///
+    /// Exact for ids that all serialize to the same length, such as integers and UUIDs.
+    /// Meant to decide whether to chunk a diff.
+    pub fn estimated_byte_size(&self) -> usize {
+        // every id serializes to the same length, measure one
+        let mut ids = (self.new_or_updated_nodes().keys())
+            .chain(self.deleted_nodes())
+            .chain(self.new_or_updated_edges().keys())
+            .chain(self.deleted_edges().keys());
+        let Some(id) = ids.next() else {
+            return Chunks::<Id, NodeUpdate>::EMPTY_LEN;
+        };
+        let id_len = serialized_len(id).unwrap_or(std::mem::size_of::<Id>());
+        let meta_len = self.meta.as_ref().map_or(0, |meta| {
+            let producer = meta.producer.as_ref().map_or(0, |p| 8 + p.len());
+            3 + producer
+                + 8 * meta.timestamp.is_some() as usize
+                + 4 * meta.schema_version.is_some() as usize
+        });
+
+        let nodes: usize = self
+            .iter_new_or_updated_nodes()
+            .map(|(_, update)| id_len + estimated_update_len(update))
+            .sum();
+        let deleted_nodes = self.deleted_nodes().len() * id_len;
+        let edges: usize = self
+            .new_or_updated_edges()
+            .values()
+            .map(|to_weight| id_len + 8 + to_weight.len() * (id_len + 4))
+            .sum();
+        let deleted_edges: usize = self
+            .deleted_edges()
+            .values()
+            .map(|to| id_len + 8 + to.len() * id_len)
+            .sum();
+        Chunks::<Id, NodeUpdate>::EMPTY_LEN
+            + meta_len
+            + nodes
+            + deleted_nodes
+            + edges
+            + deleted_edges
+    }
+}
+
+/// Length of a `NodeUpdate` in bincode: the number of set fields, and a variant tag and
+/// the value of each set field.
+fn estimated_update_len(update: &NodeUpdate) -> usize {
+    let string = |s: &Option<String>| s.as_ref().map_or(0, |s| 4 + 8 + s.len());
+    let byte = |b: Option<u8>| 5 * b.is_some() as usize;
+    let cleared = match update.cleared.len() {
+        0 => 0,
+        len => 4 + 8 + 4 * len,
+    };
+    let extra = update.extra.as_ref().map_or(0, |extra| {
+        let entries: usize = extra
+            .iter()
+            .map(|(key, value)| 8 + key.len() + estimated_value_len(value))
+            .sum();
+        4 + 8 + entries
+    });
+    8 + string(&update.label)
+        + 8 * update.size.is_some() as usize
+        + string(&update.url)
+        + byte(update.red)
+        + byte(update.green)
+        + byte(update.blue)
+        + byte(update.alpha)
+        + byte(update.show_label.map(u8::from))
+        + cleared
+        + extra
+}
+
+/// Length of an extra property value in bincode: a variant tag and the value.
+fn estimated_value_len(value: &serde_json::Value) -> usize {
+    use serde_json::Value;
+    4 + match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 8,
+        Value::String(s) => 8 + s.len(),
+        Value::Array(values) => 8 + values.iter().map(estimated_value_len).sum::<usize>(),
+        Value::Object(entries) => {
+            let entries: usize = entries
+                .iter()
+                .map(|(key, value)| 8 + key.len() + estimated_value_len(value))
+                .sum();
+            8 + entries
+        }
+    }
+}
+
+/// Chunks being filled by `graph_diff_to_chunks`, tracking the serialized length of the
+/// current chunk.
+struct Chunks<Id: Copy + Eq + Hash, T: AddAssign + Default> {
+    max_bytes: usize,
+    chunk: GraphDiff<Id, T>,
+    len: usize,
+    chunks: Vec<Vec<u8>>,
+}
+
+impl<Id, T> Chunks<Id, T>
+where
+    Id: Copy + Eq + Hash + Serialize,
+    T: AddAssign + Default + Serialize,
+{
+    /// Length of an empty diff: the header, an empty `DiffMeta` section and the lengths of
+    /// its four maps and sets.
+    const EMPTY_LEN: usize = HEADER_LEN + 8 + 1 + 4 * 8;
+
+    fn new(max_bytes: usize) -> Self {
+        Chunks {
+            max_bytes,
+            chunk: GraphDiff::new(),
+            len: Self::EMPTY_LEN,
+            chunks: Vec::new(),
+        }
+    }
+
+    /// Make room for an entry whose serialized length in a chunk is `len(chunk)`, starting
+    /// a new chunk if the current one is full. Returns the chunk to add the entry to.
+    fn reserve(
+        &mut self,
+        len: impl Fn(&GraphDiff<Id, T>) -> usize,
+    ) -> Result<&mut GraphDiff<Id, T>, GraphDiffError> {
+        if self.len + len(&self.chunk) > self.max_bytes && !self.chunk.is_empty() {
+            self.flush()?;
+        }
+        self.len += len(&self.chunk);
+        if self.len > self.max_bytes {
+            return Err(GraphDiffError::serialize(format!(
+                "a node or edge does not fit into a chunk of {} bytes",
+                self.max_bytes
+            )));
+        }
+        Ok(&mut self.chunk)
+    }
+
+    fn flush(&mut self) -> Result<(), GraphDiffError> {
+        let bytes = graph_diff_to_bytes(&std::mem::take(&mut self.chunk))?;
+        debug_assert_eq!(bytes.len(), self.len);
+        self.chunks.push(bytes);
+        self.len = Self::EMPTY_LEN;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<Vec<u8>>, GraphDiffError> {
+        if !self.chunk.is_empty() {
+            self.flush()?;
+        }
+        Ok(self.chunks)
+    }
+}
+
+/// Error of `to_bytes_wasm` and `from_bytes_wasm`.
+///
+/// Unlike `GraphDiffError` it holds no boxed errors, so it is easy to convert into the error
+/// types of WASM bindings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WasmError {
+    /// The bytes do not start with a supported header.
+    Format(FormatError),
+    /// Serializing the diff failed, with the message of the underlying error.
+    Serialize(String),
+    /// Deserializing the diff failed, with the message of the underlying error.
+    Deserialize(String),
+}
+
+impl fmt::Display for WasmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasmError::Format(e) => write!(f, "{}", e),
+            WasmError::Serialize(e) => write!(f, "Failed to serialize graph diff: {}", e),
+            WasmError::Deserialize(e) => write!(f, "Failed to deserialize graph diff: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WasmError {}
+
+impl From<GraphDiffError> for WasmError {
+    fn from(e: GraphDiffError) -> Self {
+        match e {
+            GraphDiffError::Format(e) => WasmError::Format(e),
+            GraphDiffError::Serialize(e) => WasmError::Serialize(e.to_string()),
+            e => WasmError::Deserialize(e.to_string()),
+        }
+    }
+}
+
+/// Serialize a `GraphDiff` like `graph_diff_to_bytes`, failing with a `WasmError`.
+///
+/// Only uses dependencies that build for `wasm32-unknown-unknown`.
+pub fn to_bytes_wasm<Id, T>(diff: &GraphDiff<Id, T>) -> Result<Vec<u8>, WasmError>
+where
+    Id: Copy + Eq + Hash + Serialize,
+    T: AddAssign + Default + Serialize,
+{
+    Ok(graph_diff_to_bytes(diff)?)
+}
+
+/// Deserialize a `GraphDiff` like `bytes_to_graph_diff`, failing with a `WasmError`.
+pub fn from_bytes_wasm<Id, T>(bytes: &[u8]) -> Result<GraphDiff<Id, T>, WasmError>
+where
+    Id: Copy + Eq + Hash + for<'de> Deserialize<'de>,
+    for<'a> T: AddAssign + Default + Deserialize<'a> + Serialize,
+{
+    Ok(bytes_to_graph_diff(bytes)?)
+}
+
+/// Magic bytes at the start of a `GraphDiff` serialized by `graph_diff_to_bytes_compressed`.
+#[cfg(feature = "compression")]
+pub const COMPRESSED_MAGIC: &[u8; 4] = b"DGDZ";
+
+/// Serialize a `GraphDiff` to zstd-compressed bytes at the given compression level.
+///
+/// The bytes start with `COMPRESSED_MAGIC` followed by a zstd frame containing the output
+/// of `graph_diff_to_bytes`. Level `0` selects the zstd default.
+#[cfg(feature = "compression")]
+pub fn graph_diff_to_bytes_compressed<Id, T>(
+    diff: &GraphDiff<Id, T>,
+    level: i32,
+) -> Result<Vec<u8>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize,
+    T: AddAssign + Default + Serialize,
+{
+    let mut bytes = COMPRESSED_MAGIC.to_vec();
+    let mut encoder = zstd::Encoder::new(&mut bytes, level).map_err(GraphDiffError::serialize)?;
+    graph_diff_to_writer(diff, &mut encoder)?;
+    encoder.finish().map_err(GraphDiffError::serialize)?;
+    Ok(bytes)
+}
+
+/// Deserialize a `GraphDiff` from bytes produced by `graph_diff_to_bytes_compressed`.
+///
+/// Uncompressed bytes from `graph_diff_to_bytes` are accepted as well.
+#[cfg(feature = "compression")]
+pub fn bytes_to_graph_diff_compressed<Id, T>(
+    bytes: &[u8],
+) -> Result<GraphDiff<Id, T>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + for<'de> Deserialize<'de>,
+    for<'a> T: AddAssign + Default + Deserialize<'a> + Serialize,
+{
+    match bytes.strip_prefix(COMPRESSED_MAGIC) {
+        Some(compressed) => {
+            let bytes = zstd::decode_all(compressed).map_err(GraphDiffError::deserialize)?;
+            bytes_to_graph_diff(&bytes)
+        }
+        None => bytes_to_graph_diff(bytes),
+    }
+}
+
+/// Magic bytes at the start of a `GraphDiff` serialized with varint encoded ids.
+pub const COMPACT_ID_MAGIC: &[u8; 4] = b"DGDV";
+
+/// Integer id types that `graph_diff_to_bytes_compact_ids` can varint encode.
+///
+/// Small ids then take fewer bytes than their fixed width, which shrinks diffs of graphs with
+/// dense integer ids. UUIDs are always written as 16 bytes and do not implement this trait.
+pub trait CompactId: TypeTag {}
+
+impl CompactId for u16 {}
+impl CompactId for u32 {}
+impl CompactId for u64 {}
+impl CompactId for usize {}
+impl CompactId for i16 {}
+impl CompactId for i32 {}
+impl CompactId for i64 {}
+impl CompactId for isize {}
+
+/// Bincode options of the payload after `COMPACT_ID_MAGIC`.
+fn compact_id_options() -> impl bincode::Options {
+    bincode::DefaultOptions::new().with_varint_encoding()
+}
+
+/// Serialize a `GraphDiff` with integer ids to bytes with varint encoded ids.
+///
+/// The bytes start with `COMPACT_ID_MAGIC` followed by the header and `DiffMeta` section of
+/// `graph_diff_to_bytes`. The nodes and edges are encoded with bincode's varint encoding,
+/// which applies to the ids and to the lengths of the maps and sets as well. Read them with
+/// `bytes_to_graph_diff_compact_ids`.
+pub fn graph_diff_to_bytes_compact_ids<Id, T, W>(
+    diff: &GraphDiff<Id, T, W>,
+) -> Result<Vec<u8>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize + CompactId,
+    T: AddAssign + Default + Serialize,
+    W: Copy + PartialEq + Serialize,
+{
+    let mut bytes = COMPACT_ID_MAGIC.to_vec();
+    write_header(&mut bytes, diff.meta(), [Id::TAG, UNTAGGED])?;
+    (compact_id_options().serialize_into(&mut bytes, diff)).map_err(GraphDiffError::serialize)?;
+    Ok(bytes)
+}
+
+/// Deserialize a `GraphDiff` from bytes produced by `graph_diff_to_bytes_compact_ids`.
+///
+/// Bytes from `graph_diff_to_bytes` are accepted as well.
+pub fn bytes_to_graph_diff_compact_ids<Id, T, W>(
+    bytes: &[u8],
+) -> Result<GraphDiff<Id, T, W>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + for<'de> Deserialize<'de> + CompactId,
+    for<'a> T: AddAssign + Default + Deserialize<'a> + Serialize,
+    W: Copy + PartialEq + for<'de> Deserialize<'de>,
+{
+    let Some(bytes) = bytes.strip_prefix(COMPACT_ID_MAGIC) else {
+        return read_graph_diff(bytes, [Id::TAG, UNTAGGED]);
+    };
+    let (meta, payload) = strip_meta(strip_header(bytes, [Id::TAG, UNTAGGED])?)?;
+    let mut diff: GraphDiff<Id, T, W> =
+        (compact_id_options().deserialize(payload)).map_err(GraphDiffError::deserialize)?;
+    diff.set_meta(meta);
+    Ok(diff)
+}
+
+/// Node updates encoded per task by `graph_diff_to_bytes_parallel`.
+#[cfg(feature = "parallel")]
+const PARALLEL_PART_LEN: usize = 1024;
+
+/// Serialize a `GraphDiff` to bytes, encoding the node updates on several threads.
+///
+/// Produces the same bytes as `graph_diff_to_bytes`, which is faster for small diffs. The
+/// node updates are split into parts of `PARALLEL_PART_LEN` encoded on the rayon thread
+/// pool, the rest of the diff is encoded on the calling thread. With the `ordered` feature
+/// the entries are sorted as in `graph_diff_to_bytes`.
+#[cfg(feature = "parallel")]
+pub fn graph_diff_to_bytes_parallel<Id, T, W>(
+    diff: &GraphDiff<Id, T, W>,
+) -> Result<Vec<u8>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize + Sync,
+    T: AddAssign + Default + Serialize + Sync,
+    W: Copy + PartialEq + Serialize,
+{
+    use rayon::prelude::*;
+
+    let nodes: Vec<_> = diff.iter_new_or_updated_nodes().collect();
+    let mut bytes = Vec::new();
+    write_header(&mut bytes, diff.meta(), [UNTAGGED; 2])?;
+    #[cfg(feature = "ordered")]
+    {
+        // entries are encoded separately to sort them
+        let parts = nodes
+            .par_chunks(PARALLEL_PART_LEN)
+            .map(|part| part.iter().map(bincode::serialize).collect())
+            .collect::<Result<Vec<Vec<_>>, bincode::Error>>()
+            .map_err(GraphDiffError::serialize)?;
+        write_sorted(parts.into_iter().flatten().map(Ok), &mut bytes)?;
+        write_sorted_rest(diff, &mut bytes)?;
     }
-    Ok(GraphDiff {
-        nodes: NodeDiff::new(new_or_updated, deserialized.1),
-        edges: deserialized.2,
-    })
+    #[cfg(not(feature = "ordered"))]
+    {
+        let parts = nodes
+            .par_chunks(PARALLEL_PART_LEN)
+            .map(|part| {
+                let mut bytes = Vec::new();
+                for entry in part {
+                    bincode::serialize_into(&mut bytes, entry)?;
+                }
+                Ok(bytes)
+            })
+            .collect::<Result<Vec<_>, bincode::Error>>()
+            .map_err(GraphDiffError::serialize)?;
+
+        // a struct is encoded as its fields in order and a map as its length and entries, so
+        // this matches the encoding of the whole diff in `graph_diff_to_writer`
+        bytes.extend((nodes.len() as u64).to_le_bytes());
+        for part in parts {
+            bytes.extend(part);
+        }
+        let rest = (
+            diff.deleted_nodes(),
+            diff.new_or_updated_edges(),
+            diff.deleted_edges(),
+        );
+        bincode::serialize_into(&mut bytes, &rest).map_err(GraphDiffError::serialize)?;
+    }
+    Ok(bytes)
+}
+
+/*
+ * GraphDiff JSON (de-)serialization
+ */
+
+/// Serialize a `GraphDiff` to JSON.
+///
+/// The JSON has the shape `{"nodes": {"newOrUpdated": .., "deleted": ..}, "edges": {..}}`
+/// with node updates as nested objects and edge weights as numbers.
+pub fn graph_diff_to_json<Id, T, W>(diff: &GraphDiff<Id, T, W>) -> Result<String, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + Serialize,
+    T: AddAssign + Default + Serialize,
+    W: Serialize,
+{
+    serde_json::to_string(diff).map_err(GraphDiffError::serialize)
+}
+
+/// Deserialize a `GraphDiff` from JSON produced by `graph_diff_to_json`.
+pub fn graph_diff_from_json<Id, T, W>(json: &str) -> Result<GraphDiff<Id, T, W>, GraphDiffError>
+where
+    Id: Copy + Eq + Hash + for<'de> Deserialize<'de>,
+    for<'a> T: AddAssign + Default + Deserialize<'a>,
+    for<'a> W: Deserialize<'a>,
+{
+    serde_json::from_str(json).map_err(GraphDiffError::deserialize)
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
-    use crate::{diff::GraphDiff, node_update::NodeUpdate};
+    use crate::{
+        diff::GraphDiff,
+        node_update::{NodeField, NodeUpdate},
+    };
+    use std::{collections::hash_map::DefaultHasher, hash::Hasher};
+    use uuid::Uuid;
 
     #[test]
     fn test_serialization() {
@@ -77,6 +1071,8 @@ mod tests {
                 ..NodeUpdate::default()
             },
         );
+        diff.get_or_create_mut_node_update(&3)
+            .clear(NodeField::Label);
         diff.add_edge(&1, &2, 0.).unwrap();
         diff.add_edge(&2, &3, 10.).unwrap();
 
@@ -84,4 +1080,644 @@ mod tests {
         let deserialized = bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap();
         assert_eq!(diff, deserialized);
     }
+
+    #[test]
+    fn test_serialization_alpha() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(
+            &1,
+            NodeUpdate {
+                red: Some(255),
+                alpha: Some(128),
+                ..NodeUpdate::default()
+            },
+        );
+        let bytes = graph_diff_to_bytes(&diff).unwrap();
+        let deserialized = bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap();
+        assert_eq!(deserialized.new_or_updated_nodes()[&1].alpha, Some(128));
+
+        // updates serialized before alpha existed
+        let node: NodeUpdate = serde_json::from_str(r#"{"red":255}"#).unwrap();
+        assert_eq!(node.alpha, None);
+    }
+
+    #[test]
+    fn test_bincode_matches_json() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(
+            &1,
+            NodeUpdate {
+                label: Some("test".to_string()),
+                alpha: Some(3),
+                extra: Some(HashMap::from([("key".to_string(), 1.into())])),
+                ..NodeUpdate::default()
+            },
+        );
+        diff.get_or_create_mut_node_update(&1).clear(NodeField::Url);
+        diff.delete_node(2);
+        diff.add_edge(&1, &3, 1.).unwrap();
+        diff.delete_edge(&3, &1);
+
+        let bytes = graph_diff_to_bytes(&diff).unwrap();
+        let (meta, payload) = strip_meta(&bytes[HEADER_LEN..]).unwrap();
+        assert_eq!(meta, None);
+        assert_eq!(payload, bincode::serialize(&diff).unwrap());
+        let json = graph_diff_to_json(&diff).unwrap();
+        let from_bytes = bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap();
+        let from_json = graph_diff_from_json::<usize, NodeUpdate, f32>(&json).unwrap();
+        assert_eq!(from_bytes, from_json);
+        assert_eq!(from_bytes, diff);
+    }
+
+    #[test]
+    fn test_header() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&1);
+        let mut bytes = graph_diff_to_bytes(&diff).unwrap();
+        assert_eq!(&bytes[..4], b"DGDF");
+        assert_eq!(bytes[4..7], [FORMAT_VERSION, UNTAGGED, UNTAGGED]);
+
+        bytes[4] = FORMAT_VERSION + 1;
+        let err = bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap_err();
+        assert!(matches!(
+            err,
+            GraphDiffError::Format(FormatError::UnsupportedVersion(v)) if v == FORMAT_VERSION + 1
+        ));
+
+        bytes[0] = b'X';
+        let err = bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap_err();
+        assert!(matches!(
+            err,
+            GraphDiffError::Format(FormatError::InvalidMagic)
+        ));
+
+        let err = bytes_to_graph_diff::<usize, NodeUpdate>(b"DG").unwrap_err();
+        assert!(matches!(
+            err,
+            GraphDiffError::Format(FormatError::InvalidMagic)
+        ));
+
+        for truncated in [&b"DGDF"[..], &[b'D', b'G', b'D', b'F', FORMAT_VERSION, 0]] {
+            let err = bytes_to_graph_diff::<usize, NodeUpdate>(truncated).unwrap_err();
+            assert!(matches!(
+                err,
+                GraphDiffError::Format(FormatError::Truncated)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_legacy_bytes() {
+        let node = NodeUpdate {
+            label: Some("test".to_string()),
+            red: Some(5),
+            ..NodeUpdate::default()
+        };
+        let mut diff = GraphDiff::<Uuid, NodeUpdate>::new();
+        let ids: Vec<_> = (0..3u128).map(Uuid::from_u128).collect();
+        diff.add_or_update_node(&ids[0], node.clone());
+        diff.delete_node(ids[1]);
+        diff.add_edge(&ids[0], &ids[2], 0.5).unwrap();
+
+        // as written by drisk_api 0.0.9 and the Edge server
+        let json_map: HashMap<Uuid, String> =
+            HashMap::from([(ids[0], r#"{"label":"test","red":5}"#.to_string())]);
+        let released = bincode::serialize(&(json_map, diff.deleted_nodes(), diff.edges())).unwrap();
+        assert_eq!(
+            bytes_to_graph_diff::<Uuid, NodeUpdate>(&released).unwrap(),
+            diff
+        );
+        let bytes = graph_diff_to_bytes_legacy(&diff).unwrap();
+        let (nodes, deleted, edges): SlimDiff<Uuid, f32> = bincode::deserialize(&bytes).unwrap();
+        let node_json: serde_json::Value = serde_json::from_str(&nodes[&ids[0]]).unwrap();
+        assert_eq!(node_json["label"], "test");
+        assert_eq!((&deleted, &edges), (diff.deleted_nodes(), diff.edges()));
+        assert!(matches!(
+            bytes_to_graph_diff_tagged::<Uuid, NodeUpdate, f64>(&released),
+            Err(GraphDiffError::Format(
+                FormatError::WeightTypeMismatch { .. }
+            ))
+        ));
+
+        let empty = GraphDiff::<Uuid, NodeUpdate>::new();
+        let bytes = graph_diff_to_bytes_legacy(&empty).unwrap();
+        assert_eq!(
+            bytes_to_graph_diff::<Uuid, NodeUpdate>(&bytes).unwrap(),
+            empty
+        );
+    }
+
+    #[test]
+    fn test_meta() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&1);
+        let bytes = graph_diff_to_bytes(&diff).unwrap();
+        assert_eq!(
+            bytes_to_graph_diff::<usize, NodeUpdate>(&bytes)
+                .unwrap()
+                .meta(),
+            None
+        );
+
+        let meta = DiffMeta {
+            producer: Some("test".to_string()),
+            timestamp: Some(1_700_000_000),
+            schema_version: Some(2),
+        };
+        diff.set_meta(Some(meta.clone()));
+        let bytes = graph_diff_to_bytes(&diff).unwrap();
+        let deserialized = bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap();
+        assert_eq!(deserialized.meta(), Some(&meta));
+        assert_eq!(deserialized, diff);
+        let canonical = graph_diff_to_bytes_canonical(&diff).unwrap();
+        assert_eq!(
+            bytes_to_graph_diff::<usize, NodeUpdate>(&canonical).unwrap(),
+            diff
+        );
+
+        // metadata fields added by later versions are skipped
+        let mut section = bincode::serialize(&Some(&meta)).unwrap();
+        section.extend_from_slice(b"unknown field");
+        let mut bytes = bytes[..HEADER_LEN].to_vec();
+        bytes.extend_from_slice(&(section.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&section);
+        bytes.extend_from_slice(&bincode::serialize(&diff).unwrap());
+        let deserialized = bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap();
+        assert_eq!(deserialized, diff);
+
+        // and metadata that cannot be decoded is dropped
+        let section = [7u8; 5];
+        let mut unknown = bytes[..HEADER_LEN].to_vec();
+        unknown.extend_from_slice(&(section.len() as u64).to_le_bytes());
+        unknown.extend_from_slice(&section);
+        unknown.extend_from_slice(&bincode::serialize(&diff).unwrap());
+        let deserialized = bytes_to_graph_diff::<usize, NodeUpdate>(&unknown).unwrap();
+        assert_eq!(deserialized.meta(), None);
+        assert_eq!(
+            (deserialized.nodes(), deserialized.edges()),
+            (diff.nodes(), diff.edges())
+        );
+
+        bytes.truncate(HEADER_LEN + 4);
+        assert!(matches!(
+            bytes_to_graph_diff::<usize, NodeUpdate>(&bytes),
+            Err(GraphDiffError::Format(FormatError::Truncated))
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_frames() {
+        let mut diffs = Vec::new();
+        for i in 0..3u32 {
+            let mut diff = GraphDiff::<u32, NodeUpdate>::new();
+            diff.add_node(&i);
+            diff.add_edge(&i, &(i + 1), i as f32).unwrap();
+            diffs.push(diff);
+        }
+        diffs.push(GraphDiff::new());
+
+        // a buffer smaller than a frame, so writing and reading interleave
+        let (mut client, mut server) = tokio::io::duplex(16);
+        let write = async {
+            for diff in diffs.iter() {
+                graph_diff_to_async_writer(diff, &mut client).await.unwrap();
+            }
+            drop(client);
+        };
+        let read = async {
+            let mut read = Vec::new();
+            while let Some(diff) = graph_diff_from_async_reader(&mut server).await.unwrap() {
+                read.push(diff);
+            }
+            read
+        };
+        let ((), read) = tokio::join!(write, read);
+        assert_eq!(read, diffs);
+
+        // truncated within the length and within the body
+        let mut stream = Vec::new();
+        graph_diff_to_async_writer(&diffs[0], &mut stream)
+            .await
+            .unwrap();
+        let result = graph_diff_from_async_reader::<u32, NodeUpdate, f32, _>(&stream[..3]).await;
+        assert!(result.is_err());
+        let body = &stream[..stream.len() - 1];
+        let result = graph_diff_from_async_reader::<u32, NodeUpdate, f32, _>(body).await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_f64_weights() {
+        let weight = 0.1 + 1e-12;
+        let mut diff = GraphDiff::<u32, NodeUpdate, f64>::new();
+        diff.add_edge(&1, &2, weight).unwrap();
+        let bytes = graph_diff_to_bytes_tagged(&diff).unwrap();
+        assert_eq!(bytes[MAGIC.len() + 2], f64::TAG);
+
+        let deserialized = bytes_to_graph_diff_tagged::<u32, NodeUpdate, f64>(&bytes).unwrap();
+        assert_eq!(deserialized.edge_weight(&1, &2), Some(&weight));
+        assert!(matches!(
+            bytes_to_graph_diff_tagged::<u32, NodeUpdate, f32>(&bytes),
+            Err(GraphDiffError::Format(FormatError::WeightTypeMismatch {
+                expected: 16,
+                found: 17
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_wasm_bytes() {
+        let mut diff = GraphDiff::<u32, NodeUpdate>::new();
+        diff.add_node(&1);
+        diff.add_edge(&1, &2, 1.).unwrap();
+        let bytes = to_bytes_wasm(&diff).unwrap();
+        assert_eq!(bytes, graph_diff_to_bytes(&diff).unwrap());
+        assert_eq!(from_bytes_wasm::<u32, NodeUpdate>(&bytes).unwrap(), diff);
+
+        assert_eq!(
+            from_bytes_wasm::<u32, NodeUpdate>(MAGIC),
+            Err(WasmError::Format(FormatError::Truncated))
+        );
+        assert!(matches!(
+            from_bytes_wasm::<u32, NodeUpdate>(&bytes[..bytes.len() - 1]),
+            Err(WasmError::Deserialize(_))
+        ));
+    }
+
+    #[test]
+    fn test_type_tags() {
+        let mut diff = GraphDiff::<u64, NodeUpdate>::new();
+        diff.add_edge(&1, &2, 1.).unwrap();
+        let mut bytes = graph_diff_to_bytes_tagged(&diff).unwrap();
+        assert_eq!(bytes[4..7], [FORMAT_VERSION, u64::TAG, f32::TAG]);
+        assert_eq!(
+            bytes_to_graph_diff::<u64, NodeUpdate>(&bytes).unwrap(),
+            diff
+        );
+
+        let err = bytes_to_graph_diff_tagged::<uuid::Uuid, NodeUpdate, f32>(&bytes).unwrap_err();
+        assert!(matches!(
+            err,
+            GraphDiffError::Format(FormatError::IdTypeMismatch { expected, found })
+                if expected == uuid::Uuid::TAG && found == u64::TAG
+        ));
+
+        bytes[6] = f64::TAG;
+        let err = bytes_to_graph_diff_tagged::<u64, NodeUpdate, f32>(&bytes).unwrap_err();
+        assert!(matches!(
+            err,
+            GraphDiffError::Format(FormatError::WeightTypeMismatch { expected, found })
+                if expected == f32::TAG && found == f64::TAG
+        ));
+
+        // untagged bytes are read by the tagged reader without the check
+        let untagged = graph_diff_to_bytes(&diff).unwrap();
+        assert_eq!(
+            bytes_to_graph_diff_tagged::<u64, NodeUpdate, f32>(&untagged).unwrap(),
+            diff
+        );
+    }
+
+    #[test]
+    fn test_user_defined_types() {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        struct NodeId(u32);
+        #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+        struct Weight(f32);
+        impl TypeTag for Weight {
+            const TAG: u8 = 128;
+        }
+
+        // ids without a `TypeTag` in untagged bytes
+        let mut diff = GraphDiff::<NodeId, NodeUpdate>::new();
+        diff.add_node(&NodeId(1));
+        diff.add_edge(&NodeId(1), &NodeId(2), 0.5).unwrap();
+        let bytes = graph_diff_to_bytes(&diff).unwrap();
+        assert_eq!(
+            bytes_to_graph_diff::<NodeId, NodeUpdate>(&bytes).unwrap(),
+            diff
+        );
+
+        let mut diff = GraphDiff::<u32, NodeUpdate, Weight>::new();
+        diff.add_edge(&1, &2, Weight(0.5)).unwrap();
+        let bytes = graph_diff_to_bytes_tagged(&diff).unwrap();
+        assert_eq!(bytes[MAGIC.len() + 2], Weight::TAG);
+        assert_eq!(
+            bytes_to_graph_diff_tagged::<u32, NodeUpdate, Weight>(&bytes).unwrap(),
+            diff
+        );
+    }
+
+    #[test]
+    fn test_canonical_bytes() {
+        let update = |id: usize| NodeUpdate {
+            size: Some(id as f32),
+            extra: Some(HashMap::from_iter(
+                (0..10).map(|key| (key.to_string(), id.into())),
+            )),
+            ..NodeUpdate::default()
+        };
+        let mut forward = GraphDiff::<usize, NodeUpdate>::new();
+        for id in 0..50 {
+            forward.add_or_update_node(&id, update(id));
+            forward.add_edge(&id, &((id + 1) % 50), id as f32).unwrap();
+        }
+        forward.delete_edge(&0, &7);
+        forward.delete_node(49);
+        let mut backward = GraphDiff::<usize, NodeUpdate>::new();
+        backward.delete_edge(&0, &7);
+        for id in (0..50).rev() {
+            backward.add_or_update_node(&id, update(id));
+            backward.add_edge(&id, &((id + 1) % 50), id as f32).unwrap();
+        }
+        backward.delete_node(49);
+        assert_eq!(forward, backward);
+
+        let hash = |diff: &GraphDiff<usize, NodeUpdate>| {
+            let mut hasher = DefaultHasher::new();
+            graph_diff_to_bytes_canonical(diff)
+                .unwrap()
+                .hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&forward), hash(&backward));
+
+        let bytes = graph_diff_to_bytes_canonical(&forward).unwrap();
+        assert_eq!(
+            bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap(),
+            forward
+        );
+    }
+
+    #[test]
+    fn test_estimated_byte_size() {
+        let empty = GraphDiff::<u64, NodeUpdate>::new();
+        assert_eq!(
+            empty.estimated_byte_size(),
+            graph_diff_to_bytes(&empty).unwrap().len()
+        );
+
+        let mut diff = GraphDiff::<Uuid, NodeUpdate>::new();
+        let ids: Vec<_> = (0..50u128).map(Uuid::from_u128).collect();
+        for (i, id) in ids.iter().enumerate() {
+            let mut update = NodeUpdate {
+                label: Some(format!("node {}", i)),
+                size: Some(i as f32),
+                ..NodeUpdate::default()
+            };
+            if i % 3 == 0 {
+                update.set_color_hex("#ff8800").unwrap();
+                update.clear(NodeField::Url);
+            }
+            if i % 5 == 0 {
+                update.extra = Some(HashMap::from([(
+                    "tags".to_string(),
+                    serde_json::json!(["a", i, {"b": null}]),
+                )]));
+            }
+            diff.add_or_update_node(id, update);
+        }
+        for pair in ids.windows(2) {
+            diff.add_edge(&pair[0], &pair[1], 1.0).unwrap();
+        }
+        let extra = Uuid::from_u128(100);
+        diff.delete_node(extra);
+        diff.delete_edge(&ids[0], &ids[2]);
+        diff.set_meta(Some(DiffMeta {
+            producer: Some("test".to_string()),
+            timestamp: Some(1),
+            schema_version: None,
+        }));
+
+        assert_eq!(
+            diff.estimated_byte_size(),
+            graph_diff_to_bytes(&diff).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_chunks() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for id in 0..200 {
+            diff.add_or_update_node(
+                &id,
+                NodeUpdate {
+                    label: Some(format!("node {}", id)),
+                    ..NodeUpdate::default()
+                },
+            );
+            diff.add_edge(&id, &((id + 1) % 200), id as f32).unwrap();
+            diff.add_edge(&id, &((id + 7) % 200), id as f32).unwrap();
+        }
+        for id in (0..200).step_by(5) {
+            diff.delete_node(id);
+        }
+        diff.delete_edge(&1, &3);
+
+        let chunks = graph_diff_to_chunks(&diff, 512).unwrap();
+        assert!(chunks.len() > 1);
+        for chunk in chunks.iter() {
+            assert!(chunk.len() <= 512);
+            let chunk = bytes_to_graph_diff::<usize, NodeUpdate>(chunk).unwrap();
+            assert!(chunk.is_internally_consistent());
+        }
+        assert_eq!(graph_diff_from_chunks(chunks.iter().rev()).unwrap(), diff);
+
+        assert!(
+            graph_diff_to_chunks(&GraphDiff::<usize, NodeUpdate>::new(), 0)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(matches!(
+            graph_diff_to_chunks(&diff, 32),
+            Err(GraphDiffError::Serialize(_))
+        ));
+    }
+
+    #[cfg(feature = "ordered")]
+    #[test]
+    fn test_ordered_bytes() {
+        let build = |mut diff: GraphDiff<usize, NodeUpdate>| {
+            for id in 0..100 {
+                diff.add_or_update_node(
+                    &id,
+                    NodeUpdate {
+                        size: Some(id as f32),
+                        ..NodeUpdate::default()
+                    },
+                );
+                diff.add_edge(&id, &((id * 7) % 100), 1.).unwrap();
+            }
+            for id in (0..100).step_by(3) {
+                diff.delete_node(id);
+            }
+            diff
+        };
+        // differently sized maps, so map iteration order differs
+        let first = build(GraphDiff::new());
+        let second = build(GraphDiff::with_capacity(1000, 1000));
+        assert!(!(first.new_or_updated_nodes().iter()).eq(second.new_or_updated_nodes()));
+        assert_eq!(first, second);
+
+        let bytes = graph_diff_to_bytes(&first).unwrap();
+        assert_eq!(bytes, graph_diff_to_bytes(&second).unwrap());
+        let mut written = Vec::new();
+        graph_diff_to_writer(&second, &mut written).unwrap();
+        assert_eq!(bytes, written);
+        assert_eq!(
+            bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap(),
+            first
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compression() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for id in 0..100 {
+            diff.add_or_update_node(
+                &id,
+                NodeUpdate {
+                    red: Some(255),
+                    size: Some(1.),
+                    ..NodeUpdate::default()
+                },
+            );
+            diff.add_edge(&id, &((id + 1) % 100), 1.).unwrap();
+        }
+        diff.delete_node(100);
+
+        let bytes = graph_diff_to_bytes(&diff).unwrap();
+        let compressed = graph_diff_to_bytes_compressed(&diff, 3).unwrap();
+        assert_eq!(&compressed[..4], COMPRESSED_MAGIC);
+        assert!(compressed.len() < bytes.len());
+
+        let from_compressed = bytes_to_graph_diff_compressed::<usize, NodeUpdate>(&compressed);
+        let from_bytes = bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap();
+        assert_eq!(from_compressed.unwrap(), diff);
+        assert_eq!(from_bytes, diff);
+        assert_eq!(
+            bytes_to_graph_diff_compressed::<usize, NodeUpdate>(&bytes).unwrap(),
+            diff
+        );
+
+        let err = bytes_to_graph_diff_compressed::<usize, NodeUpdate>(b"DGDZ??").unwrap_err();
+        assert!(matches!(err, GraphDiffError::Deserialize(_)));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_bytes() {
+        // several parts, the last one shorter
+        let len = 3 * PARALLEL_PART_LEN + 1;
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for id in 0..len {
+            let update = NodeUpdate {
+                label: Some(format!("node {}", id)),
+                ..NodeUpdate::default()
+            };
+            diff.add_or_update_node(&id, update);
+            diff.add_edge(&id, &((id + 1) % len), 1.).unwrap();
+        }
+        diff.delete_node(len);
+        diff.delete_edge(&(len + 1), &(len + 2));
+
+        let bytes = graph_diff_to_bytes_parallel(&diff).unwrap();
+        assert_eq!(bytes, graph_diff_to_bytes(&diff).unwrap());
+        let deserialized = bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap();
+        assert_eq!(deserialized, diff);
+
+        let empty = GraphDiff::<usize, NodeUpdate>::new();
+        let bytes = graph_diff_to_bytes_parallel(&empty).unwrap();
+        assert_eq!(bytes, graph_diff_to_bytes(&empty).unwrap());
+    }
+
+    #[test]
+    fn test_sorted_deletions_bytes() {
+        let mut forward = GraphDiff::<usize, NodeUpdate>::new();
+        let mut backward = GraphDiff::<usize, NodeUpdate>::new();
+        for id in 0..50 {
+            forward.delete_node(id);
+            forward.delete_edge(&(id % 5), &(id + 100));
+            backward.delete_node(49 - id);
+            backward.delete_edge(&((49 - id) % 5), &(149 - id));
+        }
+        assert_eq!(forward, backward);
+
+        let bytes = graph_diff_to_bytes_sorted_deletions(&forward).unwrap();
+        assert_eq!(
+            bytes,
+            graph_diff_to_bytes_sorted_deletions(&backward).unwrap()
+        );
+        assert_eq!(bytes, graph_diff_to_bytes_canonical(&forward).unwrap());
+        let deserialized = bytes_to_graph_diff::<usize, NodeUpdate>(&bytes).unwrap();
+        assert_eq!(deserialized, forward);
+
+        forward.add_node(&100);
+        let bytes = graph_diff_to_bytes_sorted_deletions(&forward).unwrap();
+        assert_eq!(bytes, graph_diff_to_bytes_canonical(&forward).unwrap());
+    }
+
+    #[test]
+    fn test_compact_id_bytes() {
+        let mut diff = GraphDiff::<u32, NodeUpdate>::new();
+        for id in 0..1000 {
+            diff.add_node(&id);
+            diff.add_edge(&id, &((id + 1) % 1000), 1.).unwrap();
+        }
+        diff.delete_node(1000);
+        diff.delete_edge(&1001, &1002);
+        diff.set_meta(Some(DiffMeta {
+            producer: Some("test".into()),
+            ..DiffMeta::default()
+        }));
+
+        let bytes = graph_diff_to_bytes(&diff).unwrap();
+        let compact = graph_diff_to_bytes_compact_ids(&diff).unwrap();
+        assert_eq!(&compact[..4], COMPACT_ID_MAGIC);
+        // ids below 251 take one byte and the others three instead of four, and lengths of
+        // maps and sets one instead of eight
+        assert!(compact.len() * 3 < bytes.len() * 2);
+
+        let deserialized = bytes_to_graph_diff_compact_ids::<u32, NodeUpdate, f32>(&compact);
+        let deserialized = deserialized.unwrap();
+        assert_eq!(deserialized, diff);
+        assert_eq!(deserialized.meta(), diff.meta());
+        assert_eq!(
+            bytes_to_graph_diff_compact_ids::<u32, NodeUpdate, f32>(&bytes).unwrap(),
+            diff
+        );
+
+        let err = bytes_to_graph_diff_compact_ids::<u64, NodeUpdate, f32>(&compact).unwrap_err();
+        assert!(matches!(
+            err,
+            GraphDiffError::Format(FormatError::IdTypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_json() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(
+            &1,
+            NodeUpdate {
+                label: Some("test".to_string()),
+                ..NodeUpdate::default()
+            },
+        );
+        diff.delete_node(3);
+        diff.add_edge(&1, &2, 0.5).unwrap();
+        diff.delete_edge(&2, &1);
+
+        let json = graph_diff_to_json(&diff).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["nodes"]["newOrUpdated"]["1"]["label"], "test");
+        assert_eq!(value["nodes"]["deleted"][0], 3);
+        assert_eq!(value["edges"]["newOrUpdated"]["1"]["2"], 0.5);
+        assert_eq!(value["edges"]["deleted"]["2"][0], 1);
+
+        let deserialized = graph_diff_from_json::<usize, NodeUpdate, f32>(&json).unwrap();
+        assert_eq!(deserialized, diff);
+    }
 }
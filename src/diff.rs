@@ -1,6 +1,26 @@
-use hashbrown::{HashMap, HashSet};
+use crate::{error::GraphDiffError, order::InsertionOrder};
+use alloc::{format, string::String, vec, vec::Vec};
+use core::{
+    fmt::{self, Debug, Display},
+    hash::Hash,
+    ops::AddAssign,
+};
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, hash::Hash, ops::AddAssign};
+#[cfg(feature = "std")]
+use std::{collections::hash_map::DefaultHasher, hash::Hasher};
+
+/// Bit pattern of a float for hashing, with `-0.0` and NaN normalized so that floats that
+/// compare equal hash equally and all NaNs hash the same.
+#[cfg(feature = "std")]
+pub(crate) fn float_bits(value: f64) -> u64 {
+    if value.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        (value + 0.0).to_bits()
+    }
+}
+
+pub(crate) use hashbrown::{HashMap, HashSet};
 
 /// A differential between two graphs.
 ///
@@ -27,35 +47,156 @@ use std::{fmt::Debug, hash::Hash, ops::AddAssign};
 /// // If a node is deleted in `diff2`, it will be deleted in the combined diff.
 /// diff1 += diff2;
 /// ```
+///
+/// A graph of another library can be uploaded as an "all new" diff, without deletions, with
+/// `extend_nodes` and `extend_edges`, or with `from_petgraph` for `petgraph` graphs behind
+/// the `petgraph` feature. `to_petgraph` converts the new or updated nodes and edges back.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct GraphDiff<Id: Hash + Eq + Copy, T: Default + AddAssign, W = f32> {
     pub(crate) nodes: NodeDiff<Id, T>,
     pub(crate) edges: EdgeDiff<Id, W>,
+    /// Written by `graph_diff_to_bytes` in a section of its own, not part of the serde
+    /// representation.
+    #[serde(skip)]
+    pub(crate) meta: Option<DiffMeta>,
+    /// Followed by the `iter_*` methods under the `ordered` feature.
+    #[serde(skip, default = "InsertionOrder::default")]
+    pub(crate) order: InsertionOrder<Id>,
 }
 
-impl<Id: Hash + Eq + Copy, T: Default + AddAssign> Default for GraphDiff<Id, T> {
-    fn default() -> GraphDiff<Id, T> {
+impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W> Default for GraphDiff<Id, T, W> {
+    fn default() -> GraphDiff<Id, T, W> {
         GraphDiff {
             nodes: NodeDiff {
                 new_or_updated: HashMap::new(),
                 deleted: HashSet::new(),
             },
-            edges: EdgeDiff {
-                new_or_updated: HashMap::new(),
-                deleted: HashMap::new(),
-            },
+            edges: EdgeDiff::new(HashMap::new(), HashMap::new()),
+            meta: None,
+            order: InsertionOrder::default(),
         }
     }
 }
 
 impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDiff<Id, T, W> {
-    pub fn new() -> GraphDiff<Id, T> {
+    pub fn new() -> GraphDiff<Id, T, W> {
         GraphDiff::default()
     }
 
+    /// Create an empty diff with space for at least `nodes` nodes and edges from `edges`
+    /// distinct `from` nodes.
+    ///
+    /// The `nodes` hint sizes both the new or updated and the deleted nodes. The `edges`
+    /// hint only sizes the outer edge map, inner maps still grow dynamically.
+    pub fn with_capacity(nodes: usize, edges: usize) -> GraphDiff<Id, T, W> {
+        GraphDiff {
+            nodes: NodeDiff {
+                new_or_updated: HashMap::with_capacity(nodes),
+                deleted: HashSet::with_capacity(nodes),
+            },
+            edges: EdgeDiff::new(HashMap::with_capacity(edges), HashMap::new()),
+            meta: None,
+            order: InsertionOrder::default(),
+        }
+    }
+
+    /// Merge a sequence of diffs into one, as if combining them in order with `+=`.
+    ///
+    /// The maps of the merged diff are preallocated from the sizes of all inputs.
+    pub fn merge_many(diffs: impl IntoIterator<Item = Self>) -> GraphDiff<Id, T, W> {
+        let diffs = diffs.into_iter().collect::<Vec<_>>();
+        let nodes = diffs
+            .iter()
+            .map(|d| d.nodes.new_or_updated.len() + d.nodes.deleted.len())
+            .sum();
+        let edges = diffs.iter().map(|d| d.edges.new_or_updated.len()).sum();
+        let mut merged = GraphDiff::with_capacity(nodes, edges);
+        for diff in diffs {
+            merged += diff;
+        }
+        merged
+    }
+
+    /// The changes made by every one of `diffs`.
+    ///
+    /// Keeps the nodes updated by all diffs with the parts of their updates all diffs agree
+    /// on, the edges set to the same weight by all diffs and the nodes and edges deleted by
+    /// all diffs. The intersection of no diffs is empty.
+    pub fn intersection(diffs: &[&Self]) -> GraphDiff<Id, T, W>
+    where
+        T: Clone + Intersect,
+    {
+        let mut intersection = GraphDiff::new();
+        let Some((first, rest)) = diffs.split_first() else {
+            return intersection;
+        };
+        for (id, update) in first.iter_new_or_updated_nodes() {
+            let others = rest.iter().map(|diff| diff.nodes.new_or_updated.get(id));
+            let Some(others) = others.collect::<Option<Vec<_>>>() else {
+                continue;
+            };
+            let update =
+                (others.into_iter()).fold(update.clone(), |common, other| common.intersect(other));
+            intersection.nodes.new_or_updated.insert(*id, update);
+        }
+        for id in first.iter_deleted_nodes() {
+            if rest.iter().all(|diff| diff.nodes.deleted.contains(id)) {
+                intersection.nodes.deleted.insert(*id);
+            }
+        }
+        for (from, to, weight) in first.iter_new_or_updated_edges() {
+            if rest
+                .iter()
+                .all(|diff| diff.edge_weight(from, to) == Some(weight))
+            {
+                let _ = intersection.add_edge(from, to, *weight);
+            }
+        }
+        for (from, to) in first.iter_deleted_edges() {
+            if rest.iter().all(|diff| diff.is_edge_deleted(from, to)) {
+                intersection.delete_edge(from, to);
+            }
+        }
+        intersection
+    }
+
     /// Initialse diff from a NodeDiff and an EdgeDiff
+    ///
+    /// The diffs are not validated, so the result may contain edges referencing deleted
+    /// nodes. Use `try_from_diffs` unless the inputs are known to be consistent.
     pub fn from_diffs(nodes: NodeDiff<Id, T>, edges: EdgeDiff<Id, W>) -> GraphDiff<Id, T, W> {
-        GraphDiff { nodes, edges }
+        GraphDiff {
+            nodes,
+            edges,
+            meta: None,
+            order: InsertionOrder::default(),
+        }
+    }
+
+    /// Initialise diff from a NodeDiff and an EdgeDiff, checking that no new or updated edge
+    /// references a deleted node.
+    ///
+    /// Fails with `GraphDiffError::InconsistentEdge` naming the first offending edge found.
+    pub fn try_from_diffs(
+        nodes: NodeDiff<Id, T>,
+        edges: EdgeDiff<Id, W>,
+    ) -> Result<GraphDiff<Id, T, W>, GraphDiffError>
+    where
+        Id: Debug,
+    {
+        let diff = GraphDiff {
+            nodes,
+            edges,
+            meta: None,
+            order: InsertionOrder::default(),
+        };
+        match diff.inconsistent_edge() {
+            Some((from, to)) => Err(GraphDiffError::InconsistentEdge {
+                from: format!("{:?}", from),
+                to: format!("{:?}", to),
+            }),
+            None => Ok(diff),
+        }
     }
 
     /// Get a reference to the node diff.
@@ -63,6 +204,16 @@ impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDif
         &self.nodes
     }
 
+    /// Get the metadata of the diff, if any.
+    pub fn meta(&self) -> Option<&DiffMeta> {
+        self.meta.as_ref()
+    }
+
+    /// Set the metadata written with the diff by `graph_diff_to_bytes`.
+    pub fn set_meta(&mut self, meta: Option<DiffMeta>) {
+        self.meta = meta;
+    }
+
     /// Get a reference to the new or updated nodes.
     pub fn new_or_updated_nodes(&self) -> &HashMap<Id, T> {
         &self.nodes.new_or_updated
@@ -88,6 +239,118 @@ impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDif
         &self.edges.deleted
     }
 
+    /// Consume the diff, returning its new or updated nodes, deleted nodes, new or updated
+    /// edges and deleted edges.
+    #[allow(clippy::type_complexity)]
+    pub fn into_parts(
+        self,
+    ) -> (
+        HashMap<Id, T>,
+        HashSet<Id>,
+        HashMap<Id, HashMap<Id, W>>,
+        HashMap<Id, HashSet<Id>>,
+    ) {
+        (
+            self.nodes.new_or_updated,
+            self.nodes.deleted,
+            self.edges.new_or_updated,
+            self.edges.deleted,
+        )
+    }
+
+    /// Iterate over the new or updated nodes and their updates.
+    ///
+    /// The `iter_*` methods follow hash map order, or with the `ordered` feature the order
+    /// in which the entries were first added or deleted with the methods of the diff and
+    /// `+=`. Entries placed otherwise, such as by `from_diffs` or deserialization, follow the
+    /// ordered ones in hash map order.
+    pub fn iter_new_or_updated_nodes(&self) -> impl Iterator<Item = (&Id, &T)> {
+        self.order.iter_updated_nodes(&self.nodes.new_or_updated)
+    }
+
+    /// Iterate over the deleted nodes.
+    pub fn iter_deleted_nodes(&self) -> impl Iterator<Item = &Id> {
+        self.order.iter_deleted_nodes(&self.nodes.deleted)
+    }
+
+    /// Iterate over the new or updated edges as `(from, to, weight)`.
+    pub fn iter_new_or_updated_edges(&self) -> impl Iterator<Item = (&Id, &Id, &W)> {
+        self.order.iter_updated_edges(&self.edges.new_or_updated)
+    }
+
+    /// Iterate over the deleted edges as `(from, to)`.
+    pub fn iter_deleted_edges(&self) -> impl Iterator<Item = (&Id, &Id)> {
+        self.order.iter_deleted_edges(&self.edges.deleted)
+    }
+
+    /// Returns `true` if the node is new or updated in the diff.
+    pub fn contains_new_or_updated_node(&self, id: &Id) -> bool {
+        self.nodes.new_or_updated.contains_key(id)
+    }
+
+    /// Get the update of a new or updated node.
+    pub fn node_update(&self, id: &Id) -> Option<&T> {
+        self.nodes.new_or_updated.get(id)
+    }
+
+    /// Take the update of a new or updated node out of the diff, like
+    /// `remove_updated_node`. Edges connected to the node are kept.
+    pub fn take_node_update(&mut self, id: &Id) -> Option<T> {
+        self.remove_updated_node(id)
+    }
+
+    /// Returns `true` if the node is marked for deletion.
+    pub fn is_node_deleted(&self, id: &Id) -> bool {
+        self.nodes.deleted.contains(id)
+    }
+
+    /// Get the weight of a new or updated edge.
+    pub fn edge_weight(&self, from: &Id, to: &Id) -> Option<&W> {
+        self.edges.new_or_updated.get(from).and_then(|e| e.get(to))
+    }
+
+    /// Get the new or updated edges leaving a node, keyed by `to` node.
+    pub fn outgoing(&self, from: &Id) -> Option<&HashMap<Id, W>> {
+        self.edges.new_or_updated.get(from)
+    }
+
+    /// Get the `to` nodes of the deleted edges leaving a node.
+    pub fn outgoing_deleted(&self, from: &Id) -> Option<&HashSet<Id>> {
+        self.edges.deleted.get(from)
+    }
+
+    /// Get the new or updated edges entering a node as `(from, weight)`.
+    ///
+    /// Scans all edges of the diff. For repeated lookups, build a `ReverseIndex` once with
+    /// `reverse_index` instead.
+    pub fn incoming(&self, to: &Id) -> Vec<(&Id, &W)> {
+        self.edges
+            .new_or_updated
+            .iter()
+            .filter_map(|(from, to_weight)| to_weight.get(to).map(|w| (from, w)))
+            .collect()
+    }
+
+    /// Build an index of the new or updated edges by `to` node.
+    ///
+    /// The index borrows the diff, so it cannot become stale, and stores one entry per edge
+    /// on top of the diff itself.
+    pub fn reverse_index(&self) -> ReverseIndex<'_, Id, W> {
+        let mut incoming: HashMap<&Id, Vec<(&Id, &W)>> = HashMap::new();
+        for (from, to, weight) in self.iter_new_or_updated_edges() {
+            incoming.entry(to).or_default().push((from, weight));
+        }
+        ReverseIndex { incoming }
+    }
+
+    /// Returns `true` if the edge is marked for deletion.
+    pub fn is_edge_deleted(&self, from: &Id, to: &Id) -> bool {
+        self.edges
+            .deleted
+            .get(from)
+            .is_some_and(|edges| edges.contains(to))
+    }
+
     /// Returns `true` if the diff contains no nodes or edges (new, updated or deleted).
     pub fn is_empty(&self) -> bool {
         self.nodes.new_or_updated.is_empty()
@@ -96,10 +359,204 @@ impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDif
             && self.edges.deleted.is_empty()
     }
 
+    /// Number of new, updated or deleted nodes.
+    pub fn node_change_count(&self) -> usize {
+        self.nodes.new_or_updated.len() + self.nodes.deleted.len()
+    }
+
+    /// Number of new, updated or deleted edges.
+    pub fn edge_change_count(&self) -> usize {
+        self.edges
+            .new_or_updated
+            .values()
+            .map(|e| e.len())
+            .sum::<usize>()
+            + self.deleted_edge_count()
+    }
+
+    /// Number of deleted edges.
+    pub fn deleted_edge_count(&self) -> usize {
+        self.edges.deleted.values().map(|e| e.len()).sum()
+    }
+
+    /// Count the new or updated edge weights in `bins` equally wide bins spanning `range`,
+    /// or the smallest to the largest weight if `range` is `None`.
+    ///
+    /// The last bin includes the end of the range. Weights outside the range and NaN or
+    /// infinite weights are not counted. If the range is a single value, all weights equal to
+    /// it are counted in the first bin.
+    pub fn weight_histogram(&self, bins: usize, range: Option<(W, W)>) -> Vec<u64>
+    where
+        W: Into<f64>,
+    {
+        let mut histogram = vec![0; bins];
+        let weights = || {
+            self.edges
+                .new_or_updated
+                .values()
+                .flat_map(|e| e.values())
+                .map(|weight| (*weight).into())
+                .filter(|weight: &f64| weight.is_finite())
+        };
+        let (min, max) = match range {
+            Some((min, max)) => (min.into(), max.into()),
+            None => weights().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), weight| {
+                (min.min(weight), max.max(weight))
+            }),
+        };
+        if bins == 0 || (min..=max).is_empty() {
+            return histogram;
+        }
+        let width = (max - min) / bins as f64;
+        for weight in weights().filter(|weight| (min..=max).contains(weight)) {
+            let bin = if width > 0. {
+                ((weight - min) / width) as usize
+            } else {
+                0
+            };
+            histogram[bin.min(bins - 1)] += 1;
+        }
+        histogram
+    }
+
+    /// Summarize the size of the diff.
+    pub fn stats(&self) -> DiffStats {
+        let mut endpoints = HashSet::new();
+        for (from, to_weight) in self.edges.new_or_updated.iter() {
+            endpoints.insert(from);
+            endpoints.extend(to_weight.keys());
+        }
+        for (from, to_set) in self.edges.deleted.iter() {
+            endpoints.insert(from);
+            endpoints.extend(to_set.iter());
+        }
+        DiffStats {
+            new_or_updated_nodes: self.nodes.new_or_updated.len(),
+            deleted_nodes: self.nodes.deleted.len(),
+            new_or_updated_edges: self.edges.new_or_updated.values().map(|e| e.len()).sum(),
+            deleted_edges: self.edges.deleted.values().map(|e| e.len()).sum(),
+            distinct_endpoints: endpoints.len(),
+        }
+    }
+
+    /// The distinct nodes touched by the diff: new, updated and deleted nodes and the
+    /// endpoints of new, updated and deleted edges.
+    pub fn touched_nodes(&self) -> HashSet<Id> {
+        let mut touched: HashSet<Id> = self.nodes.new_or_updated.keys().copied().collect();
+        touched.extend(self.nodes.deleted.iter());
+        for (from, to, _) in self.iter_new_or_updated_edges() {
+            touched.extend([*from, *to]);
+        }
+        for (from, to) in self.iter_deleted_edges() {
+            touched.extend([*from, *to]);
+        }
+        touched
+    }
+
+    /// Combine `other` into `self` like `+=`, returning where both diffs set different
+    /// values for the same node property or edge weight. The values of `other` win.
+    pub fn add_assign_checked(&mut self, other: Self) -> Vec<Conflict<Id, T, W>>
+    where
+        T: Conflicts,
+    {
+        let mut conflicts = Vec::new();
+        for (id, theirs) in other.nodes.new_or_updated.iter() {
+            if let Some((losing, winning)) = self
+                .nodes
+                .new_or_updated
+                .get(id)
+                .and_then(|mine| mine.conflict(theirs))
+            {
+                conflicts.push(Conflict::Node {
+                    id: *id,
+                    losing,
+                    winning,
+                });
+            }
+        }
+        for (from, to, winning) in other.iter_new_or_updated_edges() {
+            if let Some(losing) = self.edge_weight(from, to) {
+                if losing != winning {
+                    conflicts.push(Conflict::Edge {
+                        from: *from,
+                        to: *to,
+                        losing: *losing,
+                        winning: *winning,
+                    });
+                }
+            }
+        }
+        *self += other;
+        conflicts
+    }
+
+    /// Combine `other` into `self` like `+=`, except that weights of edges in both diffs
+    /// are combined with `weight_op(self_weight, other_weight)`, e.g. `|a, b| a + b` to sum
+    /// them or `f32::max` to keep the larger. Node updates are combined with `+=`.
+    ///
+    /// Edges that `add_edge` rejects, those touching a deleted node and self-loops if
+    /// rejected, are skipped as with `+=` and returned as the error, like `extend_edges`.
+    pub fn add_assign_with(
+        &mut self,
+        other: Self,
+        weight_op: impl Fn(W, W) -> W,
+    ) -> Result<(), Vec<(Id, Id, W)>> {
+        *self += other.nodes;
+        let mut rejected = Vec::new();
+        for (from, to_weight) in other.edges.new_or_updated {
+            for (to, weight) in to_weight {
+                let combined = match self.edge_weight(&from, &to) {
+                    Some(mine) => weight_op(*mine, weight),
+                    None => weight,
+                };
+                if self.add_edge(&from, &to, combined).is_err() {
+                    rejected.push((from, to, weight));
+                }
+            }
+        }
+        for (from, to) in other.edges.deleted {
+            for to in to {
+                self.delete_edge(&from, &to);
+            }
+        }
+        if rejected.is_empty() {
+            Ok(())
+        } else {
+            Err(rejected)
+        }
+    }
+
+    /// Combine `other` into `self` like `+=`, without consuming `other`.
+    ///
+    /// Only the node updates of `other` are cloned, which is cheaper than cloning all of
+    /// `other` to combine it by value. Edges that `add_edge` rejects are skipped and
+    /// returned as the error, as with `add_assign_with`.
+    pub fn merge_ref(&mut self, other: &Self) -> Result<(), Vec<(Id, Id, W)>>
+    where
+        T: Clone,
+    {
+        for (id, update) in other.iter_new_or_updated_nodes() {
+            self.add_or_update_node(id, update.clone());
+        }
+        for id in other.iter_deleted_nodes() {
+            self.delete_node(*id);
+        }
+        let rejected = self.extend_edges(
+            other
+                .iter_new_or_updated_edges()
+                .map(|(from, to, weight)| (*from, *to, *weight)),
+        );
+        for (from, to) in other.iter_deleted_edges() {
+            self.delete_edge(from, to);
+        }
+        rejected
+    }
+
     /// Add a new node to the diff. If previously marked as deleted, it will be overwritten.
     pub fn add_node(&mut self, node_id: &Id) {
         let _ = self.nodes.new_or_updated.try_insert(*node_id, T::default());
         self.nodes.deleted.remove(node_id);
+        self.order.updated_node(node_id);
     }
 
     /// Add or update a node in the diff with an update.
@@ -111,6 +568,7 @@ impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDif
             self.nodes.new_or_updated.insert(*node_id, update);
         }
         self.nodes.deleted.remove(node_id);
+        self.order.updated_node(node_id);
     }
 
     /// Get a mutable reference to a node update in the diff. If the node is not
@@ -122,10 +580,18 @@ impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDif
         self.nodes.new_or_updated.get_mut(node_id).unwrap()
     }
 
+    /// Call `f` with every new or updated node and a mutable reference to its update.
+    pub fn update_each_node(&mut self, mut f: impl FnMut(&Id, &mut T)) {
+        for (id, update) in self.nodes.new_or_updated.iter_mut() {
+            f(id, update);
+        }
+    }
+
     /// Use with caution: overwrites the node update to whatever you provide.
     pub fn set_node_update(&mut self, node_id: &Id, update: T) {
         self.nodes.new_or_updated.insert(*node_id, update);
         self.nodes.deleted.remove(node_id);
+        self.order.updated_node(node_id);
     }
 
     /// Add a new node to be deleted to the diff.
@@ -136,29 +602,200 @@ impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDif
         self.nodes.new_or_updated.remove(&node_id);
 
         // remove all edges where node_id is predecessor
-        self.edges.new_or_updated.remove(&node_id);
-
-        for (from, to_weight) in self.edges.new_or_updated.iter_mut() {
-            if to_weight.contains_key(&node_id) {
-                self.edges.deleted.entry(*from).or_default().insert(node_id);
+        if let Some(to_weight) = self.edges.new_or_updated.remove(&node_id) {
+            for (to, weight) in to_weight {
+                self.edges.record_deleted_weight(node_id, to, weight);
             }
+        }
+
+        let edges = &mut self.edges;
+        for (from, to_weight) in edges.new_or_updated.iter_mut() {
             // remove all edges where node_id is successor
-            to_weight.remove(&node_id);
+            if let Some(weight) = to_weight.remove(&node_id) {
+                edges.deleted.entry(*from).or_default().insert(node_id);
+                self.order.deleted_edge(from, &node_id);
+                if let Some(deleted_weights) = edges.deleted_weights.as_mut() {
+                    deleted_weights
+                        .entry(*from)
+                        .or_default()
+                        .insert(node_id, weight);
+                }
+            }
         }
         self.nodes.deleted.insert(node_id);
+        self.order.deleted_node(&node_id);
+    }
+
+    /// Delete several nodes like `delete_node`, in a single pass over the edges instead of
+    /// one per node.
+    ///
+    /// Gives the same diff as calling `delete_node` for each id in the iteration order of
+    /// `ids`. In particular, an edge between two of the deleted nodes is marked as deleted
+    /// when its target comes first in `ids`, and only dropped otherwise.
+    pub fn delete_nodes(&mut self, ids: &HashSet<Id>) {
+        let order: HashMap<Id, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        for (i, id) in ids.iter().enumerate() {
+            self.nodes.new_or_updated.remove(id);
+            if let Some(to_weight) = self.edges.new_or_updated.remove(id) {
+                for (to, weight) in to_weight {
+                    // the target was deleted before, so this edge was an incoming edge then
+                    if order.get(&to).is_some_and(|j| *j < i) {
+                        self.edges.deleted.entry(*id).or_default().insert(to);
+                        self.order.deleted_edge(id, &to);
+                    }
+                    self.edges.record_deleted_weight(*id, to, weight);
+                }
+            }
+        }
+
+        let edges = &mut self.edges;
+        for (from, to_weight) in edges.new_or_updated.iter_mut() {
+            to_weight.retain(|to, weight| {
+                if !ids.contains(to) {
+                    return true;
+                }
+                edges.deleted.entry(*from).or_default().insert(*to);
+                self.order.deleted_edge(from, to);
+                if let Some(deleted_weights) = edges.deleted_weights.as_mut() {
+                    deleted_weights
+                        .entry(*from)
+                        .or_default()
+                        .insert(*to, *weight);
+                }
+                false
+            });
+        }
+        self.nodes.deleted.extend(ids.iter().copied());
+        for id in ids {
+            self.order.deleted_node(id);
+        }
+    }
+
+    /// Delete a node like `delete_node`. If `remove_isolated` is set, also delete the
+    /// neighbours left without any new or updated edge in the diff.
+    /// Returns the ids of the additionally deleted neighbours.
+    ///
+    /// Only the edges of the diff are considered: a neighbour that still has edges in the
+    /// graph the diff applies to, e.g. on the server, is deleted as well since the diff
+    /// cannot see them. Use `remove_isolated` only for neighbours the diff itself creates,
+    /// or check the returned ids against the graph.
+    pub fn delete_node_cascade(&mut self, node_id: Id, remove_isolated: bool) -> HashSet<Id> {
+        let mut neighbours = HashSet::new();
+        if remove_isolated {
+            if let Some(to_weight) = self.edges.new_or_updated.get(&node_id) {
+                neighbours.extend(to_weight.keys().copied());
+            }
+            for (from, to_weight) in self.edges.new_or_updated.iter() {
+                if to_weight.contains_key(&node_id) {
+                    neighbours.insert(*from);
+                }
+            }
+            neighbours.remove(&node_id);
+        }
+        self.delete_node(node_id);
+
+        for (from, to_weight) in self.edges.new_or_updated.iter() {
+            if to_weight.is_empty() {
+                continue;
+            }
+            neighbours.remove(from);
+            for to in to_weight.keys() {
+                neighbours.remove(to);
+            }
+        }
+        for id in neighbours.iter() {
+            self.delete_node(*id);
+        }
+        neighbours
+    }
+
+    /// Merge node `src` into node `dst` and delete `src`.
+    ///
+    /// The update of `src` is added to the one of `dst` and the new or updated edges of `src`
+    /// are moved to `dst`. Edges between `src` and `dst` are dropped instead of becoming
+    /// self-loops, and an edge `dst` already has keeps its weight. Deleted edges of `src`
+    /// are not moved, they are deletions of edges of `src` and not of `dst`.
+    ///
+    /// Errors with `EndpointDeleted` and leaves the diff unchanged if `dst` or the other
+    /// endpoint of a moved edge is marked as deleted. Only an inconsistent diff has the
+    /// latter.
+    pub fn merge_node_into(&mut self, src: Id, dst: Id) -> Result<(), GraphDiffError> {
+        if src == dst {
+            return Ok(());
+        }
+        let is_deleted = |id: &Id| self.nodes.deleted.contains(id);
+        if is_deleted(&dst) {
+            return Err(GraphDiffError::EndpointDeleted {
+                from: false,
+                to: true,
+            });
+        }
+        let outgoing: Vec<(Id, W)> = self
+            .edges
+            .new_or_updated
+            .get(&src)
+            .into_iter()
+            .flatten()
+            .filter(|(to, _)| **to != src && **to != dst)
+            .filter(|(to, _)| self.edge_weight(&dst, to).is_none())
+            .map(|(to, weight)| (*to, *weight))
+            .collect();
+        let incoming: Vec<(Id, W)> = self
+            .edges
+            .new_or_updated
+            .iter()
+            .filter(|(from, _)| **from != src && **from != dst)
+            .filter_map(|(from, to_weight)| Some((*from, *to_weight.get(&src)?)))
+            .filter(|(from, _)| self.edge_weight(from, &dst).is_none())
+            .collect();
+        if outgoing.iter().any(|(to, _)| is_deleted(to)) {
+            return Err(GraphDiffError::EndpointDeleted {
+                from: false,
+                to: true,
+            });
+        }
+        if incoming.iter().any(|(from, _)| is_deleted(from)) {
+            return Err(GraphDiffError::EndpointDeleted {
+                from: true,
+                to: false,
+            });
+        }
+
+        if let Some(update) = self.nodes.new_or_updated.remove(&src) {
+            self.add_or_update_node(&dst, update);
+        }
+        // the edges of `src` are moved, so `delete_node` must not mark them as deleted
+        self.edges.new_or_updated.remove(&src);
+        for to_weight in self.edges.new_or_updated.values_mut() {
+            to_weight.remove(&src);
+        }
+        for (to, weight) in outgoing {
+            self.add_edge(&dst, &to, weight)?;
+        }
+        for (from, weight) in incoming {
+            self.add_edge(&from, &dst, weight)?;
+        }
+        self.delete_node(src);
+        Ok(())
     }
 
     /// Add a new edge to the diff.
     /// If previously marked as deleted, it will be overwritten
     /// If either the from or to nodes are marked as deleted, it will error.
-    pub fn add_edge(
-        &mut self,
-        from: &Id,
-        to: &Id,
-        weight: W,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        if self.nodes.deleted.contains(from) || self.nodes.deleted.contains(to) {
-            return Err("Either from or to nodes are marked to be deleted".into());
+    /// Self-loops are allowed unless rejected with `reject_self_loops`.
+    pub fn add_edge(&mut self, from: &Id, to: &Id, weight: W) -> Result<(), GraphDiffError> {
+        if self.edges.reject_self_loops && from == to {
+            return Err(GraphDiffError::SelfLoop);
+        }
+        let (from_deleted, to_deleted) = (
+            self.nodes.deleted.contains(from),
+            self.nodes.deleted.contains(to),
+        );
+        if from_deleted || to_deleted {
+            return Err(GraphDiffError::EndpointDeleted {
+                from: from_deleted,
+                to: to_deleted,
+            });
         }
         if let Some(inner) = self.edges.deleted.get_mut(from) {
             inner.remove(to);
@@ -166,19 +803,135 @@ impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDif
         if self.edges.deleted.get(from).is_some_and(|e| e.is_empty()) {
             self.edges.deleted.remove(from);
         }
+        if let Some(deleted_weights) = self.edges.deleted_weights.as_mut() {
+            if let Some(inner) = deleted_weights.get_mut(from) {
+                inner.remove(to);
+            }
+            if deleted_weights.get(from).is_some_and(|e| e.is_empty()) {
+                deleted_weights.remove(from);
+            }
+        }
         self.edges
             .new_or_updated
             .entry(*from)
             .or_default()
             .insert(*to, weight);
+        self.order.updated_edge(from, to);
         Ok(())
     }
 
-    /// Add edges in batch to the dif.
-    pub fn add_edges(
+    /// Add a new edge to the diff like `add_edge`, but reject NaN or infinite weights with
+    /// `GraphDiffError::NonFiniteWeight`.
+    pub fn try_add_edge(&mut self, from: &Id, to: &Id, weight: W) -> Result<(), GraphDiffError>
+    where
+        W: Into<f64>,
+    {
+        if !weight.into().is_finite() {
+            return Err(GraphDiffError::NonFiniteWeight);
+        }
+        self.add_edge(from, to, weight)
+    }
+
+    /// Add `delta` to the weight of an edge, or add the edge with weight `delta` if the diff
+    /// does not have it. Errors like `add_edge`.
+    pub fn increment_edge(&mut self, from: &Id, to: &Id, delta: W) -> Result<(), GraphDiffError>
+    where
+        W: AddAssign,
+    {
+        let weight = match self.edge_weight(from, to) {
+            Some(weight) => {
+                let mut weight = *weight;
+                weight += delta;
+                weight
+            }
+            None => delta,
+        };
+        self.add_edge(from, to, weight)
+    }
+
+    /// Replace NaN or infinite edge weights with `replacement`.
+    /// Returns the number of replaced weights.
+    pub fn sanitize_weights(&mut self, replacement: W) -> usize
+    where
+        W: Into<f64>,
+    {
+        let mut replaced = 0;
+        for weight in self
+            .edges
+            .new_or_updated
+            .values_mut()
+            .flat_map(|e| e.values_mut())
+        {
+            if !(*weight).into().is_finite() {
+                *weight = replacement;
+                replaced += 1;
+            }
+        }
+        replaced
+    }
+
+    /// Add a new edge to the diff like `add_edge`, adding its endpoints as nodes with an
+    /// empty update if they are not new or updated nodes yet.
+    pub fn add_edge_with_nodes(
         &mut self,
-        edges: &HashMap<Id, HashMap<Id, W>>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        from: &Id,
+        to: &Id,
+        weight: W,
+    ) -> Result<(), GraphDiffError> {
+        self.add_edge(from, to, weight)?;
+        self.nodes.new_or_updated.entry(*from).or_default();
+        self.nodes.new_or_updated.entry(*to).or_default();
+        self.order.updated_node(from);
+        self.order.updated_node(to);
+        Ok(())
+    }
+
+    /// Add a new edge to the diff like `add_edge`, reviving endpoints that are marked as
+    /// deleted: they are no longer deleted and are added as nodes with an empty update.
+    /// Only fails for rejected self-loops, see `reject_self_loops`.
+    pub fn add_edge_reviving(
+        &mut self,
+        from: &Id,
+        to: &Id,
+        weight: W,
+    ) -> Result<(), GraphDiffError> {
+        if self.edges.reject_self_loops && from == to {
+            return Err(GraphDiffError::SelfLoop);
+        }
+        for id in [from, to] {
+            if self.nodes.deleted.remove(id) {
+                self.add_node(id);
+            }
+        }
+        self.add_edge(from, to, weight)
+    }
+
+    /// Make `add_edge` and the methods built on it reject self-loops, edges from a node to
+    /// itself, with `GraphDiffError::SelfLoop`. Self-loops already in the diff are kept,
+    /// see `self_loops`.
+    pub fn reject_self_loops(&mut self, reject: bool) {
+        self.edges.reject_self_loops = reject;
+    }
+
+    /// The new or updated self-loops of the diff and their weights.
+    pub fn self_loops(&self) -> Vec<(Id, W)> {
+        self.iter_new_or_updated_edges()
+            .filter(|(from, to, _)| from == to)
+            .map(|(id, _, weight)| (*id, *weight))
+            .collect()
+    }
+
+    /// Add an undirected edge as the two directed edges `a -> b` and `b -> a`.
+    ///
+    /// Both directions are plain edges of the diff, so composing diffs with `+=` and
+    /// `delete_undirected_edge` keep them in sync. Deleting either node removes both.
+    pub fn add_undirected_edge(&mut self, a: &Id, b: &Id, weight: W) -> Result<(), GraphDiffError> {
+        self.add_edge(a, b, weight)?;
+        self.add_edge(b, a, weight)
+    }
+
+    /// Add edges in batch to the dif.
+    pub fn add_edges(&mut self, edges: &HashMap<Id, HashMap<Id, W>>) -> Result<(), GraphDiffError> {
         for (from, to_weight) in edges {
             for (to, weight) in to_weight {
                 self.add_edge(from, to, *weight)?;
@@ -187,11 +940,50 @@ impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDif
         Ok(())
     }
 
-    /// Delete edges in batch from the diff.
-    pub fn delete_edges(
+    /// Add or update nodes from an iterator.
+    pub fn extend_nodes(&mut self, iter: impl IntoIterator<Item = (Id, T)>) {
+        for (id, update) in iter {
+            self.add_or_update_node(&id, update);
+        }
+    }
+
+    /// Add edges from an iterator of `(from, to, weight)`.
+    /// Edges with an endpoint marked as deleted are skipped and returned as the error.
+    pub fn extend_edges(
         &mut self,
-        edges: &HashMap<Id, HashSet<Id>>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        iter: impl IntoIterator<Item = (Id, Id, W)>,
+    ) -> Result<(), Vec<(Id, Id, W)>> {
+        let mut rejected = Vec::new();
+        for (from, to, weight) in iter {
+            if self.add_edge(&from, &to, weight).is_err() {
+                rejected.push((from, to, weight));
+            }
+        }
+        if rejected.is_empty() {
+            Ok(())
+        } else {
+            Err(rejected)
+        }
+    }
+
+    /// Add the edge maps computed by several workers, in order, so an edge in a later shard
+    /// overrides its weight from an earlier one. As with `extend_edges`, edges with an
+    /// endpoint marked as deleted are skipped and returned as the error.
+    pub fn absorb_edge_shards(
+        &mut self,
+        shards: Vec<HashMap<Id, HashMap<Id, W>>>,
+    ) -> Result<(), Vec<(Id, Id, W)>> {
+        self.extend_edges(shards.into_iter().flat_map(|shard| {
+            shard.into_iter().flat_map(|(from, to_weight)| {
+                to_weight
+                    .into_iter()
+                    .map(move |(to, weight)| (from, to, weight))
+            })
+        }))
+    }
+
+    /// Delete edges in batch from the diff.
+    pub fn delete_edges(&mut self, edges: &HashMap<Id, HashSet<Id>>) -> Result<(), GraphDiffError> {
         for (from, to_set) in edges {
             for to in to_set {
                 self.delete_edge(from, to);
@@ -205,7 +997,7 @@ impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDif
     pub unsafe fn add_edges_unchecked(
         &mut self,
         edges: HashMap<Id, HashMap<Id, W>>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), GraphDiffError> {
         for (from, inner_map) in edges {
             self.edges
                 .new_or_updated
@@ -228,44 +1020,296 @@ impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDif
     /// If present, the edge is removed from `new_or_updated`.
     pub fn delete_edge(&mut self, from: &Id, to: &Id) {
         self.edges.deleted.entry(*from).or_default().insert(*to);
+        self.order.deleted_edge(from, to);
 
-        let empty_inner_map = match self.edges.new_or_updated.get_mut(from) {
-            None => false,
-            Some(to_weight) => {
-                to_weight.remove(to);
-                to_weight.is_empty()
-            }
+        let (weight, empty_inner_map) = match self.edges.new_or_updated.get_mut(from) {
+            None => (None, false),
+            Some(to_weight) => (to_weight.remove(to), to_weight.is_empty()),
         };
         if empty_inner_map {
             self.edges.new_or_updated.remove(from);
         }
+        if let Some(weight) = weight {
+            self.edges.record_deleted_weight(*from, *to, weight);
+        }
+    }
+
+    /// Mark both directed edges `a -> b` and `b -> a` as deleted.
+    pub fn delete_undirected_edge(&mut self, a: &Id, b: &Id) {
+        self.delete_edge(a, b);
+        self.delete_edge(b, a);
     }
 
     /// Clear the diff of all nodes and edges.
+    ///
+    /// The node maps and the outer edge maps keep their capacity, so a diff can be reused
+    /// without reallocating. Use `clear_and_shrink` to release the memory instead.
     pub fn clear(&mut self) {
         self.nodes.new_or_updated.clear();
         self.nodes.deleted.clear();
         self.edges.new_or_updated.clear();
         self.edges.deleted.clear();
+        if let Some(deleted_weights) = self.edges.deleted_weights.as_mut() {
+            deleted_weights.clear();
+        }
+        self.order.clear();
     }
 
-    /// Remove a new or updated node from the diff.
-    /// Returns the update if there was one.
-    pub fn remove_updated_node(&mut self, id: &Id) -> Option<T> {
-        self.nodes.new_or_updated.remove(id)
+    /// Clear the diff of all nodes and edges and release the memory held by its maps.
+    pub fn clear_and_shrink(&mut self) {
+        self.clear();
+        self.nodes.new_or_updated.shrink_to_fit();
+        self.nodes.deleted.shrink_to_fit();
+        self.edges.new_or_updated.shrink_to_fit();
+        self.edges.deleted.shrink_to_fit();
+        if let Some(deleted_weights) = self.edges.deleted_weights.as_mut() {
+            deleted_weights.shrink_to_fit();
+        }
     }
 
-    /// Remove a node marked for deletion from the diff.
-    /// Returns `true` if the node was marked for deletion.
-    pub fn remove_deleted_node(&mut self, id: &Id) -> bool {
-        self.nodes.deleted.remove(id)
+    /// Start recording the weights of new or updated edges when they are deleted with
+    /// `delete_edge` or `delete_node`, for auditing. See `deleted_edge_weights`.
+    ///
+    /// Recording keeps a weight for every deleted edge that had one, so deleted edges take
+    /// about as much memory as new ones. Recorded weights live only in this diff: they are
+    /// not serialized, and are not carried over by `+=` or transformations into new diffs.
+    pub fn record_deleted_weights(&mut self) {
+        self.edges
+            .deleted_weights
+            .get_or_insert_with(HashMap::default);
     }
 
-    /// Remove a new updated edge from the diff.
-    /// Returns the updated weight if there was one.
-    pub fn remove_updated_edge(&mut self, from: &Id, to: &Id) -> Option<W> {
-        let removed = self
-            .edges
+    /// The weights of deleted edges recorded since `record_deleted_weights`, indexed by
+    /// `from` and then `to` node, or `None` if recording is off.
+    ///
+    /// Edges that are added again are removed from the record.
+    pub fn deleted_edge_weights(&self) -> Option<&HashMap<Id, HashMap<Id, W>>> {
+        self.edges.deleted_weights.as_ref()
+    }
+
+    /// Split the diff into one diff with only the new or updated nodes and edges and one
+    /// with only the deletions. Adding the deletions to the first diff with `+=` gives back
+    /// the original diff.
+    pub fn split_deletions(self) -> (GraphDiff<Id, T, W>, GraphDiff<Id, T, W>) {
+        let updates = GraphDiff {
+            nodes: NodeDiff::new(self.nodes.new_or_updated, HashSet::new()),
+            edges: EdgeDiff::new(self.edges.new_or_updated, HashMap::new()),
+            meta: self.meta,
+            order: self.order.clone(),
+        };
+        let deletions = GraphDiff {
+            nodes: NodeDiff::new(HashMap::new(), self.nodes.deleted),
+            edges: EdgeDiff::new(HashMap::new(), self.edges.deleted),
+            meta: None,
+            order: self.order,
+        };
+        (updates, deletions)
+    }
+
+    /// Split the diff into a first diff of at most `max_ops` changes, counted as in
+    /// `node_change_count` and `edge_change_count`, and a second diff with the rest. Adding
+    /// the second diff to the first with `+=` gives back the original diff.
+    ///
+    /// A deleted node stays in the same diff as the deleted edges connected to it. If such
+    /// a group alone is larger than `max_ops`, the first diff holds only that group and
+    /// exceeds the limit.
+    pub fn split_at_limit(self, max_ops: usize) -> (GraphDiff<Id, T, W>, GraphDiff<Id, T, W>) {
+        let mut first = GraphDiff::new();
+        first.meta = self.meta;
+        let mut rest = GraphDiff::new();
+        let (mut ops, mut full) = (0, false);
+        let mut target = |len: usize| {
+            let fits = ops + len <= max_ops || (ops == 0 && max_ops > 0);
+            if full || !fits {
+                full = true;
+                return false;
+            }
+            ops += len;
+            true
+        };
+
+        let deleted_nodes = self.nodes.deleted;
+        let mut groups: HashMap<Id, Vec<(Id, Id)>> = HashMap::new();
+        let mut deleted_edges = Vec::new();
+        for (from, to) in self.edges.deleted {
+            for to in to {
+                if deleted_nodes.contains(&to) {
+                    groups.entry(to).or_default().push((from, to));
+                } else if deleted_nodes.contains(&from) {
+                    groups.entry(from).or_default().push((from, to));
+                } else {
+                    deleted_edges.push((from, to));
+                }
+            }
+        }
+        for id in deleted_nodes {
+            let edges = groups.remove(&id).unwrap_or_default();
+            let diff = if target(1 + edges.len()) {
+                &mut first
+            } else {
+                &mut rest
+            };
+            diff.nodes.deleted.insert(id);
+            for (from, to) in edges {
+                diff.edges.deleted.entry(from).or_default().insert(to);
+            }
+        }
+        for (id, update) in self.nodes.new_or_updated {
+            let diff = if target(1) { &mut first } else { &mut rest };
+            diff.nodes.new_or_updated.insert(id, update);
+        }
+        for (from, to_weight) in self.edges.new_or_updated {
+            for (to, weight) in to_weight {
+                let diff = if target(1) { &mut first } else { &mut rest };
+                diff.edges
+                    .new_or_updated
+                    .entry(from)
+                    .or_default()
+                    .insert(to, weight);
+            }
+        }
+        for (from, to) in deleted_edges {
+            let diff = if target(1) { &mut first } else { &mut rest };
+            diff.edges.deleted.entry(from).or_default().insert(to);
+        }
+        (first, rest)
+    }
+
+    /// The part of the diff restricted to a set of nodes: their updates and deletions and
+    /// the new, updated or deleted edges with both endpoints in the set.
+    pub fn subgraph(&self, nodes: &HashSet<Id>) -> GraphDiff<Id, T, W>
+    where
+        T: Clone,
+    {
+        let mut subgraph = GraphDiff::default();
+        for (id, update) in self.iter_new_or_updated_nodes() {
+            if nodes.contains(id) {
+                subgraph.nodes.new_or_updated.insert(*id, update.clone());
+            }
+        }
+        subgraph.nodes.deleted = self.nodes.deleted.intersection(nodes).copied().collect();
+        for (from, to, weight) in self.iter_new_or_updated_edges() {
+            if nodes.contains(from) && nodes.contains(to) {
+                let to_weight = subgraph.edges.new_or_updated.entry(*from).or_default();
+                to_weight.insert(*to, *weight);
+            }
+        }
+        for (from, to) in self.iter_deleted_edges() {
+            if nodes.contains(from) && nodes.contains(to) {
+                subgraph.edges.deleted.entry(*from).or_default().insert(*to);
+            }
+        }
+        subgraph
+    }
+
+    /// Keep only the new or updated nodes for which `f` returns `true`.
+    /// Deleted nodes are kept.
+    pub fn retain_nodes(&mut self, f: impl Fn(&Id, &T) -> bool) {
+        self.nodes.new_or_updated.retain(|id, update| f(id, update));
+    }
+
+    /// Keep only the new or updated edges for which `f` returns `true`.
+    /// Deleted edges are kept.
+    pub fn retain_edges(&mut self, f: impl Fn(&Id, &Id, &W) -> bool) {
+        for (from, to_weight) in self.edges.new_or_updated.iter_mut() {
+            to_weight.retain(|to, weight| f(from, to, weight));
+        }
+        self.edges.new_or_updated.retain(|_, e| !e.is_empty());
+    }
+
+    /// Keep only the new or updated edges with a weight in `min..=max`.
+    /// Deleted edges are kept. NaN weights are outside any range.
+    pub fn filter_edges_by_weight(&mut self, min: W, max: W)
+    where
+        W: PartialOrd,
+    {
+        self.retain_edges(|_, _, weight| min <= *weight && *weight <= max);
+    }
+
+    /// Remove entries that do not change the graph once the diff is applied: nodes with an
+    /// empty update that are not an endpoint of a new or updated edge, and empty edge maps.
+    ///
+    /// Note that this drops nodes that were added without properties and without edges.
+    pub fn compact(&mut self) -> CompactReport
+    where
+        T: PartialEq,
+    {
+        let nodes = self.nodes.new_or_updated.len();
+        {
+            let mut endpoints = HashSet::new();
+            for (from, to_weight) in self.edges.new_or_updated.iter() {
+                if !to_weight.is_empty() {
+                    endpoints.insert(from);
+                    endpoints.extend(to_weight.keys());
+                }
+            }
+            let empty = T::default();
+            (self.nodes.new_or_updated)
+                .retain(|id, update| *update != empty || endpoints.contains(id));
+        }
+
+        let edge_maps = self.edges.new_or_updated.len() + self.edges.deleted.len();
+        self.edges.new_or_updated.retain(|_, e| !e.is_empty());
+        self.edges.deleted.retain(|_, e| !e.is_empty());
+        CompactReport {
+            empty_nodes: nodes - self.nodes.new_or_updated.len(),
+            empty_edge_maps: edge_maps - self.edges.new_or_updated.len() - self.edges.deleted.len(),
+        }
+    }
+
+    /// Remove the new or updated edges with an endpoint that is not a new or updated node,
+    /// making the diff self-contained. Returns the number of removed edges.
+    ///
+    /// If `include_deleted` is `true`, deleted edges are pruned as well, keeping those whose
+    /// endpoints are new, updated or deleted nodes of the diff.
+    pub fn prune_dangling_edges(&mut self, include_deleted: bool) -> usize {
+        let nodes = &self.nodes;
+        let mut pruned = 0;
+        for (from, to_weight) in self.edges.new_or_updated.iter_mut() {
+            let len = to_weight.len();
+            if nodes.new_or_updated.contains_key(from) {
+                to_weight.retain(|to, _| nodes.new_or_updated.contains_key(to));
+            } else {
+                to_weight.clear();
+            }
+            pruned += len - to_weight.len();
+        }
+        self.edges.new_or_updated.retain(|_, e| !e.is_empty());
+
+        if include_deleted {
+            let in_diff =
+                |id: &Id| nodes.new_or_updated.contains_key(id) || nodes.deleted.contains(id);
+            for (from, to_set) in self.edges.deleted.iter_mut() {
+                let len = to_set.len();
+                if in_diff(from) {
+                    to_set.retain(|to| in_diff(to));
+                } else {
+                    to_set.clear();
+                }
+                pruned += len - to_set.len();
+            }
+            self.edges.deleted.retain(|_, e| !e.is_empty());
+        }
+        pruned
+    }
+
+    /// Remove a new or updated node from the diff.
+    /// Returns the update if there was one.
+    pub fn remove_updated_node(&mut self, id: &Id) -> Option<T> {
+        self.nodes.new_or_updated.remove(id)
+    }
+
+    /// Remove a node marked for deletion from the diff.
+    /// Returns `true` if the node was marked for deletion.
+    pub fn remove_deleted_node(&mut self, id: &Id) -> bool {
+        self.nodes.deleted.remove(id)
+    }
+
+    /// Remove a new updated edge from the diff.
+    /// Returns the updated weight if there was one.
+    pub fn remove_updated_edge(&mut self, from: &Id, to: &Id) -> Option<W> {
+        let removed = self
+            .edges
             .new_or_updated
             .get_mut(from)
             .and_then(|edges| edges.remove(to));
@@ -290,292 +1334,3065 @@ impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> GraphDif
         deleted
     }
 
-    #[cfg(test)]
-    fn is_internally_consistent(&self) -> bool {
+    /// Apply the diff to a graph stored as a map of nodes and an adjacency map of edges.
+    ///
+    /// New or updated nodes are combined with existing ones using `AddAssign` and new or
+    /// updated edges overwrite existing weights. Deletions are applied afterwards so that
+    /// they always win over stale updates: deleted nodes are removed together with all
+    /// their incident edges. Nodes left without outgoing edges are removed from `edges`.
+    pub fn apply(&self, nodes: &mut HashMap<Id, T>, edges: &mut HashMap<Id, HashMap<Id, W>>)
+    where
+        T: Clone,
+    {
+        for (id, update) in self.nodes.new_or_updated.iter() {
+            if let Some(node) = nodes.get_mut(id) {
+                *node += update.clone();
+            } else {
+                nodes.insert(*id, update.clone());
+            }
+        }
         for (from, to_weight) in self.edges.new_or_updated.iter() {
-            if self.nodes.deleted.contains(from) {
-                return false;
+            edges
+                .entry(*from)
+                .or_default()
+                .extend(to_weight.iter().map(|(to, weight)| (*to, *weight)));
+        }
+        self.apply_deletions(nodes, edges);
+    }
+
+    /// Report what `apply` would change in a graph, without changing it.
+    ///
+    /// New or updated nodes are inserts if they are not in `nodes` and updates otherwise.
+    /// New or updated edges are inserts if they are not in `edges` and updates if they change
+    /// the weight, setting an edge to its current weight is not reported. Deletions only list
+    /// nodes and edges of the graph, including the edges removed with a deleted node.
+    pub fn plan_apply(
+        &self,
+        nodes: &HashMap<Id, T>,
+        edges: &HashMap<Id, HashMap<Id, W>>,
+    ) -> ApplyPlan<Id> {
+        let mut plan = ApplyPlan::default();
+        for id in self.nodes.new_or_updated.keys() {
+            if nodes.contains_key(id) {
+                plan.node_updates.push(*id);
+            } else {
+                plan.node_inserts.push(*id);
+            }
+        }
+        for (from, to, weight) in self.iter_new_or_updated_edges() {
+            match edges.get(from).and_then(|e| e.get(to)) {
+                None => plan.edge_inserts.push((*from, *to)),
+                Some(current) if current != weight => plan.edge_updates.push((*from, *to)),
+                Some(_) => {}
             }
-            for (to, _) in to_weight.iter() {
-                if self.nodes.deleted.contains(to) {
-                    return false;
+        }
+        let deleted = &self.nodes.deleted;
+        plan.node_deletes
+            .extend(deleted.iter().filter(|id| nodes.contains_key(*id)));
+        for (from, to_weight) in edges.iter() {
+            for to in to_weight.keys() {
+                if deleted.contains(from) || deleted.contains(to) || self.is_edge_deleted(from, to)
+                {
+                    plan.edge_deletes.push((*from, *to));
                 }
             }
         }
+        plan
+    }
+
+    /// Apply the diff to a graph like `apply`, letting `resolver` decide each conflict
+    /// between the graph and the diff.
+    ///
+    /// A node conflicts if the diff updates properties of an existing node to different
+    /// values, an edge if the diff changes the weight of an existing edge. The resolver is
+    /// called with a `Conflict` whose `losing` side is the graph's value and `winning` side
+    /// the diff's. Deletions are applied as in `apply` and never conflict.
+    pub fn apply_with(
+        &self,
+        nodes: &mut HashMap<Id, T>,
+        edges: &mut HashMap<Id, HashMap<Id, W>>,
+        mut resolver: impl FnMut(Conflict<Id, T, W>) -> Resolution<T, W>,
+    ) where
+        T: Clone + Conflicts,
+    {
+        for (id, update) in self.nodes.new_or_updated.iter() {
+            let Some(node) = nodes.get_mut(id) else {
+                nodes.insert(*id, update.clone());
+                continue;
+            };
+            let Some((losing, winning)) = node.conflict(update) else {
+                *node += update.clone();
+                continue;
+            };
+            let conflict = Conflict::Node {
+                id: *id,
+                losing: losing.clone(),
+                winning,
+            };
+            let resolution = resolver(conflict);
+            *node += update.clone();
+            match resolution {
+                Resolution::KeepMine => *node += losing,
+                Resolution::MergeNode(merged) => *node += merged,
+                Resolution::TakeTheirs | Resolution::MergeEdge(_) => {}
+            }
+        }
+        for (from, to, winning) in self.iter_new_or_updated_edges() {
+            let to_weight = edges.entry(*from).or_default();
+            let weight = match to_weight.get(to) {
+                Some(losing) if losing != winning => {
+                    let conflict = Conflict::Edge {
+                        from: *from,
+                        to: *to,
+                        losing: *losing,
+                        winning: *winning,
+                    };
+                    match resolver(conflict) {
+                        Resolution::KeepMine => *losing,
+                        Resolution::MergeEdge(merged) => merged,
+                        Resolution::TakeTheirs | Resolution::MergeNode(_) => *winning,
+                    }
+                }
+                _ => *winning,
+            };
+            to_weight.insert(*to, weight);
+        }
+        self.apply_deletions(nodes, edges);
+    }
+
+    /// Apply the node and edge deletions of the diff to a graph, see `apply`.
+    fn apply_deletions(&self, nodes: &mut HashMap<Id, T>, edges: &mut HashMap<Id, HashMap<Id, W>>) {
         for (from, to_set) in self.edges.deleted.iter() {
-            if self.nodes.deleted.contains(from) {
-                return false;
+            if let Some(to_weight) = edges.get_mut(from) {
+                to_weight.retain(|to, _| !to_set.contains(to));
             }
-            for to in to_set.iter() {
-                if self.nodes.deleted.contains(to) {
-                    return false;
+        }
+        for id in self.nodes.deleted.iter() {
+            nodes.remove(id);
+            edges.remove(id);
+        }
+        if !self.nodes.deleted.is_empty() {
+            for to_weight in edges.values_mut() {
+                to_weight.retain(|to, _| !self.nodes.deleted.contains(to));
+            }
+        }
+        edges.retain(|_, e| !e.is_empty());
+    }
+
+    /// Transform the weights of the new or updated edges, leaving nodes and deleted edges
+    /// untouched.
+    pub fn map_weights<W2>(self, f: impl Fn(W) -> W2) -> GraphDiff<Id, T, W2> {
+        let new_or_updated = self
+            .edges
+            .new_or_updated
+            .into_iter()
+            .map(|(from, to_weight)| {
+                let to_weight = to_weight.into_iter().map(|(to, w)| (to, f(w))).collect();
+                (from, to_weight)
+            })
+            .collect();
+        GraphDiff {
+            nodes: self.nodes,
+            edges: EdgeDiff::new(new_or_updated, self.edges.deleted),
+            meta: self.meta,
+            order: self.order,
+        }
+    }
+
+    /// Remap the node ids of the diff.
+    ///
+    /// If several ids map to the same id, their node updates are combined with `+=` and
+    /// their edges overwrite each other, both in ascending order of the original ids, so the
+    /// result does not depend on the iteration order of the maps. Deletions win over updates:
+    /// a node or edge deleted under any of its original ids is deleted in the result.
+    pub fn map_ids<Id2>(self, f: impl Fn(&Id) -> Id2) -> GraphDiff<Id2, T, W>
+    where
+        Id: Ord,
+        Id2: Hash + Eq + Copy,
+    {
+        let GraphDiff {
+            nodes, edges, meta, ..
+        } = self;
+        let mut updates = nodes.new_or_updated.into_iter().collect::<Vec<_>>();
+        updates.sort_unstable_by_key(|(id, _)| *id);
+        let mut weights = edges
+            .new_or_updated
+            .into_iter()
+            .flat_map(|(from, to_weight)| to_weight.into_iter().map(move |(to, w)| (from, to, w)))
+            .collect::<Vec<_>>();
+        weights.sort_unstable_by_key(|(from, to, _)| (*from, *to));
+
+        let mut mapped = GraphDiff::new();
+        mapped.meta = meta;
+        for (id, update) in updates {
+            mapped.add_or_update_node(&f(&id), update);
+        }
+        for (from, to, weight) in weights {
+            mapped
+                .edges
+                .new_or_updated
+                .entry(f(&from))
+                .or_default()
+                .insert(f(&to), weight);
+        }
+        for (from, to_set) in edges.deleted {
+            for to in to_set {
+                mapped.delete_edge(&f(&from), &f(&to));
+            }
+        }
+        for id in nodes.deleted {
+            mapped.delete_node(f(&id));
+        }
+        mapped
+    }
+
+    /// Compute the changes turning `previous` into `self`, for storing a series of diffs as
+    /// deltas.
+    ///
+    /// Unlike `+=`, a delta can also drop entries, so it holds two diffs: `changed` with the
+    /// entries of `self` that are missing from or differ in `previous`, and `removed` with
+    /// the entries of `previous` that `self` no longer contains. Both are subsets of
+    /// consistent diffs and therefore consistent themselves.
+    pub fn delta_from(&self, previous: &Self) -> GraphDiffDelta<Id, T, W>
+    where
+        T: Clone + PartialEq,
+    {
+        let mut changed = GraphDiff::new();
+        let mut removed = GraphDiff::new();
+        for (id, update) in self.nodes.new_or_updated.iter() {
+            if previous.nodes.new_or_updated.get(id) != Some(update) {
+                changed.nodes.new_or_updated.insert(*id, update.clone());
+            }
+        }
+        for (id, update) in previous.nodes.new_or_updated.iter() {
+            if !self.nodes.new_or_updated.contains_key(id) {
+                removed.nodes.new_or_updated.insert(*id, update.clone());
+            }
+        }
+        changed.nodes.deleted = self
+            .nodes
+            .deleted
+            .difference(&previous.nodes.deleted)
+            .copied()
+            .collect();
+        removed.nodes.deleted = previous
+            .nodes
+            .deleted
+            .difference(&self.nodes.deleted)
+            .copied()
+            .collect();
+
+        for (from, to, weight) in self.iter_new_or_updated_edges() {
+            if previous.edge_weight(from, to) != Some(weight) {
+                changed
+                    .edges
+                    .new_or_updated
+                    .entry(*from)
+                    .or_default()
+                    .insert(*to, *weight);
+            }
+        }
+        for (from, to, weight) in previous.iter_new_or_updated_edges() {
+            if self.edge_weight(from, to).is_none() {
+                removed
+                    .edges
+                    .new_or_updated
+                    .entry(*from)
+                    .or_default()
+                    .insert(*to, *weight);
+            }
+        }
+        for (from, to) in self.iter_deleted_edges() {
+            if !previous.is_edge_deleted(from, to) {
+                changed.edges.deleted.entry(*from).or_default().insert(*to);
+            }
+        }
+        for (from, to) in previous.iter_deleted_edges() {
+            if !self.is_edge_deleted(from, to) {
+                removed.edges.deleted.entry(*from).or_default().insert(*to);
+            }
+        }
+        GraphDiffDelta { changed, removed }
+    }
+
+    /// Reconstruct a diff from the diff preceding it and the delta returned by `delta_from`.
+    ///
+    /// Entries of `delta.removed` are dropped from `previous`, then entries of
+    /// `delta.changed` are inserted, replacing rather than combining with existing entries.
+    pub fn rebase(mut previous: Self, delta: GraphDiffDelta<Id, T, W>) -> GraphDiff<Id, T, W> {
+        let GraphDiffDelta { changed, removed } = delta;
+        for id in removed.nodes.new_or_updated.keys() {
+            previous.nodes.new_or_updated.remove(id);
+        }
+        for id in removed.nodes.deleted.iter() {
+            previous.nodes.deleted.remove(id);
+        }
+        for (from, to, _) in removed.iter_new_or_updated_edges() {
+            if let Some(to_weight) = previous.edges.new_or_updated.get_mut(from) {
+                to_weight.remove(to);
+            }
+        }
+        for (from, to) in removed.iter_deleted_edges() {
+            if let Some(to_set) = previous.edges.deleted.get_mut(from) {
+                to_set.remove(to);
+            }
+        }
+        previous.edges.new_or_updated.retain(|_, e| !e.is_empty());
+        previous.edges.deleted.retain(|_, e| !e.is_empty());
+
+        previous
+            .nodes
+            .new_or_updated
+            .extend(changed.nodes.new_or_updated);
+        previous.nodes.deleted.extend(changed.nodes.deleted);
+        for (from, to_weight) in changed.edges.new_or_updated {
+            previous
+                .edges
+                .new_or_updated
+                .entry(from)
+                .or_default()
+                .extend(to_weight);
+        }
+        for (from, to_set) in changed.edges.deleted {
+            previous
+                .edges
+                .deleted
+                .entry(from)
+                .or_default()
+                .extend(to_set);
+        }
+        previous
+    }
+
+    /// Compare two diffs, reporting the nodes and edges that are new, updated or deleted in
+    /// only one of them and those present in both with different values.
+    ///
+    /// A node or edge that is updated in one diff and deleted in the other counts as
+    /// differing.
+    pub fn symmetric_difference(&self, other: &Self) -> SymmetricDifference<Id>
+    where
+        T: PartialEq,
+    {
+        let mut difference = SymmetricDifference::default();
+        let node_ids = self
+            .nodes
+            .new_or_updated
+            .keys()
+            .chain(self.nodes.deleted.iter());
+        for id in node_ids
+            .chain(other.nodes.new_or_updated.keys())
+            .chain(other.nodes.deleted.iter())
+        {
+            match (self.node_state(id), other.node_state(id)) {
+                (Some(_), None) => difference.nodes_only_in_self.insert(*id),
+                (None, Some(_)) => difference.nodes_only_in_other.insert(*id),
+                (Some(mine), Some(theirs)) if mine != theirs => {
+                    difference.nodes_differing.insert(*id)
+                }
+                _ => false,
+            };
+        }
+
+        let edges = self
+            .iter_new_or_updated_edges()
+            .map(|(from, to, _)| (from, to))
+            .chain(self.iter_deleted_edges())
+            .chain(
+                other
+                    .iter_new_or_updated_edges()
+                    .map(|(from, to, _)| (from, to)),
+            )
+            .chain(other.iter_deleted_edges());
+        for (from, to) in edges {
+            let edge = (*from, *to);
+            match (self.edge_state(from, to), other.edge_state(from, to)) {
+                (Some(_), None) => difference.edges_only_in_self.insert(edge),
+                (None, Some(_)) => difference.edges_only_in_other.insert(edge),
+                (Some(mine), Some(theirs)) if mine != theirs => {
+                    difference.edges_differing.insert(edge)
+                }
+                _ => false,
+            };
+        }
+        difference
+    }
+
+    /// The update of a node, `Some(None)` if it is deleted and `None` if it is not in the diff.
+    fn node_state(&self, id: &Id) -> Option<Option<&T>> {
+        match self.nodes.new_or_updated.get(id) {
+            Some(update) => Some(Some(update)),
+            None => self.nodes.deleted.contains(id).then_some(None),
+        }
+    }
+
+    /// The weight of an edge, `Some(None)` if it is deleted and `None` if it is not in the diff.
+    fn edge_state(&self, from: &Id, to: &Id) -> Option<Option<W>> {
+        match self.edge_weight(from, to) {
+            Some(weight) => Some(Some(*weight)),
+            None => self.is_edge_deleted(from, to).then_some(None),
+        }
+    }
+
+    /// Order-independent hash of the contents of the diff.
+    ///
+    /// Equal diffs have equal fingerprints regardless of the order they were built in.
+    /// Weights are hashed by bit pattern, with `-0.0` and NaN normalized. Fingerprints are
+    /// stable within a build of the library but may change between versions. Requires the
+    /// `std` feature.
+    #[cfg(feature = "std")]
+    pub fn fingerprint(&self) -> u64
+    where
+        Id: Hash,
+        T: Hash,
+        W: Into<f64>,
+    {
+        fn hash_one(item: impl Hash) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            item.hash(&mut hasher);
+            hasher.finish()
+        }
+        // the tags keep e.g. an updated node from colliding with a deleted one
+        let nodes = self.iter_new_or_updated_nodes().map(|n| hash_one((0u8, n)));
+        let deleted_nodes = self.iter_deleted_nodes().map(|id| hash_one((1u8, id)));
+        let edges = self
+            .iter_new_or_updated_edges()
+            .map(|(from, to, w)| hash_one((2u8, from, to, float_bits((*w).into()))));
+        let deleted_edges = self.iter_deleted_edges().map(|e| hash_one((3u8, e)));
+        nodes
+            .chain(deleted_nodes)
+            .chain(edges)
+            .chain(deleted_edges)
+            .fold(0, u64::wrapping_add)
+    }
+
+    /// Compute the diff that undoes `self`.
+    ///
+    /// `base_nodes` and `base_edges` must reflect the state of the graph *before* `self` was
+    /// applied to it, otherwise the previous node properties and edge weights cannot be
+    /// recovered. Nodes added by `self` are deleted, deleted nodes are re-added with their
+    /// previous update and incident edges, and edge weights revert to their previous values.
+    /// Updated nodes are reverted field by field as described by [`Invert`].
+    pub fn invert(
+        &self,
+        base_nodes: &HashMap<Id, T>,
+        base_edges: &HashMap<Id, HashMap<Id, W>>,
+    ) -> GraphDiff<Id, T, W>
+    where
+        T: Clone + Invert,
+    {
+        let mut inverse = GraphDiff::default();
+        let base_weight = |from: &Id, to: &Id| base_edges.get(from).and_then(|e| e.get(to));
+        let is_added =
+            |id: &Id| self.nodes.new_or_updated.contains_key(id) && !base_nodes.contains_key(id);
+
+        // edges touching added nodes go away with the node deletion below
+        for (from, to_weight) in self.edges.new_or_updated.iter() {
+            for to in to_weight.keys() {
+                if is_added(from) || is_added(to) {
+                    continue;
+                }
+                match base_weight(from, to) {
+                    Some(weight) => {
+                        let _ = inverse.add_edge(from, to, *weight);
+                    }
+                    None => inverse.delete_edge(from, to),
+                }
+            }
+        }
+        for (from, to_set) in self.edges.deleted.iter() {
+            for to in to_set {
+                if let Some(weight) = base_weight(from, to) {
+                    let _ = inverse.add_edge(from, to, *weight);
+                }
+            }
+        }
+
+        for id in self.nodes.deleted.iter() {
+            if let Some(node) = base_nodes.get(id) {
+                inverse.set_node_update(id, node.clone());
+            }
+        }
+        if !self.nodes.deleted.is_empty() {
+            for (from, to_weight) in base_edges.iter() {
+                for (to, weight) in to_weight.iter() {
+                    if self.nodes.deleted.contains(from) || self.nodes.deleted.contains(to) {
+                        let _ = inverse.add_edge(from, to, *weight);
+                    }
+                }
+            }
+        }
+
+        for (id, update) in self.nodes.new_or_updated.iter() {
+            match base_nodes.get(id) {
+                Some(base) => inverse.add_or_update_node(id, update.invert(base)),
+                None => inverse.delete_node(*id),
+            }
+        }
+        inverse
+    }
+
+    /// Returns `true` if no new or updated edge references a deleted node.
+    ///
+    /// Deleted edges may reference deleted nodes, `delete_node` marks the incoming edges of
+    /// the node as deleted.
+    ///
+    /// This always holds for diffs built with the public methods, but not necessarily for
+    /// diffs assembled with `from_diffs`. Useful as a debug assertion:
+    /// ```
+    /// use drisk_api::{EdgeDiff, GraphDiff, NodeDiff};
+    /// use hashbrown::{HashMap, HashSet};
+    ///
+    /// let nodes = NodeDiff::<u32, u32>::new(HashMap::new(), HashSet::from([2]));
+    /// let edges = EdgeDiff::new(HashMap::from([(1, HashMap::from([(2, 1.0)]))]), HashMap::new());
+    /// let diff = GraphDiff::from_diffs(nodes, edges);
+    /// assert!(!diff.is_internally_consistent());
+    /// ```
+    pub fn is_internally_consistent(&self) -> bool {
+        self.inconsistent_edge().is_none()
+    }
+
+    /// Check the diff against the nodes of the graph it is about to be applied to.
+    ///
+    /// Every endpoint of a new or updated edge must be in `base_nodes` or be a new or
+    /// updated node of the diff, and every deleted node and endpoint of a deleted edge must
+    /// be in `base_nodes`. Returns all issues found.
+    pub fn validate_against(
+        &self,
+        base_nodes: &HashSet<Id>,
+    ) -> Result<(), Vec<ValidationIssue<Id>>> {
+        let exists =
+            |id: &Id| base_nodes.contains(id) || self.nodes.new_or_updated.contains_key(id);
+        let mut issues = Vec::new();
+        for (from, to, _) in self.iter_new_or_updated_edges() {
+            if !exists(from) || !exists(to) {
+                issues.push(ValidationIssue::DanglingEdge {
+                    from: *from,
+                    to: *to,
+                });
+            }
+        }
+        for (from, to) in self.iter_deleted_edges() {
+            if !base_nodes.contains(from) || !base_nodes.contains(to) {
+                issues.push(ValidationIssue::MissingEdge {
+                    from: *from,
+                    to: *to,
+                });
+            }
+        }
+        for id in self.iter_deleted_nodes() {
+            if !base_nodes.contains(id) {
+                issues.push(ValidationIssue::MissingNode(*id));
+            }
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+
+    /// Report redundant or contradictory entries, such as those left by the unchecked
+    /// constructors. The diff can still be applied, but may not do what was intended.
+    pub fn lint(&self) -> Vec<LintWarning<Id>>
+    where
+        T: PartialEq,
+    {
+        let mut warnings = Vec::new();
+        for id in self.iter_deleted_nodes() {
+            if self.nodes.new_or_updated.contains_key(id) {
+                warnings.push(LintWarning::NodeUpdatedAndDeleted(*id));
+            }
+        }
+        let mut referenced = HashSet::new();
+        for (from, to_weight) in self.edges.new_or_updated.iter() {
+            if to_weight.is_empty() {
+                warnings.push(LintWarning::EmptyEdgeMap(*from));
+            }
+            for to in to_weight.keys() {
+                if self.nodes.deleted.contains(from) || self.nodes.deleted.contains(to) {
+                    warnings.push(LintWarning::EdgeToDeletedNode {
+                        from: *from,
+                        to: *to,
+                    });
+                }
+                if self.is_edge_deleted(from, to) {
+                    warnings.push(LintWarning::EdgeAddedAndDeleted {
+                        from: *from,
+                        to: *to,
+                    });
                 }
+                referenced.insert(*from);
+                referenced.insert(*to);
+            }
+        }
+        for (from, to) in self.edges.deleted.iter() {
+            if to.is_empty() {
+                warnings.push(LintWarning::EmptyEdgeMap(*from));
             }
         }
-        true
+        let empty = T::default();
+        for (id, update) in self.iter_new_or_updated_nodes() {
+            if *update == empty && !referenced.contains(id) {
+                warnings.push(LintWarning::UnreferencedEmptyNode(*id));
+            }
+        }
+        warnings
+    }
+
+    /// Returns `true` if `validate_against` finds no issues.
+    pub fn is_consistent_with(&self, base_nodes: &HashSet<Id>) -> bool {
+        self.validate_against(base_nodes).is_ok()
+    }
+
+    /// Find a new or updated edge that references a node marked for deletion.
+    fn inconsistent_edge(&self) -> Option<(Id, Id)> {
+        let is_deleted = |id: &Id| self.nodes.deleted.contains(id);
+        self.iter_new_or_updated_edges()
+            .find(|(from, to, _)| is_deleted(from) || is_deleted(to))
+            .map(|(from, to, _)| (*from, *to))
+    }
+
+    /// Mark a node as deleted without removing the edges connected to it.
+    pub(crate) fn delete_node_unchecked(&mut self, node_id: Id) {
+        self.nodes.new_or_updated.remove(&node_id);
+        self.nodes.deleted.insert(node_id);
+        self.order.deleted_node(&node_id);
     }
 }
 
-impl<Id: Hash + Eq + Copy, T: Default + AddAssign> AddAssign for GraphDiff<Id, T> {
+impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> AddAssign
+    for GraphDiff<Id, T, W>
+{
     fn add_assign(&mut self, other: Self) {
+        self.order.extend(other.order);
         *self += other.nodes;
         *self += other.edges;
     }
 }
 
-impl<Id: Hash + Eq + Copy, T: Default + AddAssign> AddAssign<EdgeDiff<Id>> for GraphDiff<Id, T> {
-    fn add_assign(&mut self, edges: EdgeDiff<Id>) {
-        for (from, to_weight) in edges.new_or_updated {
-            for (to, weight) in to_weight {
-                let _ = self.add_edge(&from, &to, weight);
+impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> AddAssign<EdgeDiff<Id, W>>
+    for GraphDiff<Id, T, W>
+{
+    /// Edges touching a deleted node, and self-loops if rejected, are skipped.
+    fn add_assign(&mut self, mut edges: EdgeDiff<Id, W>) {
+        let deleted_nodes = &self.nodes.deleted;
+        let reject_self_loops = self.edges.reject_self_loops;
+        for (from, to_weight) in edges.new_or_updated.iter_mut() {
+            if deleted_nodes.contains(from) {
+                to_weight.clear();
+            } else {
+                to_weight.retain(|to, _| {
+                    !(deleted_nodes.contains(to) || reject_self_loops && from == to)
+                });
             }
         }
-        for (from, to) in edges.deleted {
-            for to in to {
-                self.delete_edge(&from, &to);
+        edges
+            .new_or_updated
+            .retain(|_, to_weight| !to_weight.is_empty());
+        self.edges += edges;
+    }
+}
+
+impl<Id: Hash + Eq + Copy, T: Default + AddAssign, W: Copy + PartialEq> AddAssign<NodeDiff<Id, T>>
+    for GraphDiff<Id, T, W>
+{
+    /// Deleted nodes also have their edges deleted, as with `delete_node`.
+    fn add_assign(&mut self, nodes: NodeDiff<Id, T>) {
+        for node_id in nodes.deleted.iter() {
+            self.delete_node(*node_id);
+        }
+        self.nodes += nodes;
+    }
+}
+
+impl<Id: Hash + Eq + Copy + Display, T: Default + AddAssign, W: Copy + PartialEq> Display
+    for GraphDiff<Id, T, W>
+{
+    /// A one-line summary of the number of changes, for example
+    /// `GraphDiff(nodes: 2 new or updated, 1 deleted; edges: 1 new or updated, 0 deleted)`.
+    ///
+    /// The alternate form `{:#}` follows the summary with one line per change: `+ node 1`,
+    /// `- node 2`, `+ edge 1 -> 3` or `- edge 2 -> 3`, in no particular order.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let new_or_updated_edges: usize = self.edges.new_or_updated.values().map(|e| e.len()).sum();
+        write!(
+            f,
+            "GraphDiff(nodes: {} new or updated, {} deleted; edges: {} new or updated, {} deleted)",
+            self.nodes.new_or_updated.len(),
+            self.nodes.deleted.len(),
+            new_or_updated_edges,
+            self.deleted_edge_count(),
+        )?;
+        if !f.alternate() {
+            return Ok(());
+        }
+        for id in self.nodes.new_or_updated.keys() {
+            write!(f, "\n  + node {}", id)?;
+        }
+        for id in self.nodes.deleted.iter() {
+            write!(f, "\n  - node {}", id)?;
+        }
+        for (from, to_weight) in self.edges.new_or_updated.iter() {
+            for to in to_weight.keys() {
+                write!(f, "\n  + edge {} -> {}", from, to)?;
+            }
+        }
+        for (from, to_set) in self.edges.deleted.iter() {
+            for to in to_set.iter() {
+                write!(f, "\n  - edge {} -> {}", from, to)?;
             }
         }
+        Ok(())
+    }
+}
+
+/// The new or updated edges of a `GraphDiff` indexed by `to` node, see
+/// `GraphDiff::reverse_index`.
+pub struct ReverseIndex<'a, Id, W> {
+    incoming: HashMap<&'a Id, Vec<(&'a Id, &'a W)>>,
+}
+
+impl<'a, Id: Hash + Eq, W> ReverseIndex<'a, Id, W> {
+    /// Get the edges entering a node as `(from, weight)`.
+    pub fn incoming(&self, to: &Id) -> &[(&'a Id, &'a W)] {
+        self.incoming.get(to).map_or(&[], |edges| edges.as_slice())
+    }
+}
+
+/// Nodes and edges that differ between two diffs, see `GraphDiff::symmetric_difference`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymmetricDifference<Id: Hash + Eq> {
+    pub nodes_only_in_self: HashSet<Id>,
+    pub nodes_only_in_other: HashSet<Id>,
+    pub nodes_differing: HashSet<Id>,
+    pub edges_only_in_self: HashSet<(Id, Id)>,
+    pub edges_only_in_other: HashSet<(Id, Id)>,
+    pub edges_differing: HashSet<(Id, Id)>,
+}
+
+impl<Id: Hash + Eq> Default for SymmetricDifference<Id> {
+    fn default() -> SymmetricDifference<Id> {
+        SymmetricDifference {
+            nodes_only_in_self: HashSet::new(),
+            nodes_only_in_other: HashSet::new(),
+            nodes_differing: HashSet::new(),
+            edges_only_in_self: HashSet::new(),
+            edges_only_in_other: HashSet::new(),
+            edges_differing: HashSet::new(),
+        }
+    }
+}
+
+impl<Id: Hash + Eq> SymmetricDifference<Id> {
+    /// Returns `true` if the compared diffs are equal.
+    pub fn is_empty(&self) -> bool {
+        self.nodes_only_in_self.is_empty()
+            && self.nodes_only_in_other.is_empty()
+            && self.nodes_differing.is_empty()
+            && self.edges_only_in_self.is_empty()
+            && self.edges_only_in_other.is_empty()
+            && self.edges_differing.is_empty()
+    }
+}
+
+/// Metadata about a `GraphDiff`, separate from its contents.
+///
+/// Serialized by `graph_diff_to_bytes` in a length-prefixed section ahead of the nodes and
+/// edges, so readers skip fields added by later versions of the library.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct DiffMeta {
+    /// Name of the application that produced the diff.
+    pub producer: Option<String>,
+    /// Creation time in seconds since the Unix epoch.
+    pub timestamp: Option<u64>,
+    /// Version of the producer's node update schema.
+    pub schema_version: Option<u32>,
+}
+
+/// Changes turning one `GraphDiff` into another, see `GraphDiff::delta_from`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GraphDiffDelta<Id: Hash + Eq + Copy, T: Default + AddAssign, W = f32> {
+    /// Entries that are new or differ from the previous diff.
+    pub changed: GraphDiff<Id, T, W>,
+    /// Entries of the previous diff that are gone.
+    pub removed: GraphDiff<Id, T, W>,
+}
+
+/// What `GraphDiff::compact` removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompactReport {
+    /// Number of removed nodes with an empty update.
+    pub empty_nodes: usize,
+    /// Number of removed empty maps of new or updated or of deleted edges.
+    pub empty_edge_maps: usize,
+}
+
+/// Summary counts of a `GraphDiff`, see `GraphDiff::stats`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DiffStats {
+    pub new_or_updated_nodes: usize,
+    pub deleted_nodes: usize,
+    /// Number of new or updated edges, summed over all `from` nodes.
+    pub new_or_updated_edges: usize,
+    /// Number of deleted edges, summed over all `from` nodes.
+    pub deleted_edges: usize,
+    /// Number of distinct nodes that are an endpoint of a new, updated or deleted edge.
+    pub distinct_endpoints: usize,
+}
+
+/// A node update that can compute the update reverting it.
+///
+/// Used by [`GraphDiff::invert`] to restore the properties overwritten by a forward update.
+pub trait Invert {
+    /// Returns the update that restores `base` after `self` has been added to it.
+    fn invert(&self, base: &Self) -> Self;
+}
+
+/// A node update that can report where it conflicts with another update.
+///
+/// Used by [`GraphDiff::add_assign_checked`] to detect node properties set to different
+/// values by both sides.
+pub trait Conflicts: Sized {
+    /// Returns the conflicting parts of `self` and `other`, or `None` if they agree on
+    /// every property they both update.
+    fn conflict(&self, other: &Self) -> Option<(Self, Self)>;
+}
+
+/// A node update that can keep only what it has in common with another update.
+///
+/// Used by [`GraphDiff::intersection`] to find the node properties all diffs agree on.
+pub trait Intersect {
+    /// Returns the parts of the update that `other` makes as well.
+    fn intersect(&self, other: &Self) -> Self;
+}
+
+/// A node or edge updated to different values by two diffs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Conflict<Id, T, W> {
+    /// Conflicting parts of the node updates.
+    Node { id: Id, losing: T, winning: T },
+    /// Conflicting weights of an edge.
+    Edge {
+        from: Id,
+        to: Id,
+        losing: W,
+        winning: W,
+    },
+}
+
+/// A problem found by `GraphDiff::validate_against`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationIssue<Id> {
+    /// A new or updated edge has an endpoint that is neither in the graph nor added by the
+    /// diff.
+    DanglingEdge { from: Id, to: Id },
+    /// A deleted edge has an endpoint that is not in the graph, so the edge cannot exist.
+    MissingEdge { from: Id, to: Id },
+    /// A deleted node is not in the graph.
+    MissingNode(Id),
+}
+
+/// The changes `GraphDiff::apply` would make to a graph, see `GraphDiff::plan_apply`.
+///
+/// The ids are listed in no particular order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApplyPlan<Id> {
+    /// Nodes added to the graph.
+    pub node_inserts: Vec<Id>,
+    /// Nodes of the graph whose properties are updated.
+    pub node_updates: Vec<Id>,
+    /// Nodes removed from the graph.
+    pub node_deletes: Vec<Id>,
+    /// Edges added to the graph.
+    pub edge_inserts: Vec<(Id, Id)>,
+    /// Edges of the graph whose weight changes.
+    pub edge_updates: Vec<(Id, Id)>,
+    /// Edges removed from the graph, directly or with one of their endpoints.
+    pub edge_deletes: Vec<(Id, Id)>,
+}
+
+impl<Id> Default for ApplyPlan<Id> {
+    fn default() -> Self {
+        ApplyPlan {
+            node_inserts: Vec::new(),
+            node_updates: Vec::new(),
+            node_deletes: Vec::new(),
+            edge_inserts: Vec::new(),
+            edge_updates: Vec::new(),
+            edge_deletes: Vec::new(),
+        }
+    }
+}
+
+/// A redundant or contradictory entry found by `GraphDiff::lint`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LintWarning<Id> {
+    /// A node is both new or updated and deleted.
+    NodeUpdatedAndDeleted(Id),
+    /// A new or updated edge has an endpoint marked as deleted.
+    EdgeToDeletedNode { from: Id, to: Id },
+    /// An edge is both new or updated and deleted.
+    EdgeAddedAndDeleted { from: Id, to: Id },
+    /// A node has an empty map of new or updated or of deleted edges.
+    EmptyEdgeMap(Id),
+    /// A node has an empty update and no new or updated edge references it.
+    UnreferencedEmptyNode(Id),
+}
+
+/// How `GraphDiff::apply_with` resolves a `Conflict`.
+///
+/// A merge of the wrong kind, e.g. `MergeEdge` for a node conflict, takes theirs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Resolution<T, W> {
+    /// Keep the value of the graph.
+    KeepMine,
+    /// Take the value of the diff.
+    TakeTheirs,
+    /// Apply the diff's update, then this update on top of it.
+    MergeNode(T),
+    /// Set the edge to this weight.
+    MergeEdge(W),
+}
+
+/// A diff between the nodes of a graph.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeDiff<Id: Hash + Eq, T> {
+    new_or_updated: HashMap<Id, T>,
+    deleted: HashSet<Id>,
+}
+
+impl<Id: Hash + Eq, T> NodeDiff<Id, T> {
+    pub fn new(new_or_updated: HashMap<Id, T>, deleted: HashSet<Id>) -> NodeDiff<Id, T> {
+        NodeDiff {
+            new_or_updated,
+            deleted,
+        }
+    }
+    pub fn get_new_or_updated(&self) -> &HashMap<Id, T> {
+        &self.new_or_updated
+    }
+    pub fn get_deleted(&self) -> &HashSet<Id> {
+        &self.deleted
+    }
+}
+
+/// Merge the node updates with `AddAssign`, un-deleting updated nodes. A node that
+/// `other` both updates and deletes ends up deleted.
+impl<Id: Hash + Eq, T: AddAssign> AddAssign for NodeDiff<Id, T> {
+    fn add_assign(&mut self, other: Self) {
+        for (node_id, update) in other.new_or_updated {
+            self.deleted.remove(&node_id);
+            match self.new_or_updated.get_mut(&node_id) {
+                Some(node) => *node += update,
+                None => {
+                    self.new_or_updated.insert(node_id, update);
+                }
+            }
+        }
+        for node_id in other.deleted {
+            self.new_or_updated.remove(&node_id);
+            self.deleted.insert(node_id);
+        }
+    }
+}
+
+/// A diff between the edges of a graph.
+///
+/// Equality compares the new or updated and deleted edges only, like serialization.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EdgeDiff<Id: Hash + Eq, W = f32> {
+    new_or_updated: HashMap<Id, HashMap<Id, W>>,
+    deleted: HashMap<Id, HashSet<Id>>,
+    /// Previous weights of deleted edges, if recorded. Not serialized.
+    #[serde(skip, default = "Option::default")]
+    deleted_weights: Option<HashMap<Id, HashMap<Id, W>>>,
+    /// Whether `add_edge` rejects self-loops. Not serialized.
+    #[serde(skip)]
+    reject_self_loops: bool,
+}
+
+impl<Id: Hash + Eq, W> EdgeDiff<Id, W> {
+    pub fn new(
+        new_or_updated: HashMap<Id, HashMap<Id, W>>,
+        deleted: HashMap<Id, HashSet<Id>>,
+    ) -> EdgeDiff<Id, W> {
+        EdgeDiff {
+            new_or_updated,
+            deleted,
+            deleted_weights: None,
+            reject_self_loops: false,
+        }
+    }
+    pub fn get_new_or_updated(&self) -> &HashMap<Id, HashMap<Id, W>> {
+        &self.new_or_updated
+    }
+    pub fn get_deleted(&self) -> &HashMap<Id, HashSet<Id>> {
+        &self.deleted
+    }
+
+    fn record_deleted_weight(&mut self, from: Id, to: Id, weight: W) {
+        if let Some(deleted_weights) = self.deleted_weights.as_mut() {
+            deleted_weights.entry(from).or_default().insert(to, weight);
+        }
+    }
+}
+
+impl<Id: Hash + Eq, W: PartialEq> PartialEq for EdgeDiff<Id, W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.new_or_updated == other.new_or_updated && self.deleted == other.deleted
+    }
+}
+
+/// Overwrite the weights with those of `other` and apply its deletions, as with
+/// `GraphDiff::add_edge` and `GraphDiff::delete_edge`. Endpoints are not checked since an
+/// `EdgeDiff` does not know which nodes are deleted.
+impl<Id: Hash + Eq + Copy, W> AddAssign for EdgeDiff<Id, W> {
+    fn add_assign(&mut self, other: Self) {
+        for (from, to_weight) in other.new_or_updated {
+            if let Some(deleted) = self.deleted.get_mut(&from) {
+                for to in to_weight.keys() {
+                    deleted.remove(to);
+                }
+                if deleted.is_empty() {
+                    self.deleted.remove(&from);
+                }
+            }
+            if let Some(deleted_weights) = self.deleted_weights.as_mut() {
+                if let Some(inner) = deleted_weights.get_mut(&from) {
+                    for to in to_weight.keys() {
+                        inner.remove(to);
+                    }
+                    if inner.is_empty() {
+                        deleted_weights.remove(&from);
+                    }
+                }
+            }
+            self.new_or_updated
+                .entry(from)
+                .or_default()
+                .extend(to_weight);
+        }
+        for (from, to) in other.deleted {
+            for to in to {
+                let (weight, empty_inner_map) = match self.new_or_updated.get_mut(&from) {
+                    None => (None, false),
+                    Some(to_weight) => (to_weight.remove(&to), to_weight.is_empty()),
+                };
+                if empty_inner_map {
+                    self.new_or_updated.remove(&from);
+                }
+                if let Some(weight) = weight {
+                    self.record_deleted_weight(from, to, weight);
+                }
+                self.deleted.entry(from).or_default().insert(to);
+            }
+        }
+    }
+}
+
+// the tests use `NodeUpdate`, which needs `std`
+#[cfg(all(test, feature = "std"))]
+mod tests {
+
+    use super::*;
+    use crate::node_update::{NodeField, NodeUpdate};
+
+    #[test]
+    fn test_node() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+
+        let id = 1;
+        let mut node = NodeUpdate {
+            label: Some("test".to_string()),
+            ..NodeUpdate::default()
+        };
+
+        diff.add_node(&id);
+        diff.add_or_update_node(&id, node.clone());
+        assert_eq!(diff.nodes.new_or_updated.get(&id).unwrap(), &node);
+
+        node.size = Some(10.0);
+        diff.add_or_update_node(&id, node.clone());
+        assert_eq!(diff.nodes.new_or_updated.get(&id).unwrap(), &node);
+
+        let node2 = NodeUpdate {
+            green: Some(5),
+            ..NodeUpdate::default()
+        };
+        diff.add_or_update_node(&id, node2.clone());
+
+        let combined = NodeUpdate {
+            label: Some("test".to_string()),
+            size: Some(10.0),
+            green: Some(5),
+            ..NodeUpdate::default()
+        };
+        assert_eq!(diff.nodes.new_or_updated.get(&id).unwrap(), &combined);
+
+        diff.delete_node(id);
+        assert!(diff.nodes.new_or_updated.is_empty());
+    }
+
+    #[test]
+    fn test_node_clear() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(
+            &1,
+            NodeUpdate {
+                url: Some("https://drisk.ai".to_string()),
+                size: Some(1.0),
+                ..NodeUpdate::default()
+            },
+        );
+
+        // a clear overrides a prior set
+        let mut clear = NodeUpdate::default();
+        clear.clear(NodeField::Url);
+        diff.add_or_update_node(&1, clear.clone());
+        let node = diff.nodes.new_or_updated.get(&1).unwrap();
+        assert_eq!(node.url, None);
+        assert!(node.is_cleared(NodeField::Url));
+        assert_eq!(node.size, Some(1.0));
+
+        // a set overrides a prior clear
+        diff.add_or_update_node(
+            &1,
+            NodeUpdate {
+                url: Some("https://demo.drisk.ai".to_string()),
+                ..NodeUpdate::default()
+            },
+        );
+        let node = diff.nodes.new_or_updated.get(&1).unwrap();
+        assert_eq!(node.url.as_deref(), Some("https://demo.drisk.ai"));
+        assert!(!node.is_cleared(NodeField::Url));
+
+        // a clear survives composition of diffs
+        let mut diff2 = GraphDiff::<usize, NodeUpdate>::new();
+        diff2.add_or_update_node(&1, clear);
+        diff += diff2;
+        assert!(diff.nodes.new_or_updated[&1].is_cleared(NodeField::Url));
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::with_capacity(100, 10);
+        assert!(diff.is_empty());
+        assert!(diff.nodes.new_or_updated.capacity() >= 100);
+        assert!(diff.nodes.deleted.capacity() >= 100);
+        assert!(diff.edges.new_or_updated.capacity() >= 10);
+
+        let capacity = diff.nodes.new_or_updated.capacity();
+        for i in 0..100 {
+            diff.add_node(&i);
+        }
+        assert_eq!(diff.nodes.new_or_updated.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_edge() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+
+        let from = 1;
+        let to = 2;
+        let weight = 1.0;
+
+        diff.add_edge(&from, &to, weight).unwrap();
+        assert_eq!(
+            diff.edges
+                .new_or_updated
+                .get(&from)
+                .unwrap()
+                .get(&to)
+                .unwrap(),
+            &weight
+        );
+
+        let weight2 = 2.0;
+        diff.add_edge(&from, &to, weight2).unwrap();
+        assert_eq!(
+            diff.edges
+                .new_or_updated
+                .get(&from)
+                .unwrap()
+                .get(&to)
+                .unwrap(),
+            &weight2
+        );
+
+        diff.delete_node(from);
+        assert!(diff.edges.new_or_updated.is_empty());
+    }
+
+    #[test]
+    fn test_edge_endpoint_deleted() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.delete_node(2);
+        assert!(matches!(
+            diff.add_edge(&1, &2, 1.0),
+            Err(GraphDiffError::EndpointDeleted {
+                from: false,
+                to: true
+            })
+        ));
+        assert!(matches!(
+            diff.add_edge(&2, &2, 1.0),
+            Err(GraphDiffError::EndpointDeleted {
+                from: true,
+                to: true
+            })
+        ));
+        assert!(diff.edges.new_or_updated.is_empty());
+    }
+
+    #[test]
+    fn test_non_finite_weights() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        assert!(matches!(
+            diff.try_add_edge(&0, &1, f32::NAN),
+            Err(GraphDiffError::NonFiniteWeight)
+        ));
+        assert!(diff.try_add_edge(&0, &1, f32::INFINITY).is_err());
+        assert!(diff.is_empty());
+        diff.try_add_edge(&0, &1, 1.0).unwrap();
+
+        diff.add_edge(&0, &2, f32::NAN).unwrap();
+        diff.add_edge(&1, &2, f32::NEG_INFINITY).unwrap();
+        assert_eq!(diff.sanitize_weights(0.0), 2);
+        assert_eq!(diff.edge_weight(&0, &1), Some(&1.0));
+        assert_eq!(diff.edge_weight(&0, &2), Some(&0.0));
+        assert_eq!(diff.edge_weight(&1, &2), Some(&0.0));
+        assert_eq!(diff.sanitize_weights(0.0), 0);
+    }
+
+    #[test]
+    fn test_add_edge_with_nodes() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(
+            &0,
+            NodeUpdate {
+                size: Some(1.0),
+                ..NodeUpdate::default()
+            },
+        );
+        diff.add_edge_with_nodes(&0, &1, 1.0).unwrap();
+        assert_eq!(diff.edge_weight(&0, &1), Some(&1.0));
+        assert_eq!(diff.new_or_updated_nodes()[&0].size, Some(1.0));
+        assert_eq!(diff.new_or_updated_nodes()[&1], NodeUpdate::default());
+
+        diff.delete_node(2);
+        assert!(diff.add_edge_with_nodes(&3, &2, 1.0).is_err());
+        assert!(!diff.contains_new_or_updated_node(&3));
+        assert!(diff.is_node_deleted(&2));
+    }
+
+    #[test]
+    fn test_undirected_edge() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_undirected_edge(&0, &1, 2.0).unwrap();
+        assert_eq!(diff.edge_weight(&0, &1), Some(&2.0));
+        assert_eq!(diff.edge_weight(&1, &0), Some(&2.0));
+
+        diff.delete_undirected_edge(&1, &0);
+        assert!(diff.edges.new_or_updated.is_empty());
+        assert!(diff.is_edge_deleted(&0, &1));
+        assert!(diff.is_edge_deleted(&1, &0));
+
+        diff.delete_node(1);
+        assert!(diff.add_undirected_edge(&0, &1, 1.0).is_err());
+        assert!(diff.edges.new_or_updated.is_empty());
+    }
+
+    #[test]
+    fn test_add_assign_nodes() {
+        let mut diff1 = GraphDiff::<usize, NodeUpdate>::new();
+        let node = NodeUpdate {
+            label: Some("test".to_string()),
+            ..NodeUpdate::default()
+        };
+        let node_other = NodeUpdate {
+            size: Some(10.0),
+            ..NodeUpdate::default()
+        };
+        diff1.add_node(&1);
+        diff1.add_or_update_node(&1, node.clone());
+        diff1.add_node(&2);
+        diff1.delete_node(3);
+
+        let mut diff2 = GraphDiff::<usize, NodeUpdate>::new();
+        diff2.add_node(&1);
+        diff2.add_or_update_node(&1, node_other.clone());
+        diff2.delete_node(2);
+
+        diff1 += diff2;
+
+        let d1 = diff1.nodes.new_or_updated.get(&1).unwrap();
+        assert_eq!(d1.label.as_ref().unwrap(), "test");
+        assert_eq!(d1.size.unwrap(), 10.0);
+        assert!(!diff1.nodes.new_or_updated.contains_key(&2));
+        assert!(diff1.nodes.deleted.contains(&2));
+        assert!(diff1.nodes.deleted.contains(&3));
+    }
+
+    #[test]
+    fn test_add_assign_edges() {
+        let mut diff1 = GraphDiff::<usize, NodeUpdate>::new();
+        diff1.add_edge(&1, &2, 1.0).unwrap();
+        diff1.add_edge(&1, &3, 2.0).unwrap();
+        diff1.add_edge(&1, &4, 2.0).unwrap();
+        diff1.add_edge(&2, &3, 3.0).unwrap();
+        diff1.add_edge(&3, &1, 4.0).unwrap();
+
+        let mut diff2 = GraphDiff::<usize, NodeUpdate>::new();
+        diff2.add_edge(&1, &2, 5.0).unwrap();
+        diff2.add_edge(&2, &3, 6.0).unwrap();
+        diff2.add_edge(&3, &1, 7.0).unwrap();
+        diff2.delete_edge(&1, &3);
+
+        diff1 += diff2;
+
+        assert_eq!(
+            diff1.edges.new_or_updated.get(&1).unwrap().get(&2).unwrap(),
+            &5.0
+        );
+        assert_eq!(
+            diff1.edges.new_or_updated.get(&2).unwrap().get(&3).unwrap(),
+            &6.0
+        );
+        assert_eq!(
+            diff1.edges.new_or_updated.get(&3).unwrap().get(&1).unwrap(),
+            &7.0
+        );
+        assert_eq!(
+            diff1.edges.new_or_updated.get(&1).unwrap().get(&4).unwrap(),
+            &2.0
+        );
+        assert!(diff1.edges.deleted.get(&1).unwrap().contains(&3));
+    }
+
+    #[test]
+    fn test_add_assign_node_diff() {
+        let label = NodeUpdate {
+            label: Some("test".to_string()),
+            ..NodeUpdate::default()
+        };
+        let size = NodeUpdate {
+            size: Some(10.0),
+            ..NodeUpdate::default()
+        };
+        let mut nodes = NodeDiff::new(
+            HashMap::from([(1, label.clone()), (2, NodeUpdate::default())]),
+            HashSet::from([3]),
+        );
+        nodes += NodeDiff::new(
+            HashMap::from([(1, size), (3, label.clone()), (4, label)]),
+            HashSet::from([2, 4]),
+        );
+
+        let expected = NodeUpdate {
+            label: Some("test".to_string()),
+            size: Some(10.0),
+            ..NodeUpdate::default()
+        };
+        assert_eq!(nodes.get_new_or_updated().get(&1), Some(&expected));
+        assert!(nodes.get_new_or_updated().contains_key(&3));
+        assert!(!nodes.get_new_or_updated().contains_key(&4));
+        assert_eq!(nodes.get_deleted(), &HashSet::from([2, 4]));
+    }
+
+    #[test]
+    fn test_add_assign_edge_diff() {
+        let mut edges = EdgeDiff::new(
+            HashMap::from([(1, HashMap::from([(2, 1.0), (3, 2.0)]))]),
+            HashMap::from([(2, HashSet::from([3]))]),
+        );
+        edges += EdgeDiff::new(
+            HashMap::from([
+                (1, HashMap::from([(2, 5.0)])),
+                (2, HashMap::from([(3, 6.0)])),
+            ]),
+            HashMap::from([(1, HashSet::from([3]))]),
+        );
+
+        let expected = EdgeDiff::new(
+            HashMap::from([
+                (1, HashMap::from([(2, 5.0)])),
+                (2, HashMap::from([(3, 6.0)])),
+            ]),
+            HashMap::from([(1, HashSet::from([3]))]),
+        );
+        assert_eq!(edges, expected);
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Link {
+        capacity: u32,
+        latency: f32,
+    }
+
+    #[test]
+    fn test_add_assign_custom_weight() {
+        let fast = Link {
+            capacity: 10,
+            latency: 0.5,
+        };
+        let slow = Link {
+            capacity: 1,
+            latency: 20.0,
+        };
+
+        let mut diff1 = GraphDiff::<usize, NodeUpdate, Link>::new();
+        diff1.add_edge(&1, &2, slow).unwrap();
+        diff1.add_edge(&1, &3, slow).unwrap();
+        diff1.add_edge(&2, &3, slow).unwrap();
+
+        let mut diff2 = GraphDiff::<usize, NodeUpdate, Link>::new();
+        diff2.add_edge(&1, &2, fast).unwrap();
+        diff2.delete_edge(&1, &3);
+        diff2.delete_node(3);
+
+        diff1 += diff2;
+
+        assert_eq!(
+            diff1.edges.new_or_updated.get(&1).unwrap().get(&2),
+            Some(&fast)
+        );
+        assert!(diff1.edges.new_or_updated.get(&2).unwrap().is_empty());
+        assert!(diff1.nodes.deleted.contains(&3));
+    }
+
+    #[test]
+    fn test_add_assign_custom_weight_edge_diff() {
+        let link = Link {
+            capacity: 5,
+            latency: 1.0,
+        };
+        let mut diff = GraphDiff::<usize, NodeUpdate, Link>::new();
+        diff.add_edge(&1, &2, link).unwrap();
+
+        let mut new_or_updated = HashMap::new();
+        new_or_updated.insert(2, HashMap::from([(3, link)]));
+        let deleted = HashMap::from([(1, HashSet::from([2]))]);
+        diff += EdgeDiff::new(new_or_updated, deleted);
+
+        assert!(!diff.edges.new_or_updated.contains_key(&1));
+        assert_eq!(
+            diff.edges.new_or_updated.get(&2).unwrap().get(&3),
+            Some(&link)
+        );
+        assert!(diff.edges.deleted.get(&1).unwrap().contains(&2));
+    }
+
+    #[test]
+    fn test_merge_many() {
+        let diffs = (0..5usize)
+            .map(|i| {
+                let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+                diff.add_or_update_node(
+                    &i,
+                    NodeUpdate {
+                        size: Some(i as f32),
+                        ..NodeUpdate::default()
+                    },
+                );
+                diff.add_or_update_node(
+                    &0,
+                    NodeUpdate {
+                        red: Some(i as u8),
+                        ..NodeUpdate::default()
+                    },
+                );
+                diff.add_edge(&i, &(i + 1), i as f32).unwrap();
+                if i % 2 == 1 {
+                    diff.delete_node(i - 1);
+                }
+                if i == 4 {
+                    diff.delete_edge(&1, &2);
+                }
+                diff
+            })
+            .collect::<Vec<_>>();
+
+        let mut folded = GraphDiff::new();
+        for diff in diffs.clone() {
+            folded += diff;
+        }
+        assert_eq!(GraphDiff::merge_many(diffs), folded);
+    }
+
+    #[test]
+    fn test_add_assign_checked_edges() {
+        let mut diff1 = GraphDiff::<usize, NodeUpdate>::new();
+        diff1.add_edge(&1, &2, 1.0).unwrap();
+        diff1.add_edge(&1, &3, 2.0).unwrap();
+
+        let mut diff2 = GraphDiff::<usize, NodeUpdate>::new();
+        diff2.add_edge(&1, &2, 5.0).unwrap();
+        diff2.add_edge(&1, &3, 2.0).unwrap();
+        diff2.add_edge(&2, &3, 1.0).unwrap();
+
+        let conflicts = diff1.add_assign_checked(diff2);
+        assert_eq!(
+            conflicts,
+            vec![Conflict::Edge {
+                from: 1,
+                to: 2,
+                losing: 1.0,
+                winning: 5.0
+            }]
+        );
+        assert_eq!(diff1.edge_weight(&1, &2), Some(&5.0));
+    }
+
+    #[test]
+    fn test_add_assign_checked_nodes() {
+        let mut diff1 = GraphDiff::<usize, NodeUpdate>::new();
+        diff1.add_or_update_node(
+            &1,
+            NodeUpdate {
+                label: Some("a".to_string()),
+                size: Some(1.0),
+                url: Some("https://drisk.ai".to_string()),
+                ..NodeUpdate::default()
+            },
+        );
+        diff1.add_or_update_node(
+            &2,
+            NodeUpdate {
+                red: Some(1),
+                ..NodeUpdate::default()
+            },
+        );
+
+        let mut diff2 = GraphDiff::<usize, NodeUpdate>::new();
+        let mut update = NodeUpdate {
+            label: Some("b".to_string()),
+            size: Some(1.0),
+            ..NodeUpdate::default()
+        };
+        update.clear(NodeField::Url);
+        diff2.add_or_update_node(&1, update);
+        diff2.add_or_update_node(
+            &2,
+            NodeUpdate {
+                green: Some(1),
+                ..NodeUpdate::default()
+            },
+        );
+
+        let conflicts = diff1.add_assign_checked(diff2);
+        assert_eq!(conflicts.len(), 1);
+        let Conflict::Node {
+            id,
+            losing,
+            winning,
+        } = &conflicts[0]
+        else {
+            panic!("expected a node conflict");
+        };
+        assert_eq!(*id, 1);
+        assert_eq!(
+            losing,
+            &NodeUpdate {
+                label: Some("a".to_string()),
+                url: Some("https://drisk.ai".to_string()),
+                ..NodeUpdate::default()
+            }
+        );
+        assert_eq!(winning.label.as_deref(), Some("b"));
+        assert!(winning.is_cleared(NodeField::Url));
+        assert_eq!(winning.size, None);
+        assert_eq!(diff1.nodes.new_or_updated[&1].label.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_add_assign_with() {
+        let mut diff1 = GraphDiff::<usize, NodeUpdate>::new();
+        diff1.add_edge(&0, &1, 1.0).unwrap();
+        diff1.add_edge(&1, &2, 4.0).unwrap();
+        let mut diff2 = GraphDiff::<usize, NodeUpdate>::new();
+        diff2.add_edge(&0, &1, 2.0).unwrap();
+        diff2.add_edge(&1, &2, 3.0).unwrap();
+        diff2.add_edge(&2, &0, 5.0).unwrap();
+
+        let mut sum = diff1.clone();
+        sum.add_assign_with(diff2.clone(), |a, b| a + b).unwrap();
+        assert_eq!(sum.edge_weight(&0, &1), Some(&3.0));
+        assert_eq!(sum.edge_weight(&1, &2), Some(&7.0));
+        assert_eq!(sum.edge_weight(&2, &0), Some(&5.0));
+
+        let mut max = diff1.clone();
+        max.add_assign_with(diff2.clone(), f32::max).unwrap();
+        assert_eq!(max.edge_weight(&0, &1), Some(&2.0));
+        assert_eq!(max.edge_weight(&1, &2), Some(&4.0));
+        assert_eq!(max.edge_weight(&2, &0), Some(&5.0));
+
+        // overwriting matches `+=`, and deletions still win
+        diff2.delete_node(2);
+        let mut overwrite = diff1.clone();
+        overwrite.add_assign_with(diff2.clone(), |_, b| b).unwrap();
+        diff1 += diff2;
+        assert_eq!(overwrite, diff1);
+        assert_eq!(overwrite.edge_weight(&1, &2), None);
+
+        // rejected edges are skipped and returned
+        let mut diff3 = GraphDiff::<usize, NodeUpdate>::new();
+        diff3.add_edge(&0, &0, 1.0).unwrap();
+        diff3.add_edge(&0, &1, 1.0).unwrap();
+        overwrite.reject_self_loops(true);
+        let rejected = overwrite.add_assign_with(diff3, |a, b| a + b);
+        assert_eq!(rejected, Err(vec![(0, 0, 1.0)]));
+        assert_eq!(overwrite.edge_weight(&0, &0), None);
+        assert_eq!(overwrite.edge_weight(&0, &1), Some(&3.0));
+    }
+
+    #[test]
+    fn test_merge_ref() {
+        let mut diff1 = GraphDiff::<usize, NodeUpdate>::new();
+        diff1.add_node(&0);
+        diff1.add_node(&1);
+        diff1.add_edge(&0, &1, 1.0).unwrap();
+        diff1.add_edge(&1, &2, 1.0).unwrap();
+        let mut diff2 = GraphDiff::<usize, NodeUpdate>::new();
+        diff2.get_or_create_mut_node_update(&0).label = Some("a".to_string());
+        diff2.add_edge(&0, &1, 2.0).unwrap();
+        diff2.delete_edge(&2, &0);
+        diff2.delete_node(1);
+        let source = diff2.clone();
+
+        let mut merged = diff1.clone();
+        merged.merge_ref(&diff2).unwrap();
+        assert_eq!(diff2, source);
+        diff1 += diff2;
+        assert_eq!(merged, diff1);
+
+        // edges to nodes deleted in `merged` are skipped and returned
+        let mut diff3 = GraphDiff::<usize, NodeUpdate>::new();
+        diff3.add_edge(&0, &1, 3.0).unwrap();
+        diff3.add_edge(&0, &2, 3.0).unwrap();
+        assert_eq!(merged.merge_ref(&diff3), Err(vec![(0, 1, 3.0)]));
+        assert_eq!(merged.edge_weight(&0, &2), Some(&3.0));
+    }
+
+    #[test]
+    fn test_self_loops() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_edge(&0, &0, 1.0).unwrap();
+        diff.add_edge(&0, &1, 2.0).unwrap();
+        diff.add_edge(&2, &2, 3.0).unwrap();
+        let mut loops = diff.self_loops();
+        loops.sort_by_key(|(id, _)| *id);
+        assert_eq!(loops, vec![(0, 1.0), (2, 3.0)]);
+
+        diff.reject_self_loops(true);
+        assert!(matches!(
+            diff.add_edge(&1, &1, 1.0),
+            Err(GraphDiffError::SelfLoop)
+        ));
+        assert!(diff.add_edge_reviving(&1, &1, 1.0).is_err());
+        assert!(diff.add_edge(&1, &0, 1.0).is_ok());
+        assert_eq!(diff.self_loops().len(), 2);
+
+        diff.reject_self_loops(false);
+        diff.add_edge(&1, &1, 1.0).unwrap();
+        assert_eq!(diff.self_loops().len(), 3);
+    }
+
+    #[test]
+    fn test_add_edge_reviving() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.delete_node(0);
+        diff.delete_node(2);
+        assert!(diff.add_edge(&0, &1, 2.0).is_err());
+
+        diff.add_edge_reviving(&0, &1, 2.0).unwrap();
+        assert!(!diff.is_node_deleted(&0));
+        assert!(diff.new_or_updated_nodes().contains_key(&0));
+        assert!(!diff.new_or_updated_nodes().contains_key(&1));
+        assert_eq!(diff.edge_weight(&0, &1), Some(&2.0));
+        assert!(diff.is_node_deleted(&2));
+        assert!(diff.is_internally_consistent());
+    }
+
+    #[test]
+    fn test_touched_nodes() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        assert!(diff.touched_nodes().is_empty());
+        diff.add_node(&0);
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.add_edge(&2, &0, 1.0).unwrap();
+        diff.delete_edge(&3, &4);
+        diff.delete_node(5);
+        assert_eq!(diff.touched_nodes(), HashSet::from([0, 1, 2, 3, 4, 5]));
+        assert_eq!(
+            diff.touched_nodes().len(),
+            diff.stats().distinct_endpoints + 1
+        );
+    }
+
+    #[test]
+    fn test_delete_node_cascade() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for id in 0..4 {
+            diff.add_node(&id);
+        }
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.add_edge(&1, &2, 1.0).unwrap();
+        diff.add_edge(&3, &2, 1.0).unwrap();
+
+        let mut kept = diff.clone();
+        assert!(kept.delete_node_cascade(1, false).is_empty());
+        assert_eq!(kept.iter_deleted_nodes().count(), 1);
+
+        assert_eq!(diff.delete_node_cascade(1, true), HashSet::from([0]));
+        assert_eq!(
+            diff.iter_deleted_nodes().copied().collect::<HashSet<_>>(),
+            HashSet::from([0, 1])
+        );
+        assert_eq!(diff.edge_weight(&3, &2), Some(&1.0));
+        assert!(diff.is_internally_consistent());
+    }
+
+    #[test]
+    fn test_merge_node_into() {
+        let (a, b, c) = (0, 1, 2);
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(
+            &a,
+            NodeUpdate {
+                label: Some("a".to_string()),
+                ..NodeUpdate::default()
+            },
+        );
+        diff.add_or_update_node(
+            &b,
+            NodeUpdate {
+                size: Some(2.0),
+                ..NodeUpdate::default()
+            },
+        );
+        diff.add_node(&c);
+        diff.add_edge(&a, &b, 1.0).unwrap();
+        diff.add_edge(&b, &c, 2.0).unwrap();
+        diff.delete_edge(&c, &b);
+        diff.delete_edge(&b, &a);
+
+        // nothing moves to a deleted node
+        let mut deleted_dst = diff.clone();
+        deleted_dst.delete_node(c);
+        let unchanged = deleted_dst.clone();
+        assert!(matches!(
+            deleted_dst.merge_node_into(b, c),
+            Err(GraphDiffError::EndpointDeleted { to: true, .. })
+        ));
+        assert_eq!(deleted_dst, unchanged);
+
+        diff.merge_node_into(b, a).unwrap();
+
+        let expected = NodeUpdate {
+            label: Some("a".to_string()),
+            size: Some(2.0),
+            ..NodeUpdate::default()
+        };
+        assert_eq!(diff.new_or_updated_nodes().get(&a), Some(&expected));
+        assert!(diff.deleted_nodes().contains(&b));
+        assert_eq!(
+            diff.iter_new_or_updated_edges().collect::<Vec<_>>(),
+            vec![(&a, &c, &2.0)]
+        );
+        // the deletions of edges of `b` are not moved to `a`
+        assert!(!diff
+            .deleted_edges()
+            .get(&c)
+            .is_some_and(|to| to.contains(&a)));
+        assert!(diff.deleted_edges()[&c].contains(&b));
+        assert!(diff.deleted_edges()[&b].contains(&a));
+        assert!(diff.self_loops().is_empty());
+        assert!(diff.is_internally_consistent());
+    }
+
+    #[test]
+    fn test_merge_node_into_leaves_diff_unchanged_on_error() {
+        let (a, b, c, d) = (0, 1, 2, 3);
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(
+            &b,
+            NodeUpdate {
+                size: Some(2.0),
+                ..NodeUpdate::default()
+            },
+        );
+        diff.add_edge(&b, &c, 1.0).unwrap();
+        diff.add_edge(&c, &b, 1.0).unwrap();
+        diff.add_edge(&d, &b, 1.0).unwrap();
+        diff.add_edge(&b, &d, 1.0).unwrap();
+        // an inconsistent diff, with edges of `b` to and from a deleted node
+        diff.delete_node_unchecked(d);
+
+        let unchanged = diff.clone();
+        assert!(matches!(
+            diff.merge_node_into(b, a),
+            Err(GraphDiffError::EndpointDeleted { to: true, .. })
+        ));
+        assert_eq!(diff, unchanged);
+
+        diff.edges.new_or_updated.get_mut(&b).unwrap().remove(&d);
+        let unchanged = diff.clone();
+        assert!(matches!(
+            diff.merge_node_into(b, a),
+            Err(GraphDiffError::EndpointDeleted { from: true, .. })
+        ));
+        assert_eq!(diff, unchanged);
+    }
+
+    #[test]
+    fn test_increment_edge() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for _ in 0..3 {
+            diff.increment_edge(&0, &1, 1.5).unwrap();
+        }
+        assert_eq!(diff.edge_weight(&0, &1), Some(&4.5));
+
+        diff.delete_node(2);
+        assert!(matches!(
+            diff.increment_edge(&0, &2, 1.0),
+            Err(GraphDiffError::EndpointDeleted {
+                from: false,
+                to: true
+            })
+        ));
+        assert_eq!(diff.edge_weight(&0, &2), None);
+    }
+
+    #[test]
+    fn test_lint() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&0);
+        diff.add_node(&1);
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        assert!(diff.lint().is_empty());
+
+        diff.add_node(&2);
+        assert_eq!(diff.lint(), vec![LintWarning::UnreferencedEmptyNode(2)]);
+
+        let nodes = NodeDiff::new(
+            HashMap::from([(0, NodeUpdate::default()), (1, NodeUpdate::default())]),
+            HashSet::from([1]),
+        );
+        let edges = EdgeDiff::new(
+            HashMap::from([(0, HashMap::from([(1, 1.0)]))]),
+            HashMap::from([(0, HashSet::from([1])), (2, HashSet::new())]),
+        );
+        let diff = GraphDiff::from_diffs(nodes, edges);
+        let warnings = diff.lint();
+        assert_eq!(warnings.len(), 4);
+        for warning in [
+            LintWarning::NodeUpdatedAndDeleted(1),
+            LintWarning::EdgeToDeletedNode { from: 0, to: 1 },
+            LintWarning::EdgeAddedAndDeleted { from: 0, to: 1 },
+            LintWarning::EmptyEdgeMap(2),
+        ] {
+            assert!(warnings.contains(&warning));
+        }
+
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        unsafe {
+            diff.set_edges_unchecked(HashMap::from([(0, HashMap::new())]));
+        }
+        assert_eq!(diff.lint(), vec![LintWarning::EmptyEdgeMap(0)]);
+    }
+
+    #[test]
+    fn test_intersection() {
+        let update = |label: &str, size: f32| NodeUpdate {
+            label: Some(label.to_string()),
+            size: Some(size),
+            ..NodeUpdate::default()
+        };
+        let mut a = GraphDiff::<usize, NodeUpdate>::new();
+        a.add_or_update_node(&0, update("a", 1.0));
+        a.add_or_update_node(&1, update("b", 2.0));
+        a.add_edge(&0, &1, 1.0).unwrap();
+        a.delete_node(2);
+        a.delete_edge(&3, &4);
+
+        // full agreement
+        let b = a.clone();
+        let c = a.clone();
+        assert_eq!(GraphDiff::intersection(&[&a, &b, &c]), a);
+
+        // partial agreement
+        let mut b = a.clone();
+        b.add_or_update_node(&0, update("a", 5.0));
+        b.add_edge(&0, &1, 2.0).unwrap();
+        b.delete_node(5);
+        let common = GraphDiff::intersection(&[&a, &b, &c]);
+        assert_eq!(
+            common.new_or_updated_nodes().get(&0),
+            Some(&NodeUpdate {
+                label: Some("a".to_string()),
+                ..NodeUpdate::default()
+            })
+        );
+        assert_eq!(
+            common.new_or_updated_nodes().get(&1),
+            Some(&update("b", 2.0))
+        );
+        assert_eq!(common.edge_weight(&0, &1), None);
+        assert_eq!(common.deleted_nodes(), &HashSet::from([2]));
+        assert!(common.is_edge_deleted(&3, &4));
+
+        // no agreement
+        let mut d = GraphDiff::<usize, NodeUpdate>::new();
+        d.add_or_update_node(&6, update("c", 3.0));
+        d.add_edge(&6, &7, 1.0).unwrap();
+        assert!(GraphDiff::intersection(&[&a, &d]).is_empty());
+        assert!(GraphDiff::<usize, NodeUpdate>::intersection(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for id in 0..4 {
+            diff.add_node(&id);
+        }
+        diff.add_or_update_node(
+            &3,
+            NodeUpdate {
+                label: Some("kept".to_string()),
+                ..NodeUpdate::default()
+            },
+        );
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        // leaves an empty map of new edges from 4
+        diff.add_edge(&4, &5, 1.0).unwrap();
+        diff.delete_node(5);
+
+        let report = diff.compact();
+        assert_eq!(
+            report,
+            CompactReport {
+                empty_nodes: 1,
+                empty_edge_maps: 1,
+            }
+        );
+        assert_eq!(
+            diff.new_or_updated_nodes()
+                .keys()
+                .copied()
+                .collect::<HashSet<_>>(),
+            HashSet::from([0, 1, 3])
+        );
+        assert!(diff.new_or_updated_edges().values().all(|e| !e.is_empty()));
+        assert_eq!(diff.compact(), CompactReport::default());
+    }
+
+    #[test]
+    fn test_node_update() {
+        let update = NodeUpdate {
+            label: Some("a".to_string()),
+            ..NodeUpdate::default()
+        };
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(&0, update.clone());
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.delete_node(2);
+
+        assert_eq!(diff.node_update(&0), Some(&update));
+        assert_eq!(diff.node_update(&1), None);
+        assert_eq!(diff.node_update(&2), None);
+
+        assert_eq!(diff.take_node_update(&0), Some(update));
+        assert_eq!(diff.take_node_update(&0), None);
+        assert_eq!(diff.take_node_update(&2), None);
+        assert!(diff.new_or_updated_nodes().is_empty());
+        assert_eq!(diff.edge_weight(&0, &1), Some(&1.0));
+    }
+
+    #[test]
+    fn test_split_at_limit() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for id in 0..5 {
+            diff.add_node(&id);
+        }
+        for id in 0..4 {
+            diff.add_edge(&id, &(id + 1), 1.0).unwrap();
+        }
+        diff.add_edge(&7, &4, 1.0).unwrap();
+        diff.add_edge(&8, &4, 1.0).unwrap();
+        diff.delete_node(4);
+        diff.delete_edge(&5, &6);
+        let ops = diff.node_change_count() + diff.edge_change_count();
+
+        for max_ops in 0..=ops + 1 {
+            let (first, rest) = diff.clone().split_at_limit(max_ops);
+            let first_ops = first.node_change_count() + first.edge_change_count();
+            // node 4 is deleted together with its three deleted edges
+            assert!(first_ops <= max_ops.max(4));
+            assert_eq!(
+                first_ops + rest.node_change_count() + rest.edge_change_count(),
+                ops
+            );
+            assert!(first.is_internally_consistent() && rest.is_internally_consistent());
+            for part in [&first, &rest] {
+                if part.is_node_deleted(&4) {
+                    assert_eq!(
+                        part.deleted_edge_count(),
+                        3 + part.is_edge_deleted(&5, &6) as usize
+                    );
+                }
+            }
+
+            // `delete_node` leaves empty edge maps behind which the split does not keep
+            let mut expected = diff.clone();
+            expected.compact();
+            let mut combined = first;
+            combined += rest;
+            assert_eq!(combined, expected);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        assert_eq!(
+            diff.to_string(),
+            "GraphDiff(nodes: 0 new or updated, 0 deleted; edges: 0 new or updated, 0 deleted)"
+        );
+
+        diff.add_node(&1);
+        diff.add_node(&3);
+        diff.add_edge(&1, &3, 1.0).unwrap();
+        diff.delete_node(2);
+        diff.delete_edge(&3, &4);
+        let summary =
+            "GraphDiff(nodes: 2 new or updated, 1 deleted; edges: 1 new or updated, 1 deleted)";
+        assert_eq!(diff.to_string(), summary);
+
+        let listing = format!("{:#}", diff);
+        let mut lines = listing.lines();
+        assert_eq!(lines.next(), Some(summary));
+        let mut changes: Vec<_> = lines.collect();
+        changes.sort();
+        assert_eq!(
+            changes,
+            [
+                "  + edge 1 -> 3",
+                "  + node 1",
+                "  + node 3",
+                "  - edge 3 -> 4",
+                "  - node 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_absorb_edge_shards() {
+        let shards: Vec<HashMap<usize, HashMap<usize, f32>>> = (0..4)
+            .map(|shard| {
+                (0..10)
+                    .filter(|from| from % 4 == shard)
+                    .map(|from| (from, HashMap::from([(from + 1, from as f32)])))
+                    .collect()
+            })
+            .collect();
+
+        let mut diff = GraphDiff::<usize, usize>::new();
+        let mut sequential = diff.clone();
+        for shard in shards.iter() {
+            sequential.add_edges(shard).unwrap();
+        }
+        diff.absorb_edge_shards(shards.clone()).unwrap();
+        assert_eq!(diff, sequential);
+        assert_eq!(diff.edge_change_count(), 10);
+
+        // edges touching a deleted node are rejected, the others are still added
+        let mut diff = GraphDiff::<usize, usize>::new();
+        diff.delete_node(7);
+        let mut rejected = diff.absorb_edge_shards(shards).unwrap_err();
+        rejected.sort_by_key(|(from, _, _)| *from);
+        assert_eq!(rejected, [(6, 7, 6.0), (7, 8, 7.0)]);
+        assert_eq!(diff.edge_change_count(), 8);
+    }
+
+    #[test]
+    fn test_weight_histogram() {
+        let mut diff = GraphDiff::<usize, usize>::new();
+        assert_eq!(diff.weight_histogram(3, None), [0, 0, 0]);
+
+        // weights 0, 1, ..., 9 and a NaN
+        for id in 0..10 {
+            diff.add_edge(&id, &(id + 1), id as f32).unwrap();
+        }
+        diff.add_edge(&10, &11, f32::NAN).unwrap();
+        assert_eq!(diff.weight_histogram(3, None), [3, 3, 4]);
+        assert_eq!(diff.weight_histogram(1, None), [10]);
+        assert_eq!(diff.weight_histogram(4, Some((2., 6.))), [1, 1, 1, 2]);
+        assert_eq!(diff.weight_histogram(2, Some((20., 30.))), [0, 0]);
+        assert_eq!(diff.weight_histogram(2, Some((4., 4.))), [1, 0]);
+        assert!(diff.weight_histogram(0, None).is_empty());
+    }
+
+    #[test]
+    fn test_update_each_node() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for id in 0..5 {
+            diff.add_or_update_node(
+                &id,
+                NodeUpdate {
+                    size: Some(id as f32),
+                    ..NodeUpdate::default()
+                },
+            );
+        }
+        diff.delete_node(5);
+
+        let mut visited = Vec::new();
+        diff.update_each_node(|id, update| {
+            visited.push(*id);
+            update.size = update.size.map(|size| size * 2.);
+        });
+        visited.sort();
+        assert_eq!(visited, [0, 1, 2, 3, 4]);
+        for id in 0..5 {
+            assert_eq!(diff.node_update(&id).unwrap().size, Some(2. * id as f32));
+        }
+        assert!(diff.is_node_deleted(&5));
+    }
+
+    #[test]
+    fn test_delete_nodes() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for from in 0..20 {
+            diff.add_node(&from);
+            for to in 0..20 {
+                diff.add_edge(&from, &to, (from * to) as f32).unwrap();
+            }
+        }
+        diff.record_deleted_weights();
+        let ids = HashSet::from([3, 7, 11, 25]);
+
+        let mut batch = diff.clone();
+        batch.delete_nodes(&ids);
+        for id in ids.iter() {
+            diff.delete_node(*id);
+        }
+        assert_eq!(batch.nodes, diff.nodes);
+        assert_eq!(batch.edges.deleted, diff.edges.deleted);
+        assert_eq!(batch.edges.new_or_updated, diff.edges.new_or_updated);
+        assert_eq!(batch.deleted_edge_weights(), diff.deleted_edge_weights());
+        // some edges between deleted nodes are marked as deleted
+        assert!(ids
+            .iter()
+            .any(|from| ids.iter().any(|to| batch.is_edge_deleted(from, to))));
+    }
+
+    /// Timing of `delete_nodes` against one `delete_node` per node, run with
+    /// `cargo test --release -- --ignored --nocapture bench_delete_nodes`.
+    #[test]
+    #[ignore]
+    fn bench_delete_nodes() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for id in 0..10_000 {
+            diff.add_node(&id);
+            for offset in 1..10 {
+                diff.add_edge(&id, &((id + offset * 997) % 10_000), 1.0)
+                    .unwrap();
+            }
+        }
+        let ids: HashSet<usize> = (0..10_000).step_by(10).collect();
+
+        let mut batch = diff.clone();
+        let start = std::time::Instant::now();
+        batch.delete_nodes(&ids);
+        let batch_time = start.elapsed();
+
+        let start = std::time::Instant::now();
+        for id in ids.iter() {
+            diff.delete_node(*id);
+        }
+        let sequential_time = start.elapsed();
+        assert_eq!(batch, diff);
+        println!(
+            "delete_nodes: {:?}, delete_node per node: {:?}",
+            batch_time, sequential_time
+        );
+    }
+
+    #[test]
+    fn test_clear_capacity() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for id in 0..100 {
+            diff.add_node(&id);
+            diff.add_edge(&id, &(id + 100), 1.0).unwrap();
+            diff.delete_node(id + 200);
+        }
+        diff.clear();
+        assert!(diff.is_empty());
+        assert!(diff.nodes.new_or_updated.capacity() >= 100);
+        assert!(diff.nodes.deleted.capacity() >= 100);
+        assert!(diff.edges.new_or_updated.capacity() >= 100);
+
+        diff.clear_and_shrink();
+        assert!(diff.is_empty());
+        assert_eq!(diff.nodes.new_or_updated.capacity(), 0);
+        assert_eq!(diff.nodes.deleted.capacity(), 0);
+        assert_eq!(diff.edges.new_or_updated.capacity(), 0);
+    }
+
+    #[test]
+    fn test_add_edges() {
+        let mut diff = GraphDiff::<usize, usize>::new();
+        for i in 0..50 {
+            diff.add_node(&i);
+        }
+
+        for i in 10..20 {
+            diff.delete_node(i);
+        }
+
+        let edges = (0..50usize)
+            .map(|i| {
+                let mut inner = HashMap::new();
+                for j in 0..i {
+                    inner.insert(j, 1f32);
+                }
+                (i, inner)
+            })
+            .collect::<HashMap<usize, HashMap<usize, f32>>>();
+
+        // check can't add if nodes are deleted
+        let mut diff2 = diff.clone();
+        for i in 10..20 {
+            diff2.delete_node(i);
+        }
+        assert!(diff2.add_edges(&edges).is_err());
+
+        for i in 30..40 {
+            diff.delete_node(i);
+        }
+
+        assert!(diff.is_internally_consistent());
+    }
+
+    #[test]
+    fn test_consistent_with_deleted_edges_to_deleted_nodes() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_edge(&0, &3, 1.0).unwrap();
+        diff.delete_node(3);
+        // `delete_node` leaves the deleted edge 0 -> 3
+        assert!(diff.deleted_edges()[&0].contains(&3));
+        assert!(diff.is_internally_consistent());
+    }
+
+    #[test]
+    fn test_apply() {
+        let label = |l: &str| NodeUpdate {
+            label: Some(l.to_string()),
+            ..NodeUpdate::default()
+        };
+        let mut nodes = HashMap::from([(0, label("a")), (1, label("b")), (2, label("c"))]);
+        let mut edges = HashMap::from([
+            (0, HashMap::from([(1, 1.0), (2, 2.0)])),
+            (1, HashMap::from([(2, 3.0)])),
+            (2, HashMap::from([(0, 4.0)])),
+        ]);
+
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(
+            &0,
+            NodeUpdate {
+                size: Some(2.0),
+                ..NodeUpdate::default()
+            },
+        );
+        diff.add_or_update_node(&3, label("d"));
+        diff.add_edge(&0, &1, 5.0).unwrap();
+        diff.add_edge(&3, &0, 6.0).unwrap();
+        diff.delete_edge(&0, &2);
+        diff.delete_node(1);
+        diff.apply(&mut nodes, &mut edges);
+
+        let expected_nodes = HashMap::from([
+            (
+                0,
+                NodeUpdate {
+                    label: Some("a".to_string()),
+                    size: Some(2.0),
+                    ..NodeUpdate::default()
+                },
+            ),
+            (2, label("c")),
+            (3, label("d")),
+        ]);
+        let expected_edges = HashMap::from([
+            (2, HashMap::from([(0, 4.0)])),
+            (3, HashMap::from([(0, 6.0)])),
+        ]);
+        assert_eq!(nodes, expected_nodes);
+        assert_eq!(edges, expected_edges);
+    }
+
+    #[test]
+    fn test_plan_apply() {
+        let label = |label: &str| NodeUpdate {
+            label: Some(label.to_string()),
+            ..NodeUpdate::default()
+        };
+        let nodes = HashMap::from([(0, label("a")), (1, label("b")), (2, label("c"))]);
+        let edges = HashMap::from([
+            (0, HashMap::from([(1, 1.0), (2, 1.0)])),
+            (2, HashMap::from([(1, 1.0)])),
+        ]);
+
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(&0, label("updated"));
+        diff.add_or_update_node(&3, label("new"));
+        diff.add_edge(&0, &1, 2.0).unwrap();
+        diff.add_edge(&0, &3, 1.0).unwrap();
+        diff.delete_node(2);
+        diff.delete_node(4);
+
+        let mut plan = diff.plan_apply(&nodes, &edges);
+        plan.edge_deletes.sort();
+        assert_eq!(
+            plan,
+            ApplyPlan {
+                node_inserts: vec![3],
+                node_updates: vec![0],
+                node_deletes: vec![2],
+                edge_inserts: vec![(0, 3)],
+                edge_updates: vec![(0, 1)],
+                edge_deletes: vec![(0, 2), (2, 1)],
+            }
+        );
+
+        let (mut applied_nodes, mut applied_edges) = (nodes.clone(), edges.clone());
+        diff.apply(&mut applied_nodes, &mut applied_edges);
+        // one node inserted, one deleted
+        assert_eq!(applied_nodes.len(), nodes.len());
+
+        let unchanged = diff.plan_apply(&applied_nodes, &applied_edges);
+        assert!(unchanged.node_inserts.is_empty());
+        assert!(unchanged.edge_inserts.is_empty() && unchanged.edge_updates.is_empty());
+        assert!(unchanged.node_deletes.is_empty() && unchanged.edge_deletes.is_empty());
+    }
+
+    #[test]
+    fn test_apply_with() {
+        let label = |l: &str| NodeUpdate {
+            label: Some(l.to_string()),
+            ..NodeUpdate::default()
+        };
+        let mut nodes = HashMap::from([(0, label("a")), (1, label("b"))]);
+        let mut edges = HashMap::from([(0, HashMap::from([(1, 3.0), (2, 1.0)]))]);
+
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(
+            &0,
+            NodeUpdate {
+                size: Some(2.0),
+                ..label("x")
+            },
+        );
+        diff.add_or_update_node(&1, label("y"));
+        diff.add_edge(&0, &1, 2.0).unwrap();
+        diff.add_edge(&0, &2, 4.0).unwrap();
+        diff.add_edge(&1, &0, 5.0).unwrap();
+
+        let mut conflicts = 0;
+        diff.apply_with(&mut nodes, &mut edges, |conflict| {
+            conflicts += 1;
+            match conflict {
+                Conflict::Node { id: 0, .. } => Resolution::KeepMine,
+                Conflict::Node { .. } => Resolution::TakeTheirs,
+                Conflict::Edge {
+                    losing, winning, ..
+                } => Resolution::MergeEdge(losing.max(winning)),
+            }
+        });
+        assert_eq!(conflicts, 4);
+
+        // the non-conflicting size is still applied
+        let expected_nodes = HashMap::from([
+            (
+                0,
+                NodeUpdate {
+                    size: Some(2.0),
+                    ..label("a")
+                },
+            ),
+            (1, label("y")),
+        ]);
+        let expected_edges = HashMap::from([
+            (0, HashMap::from([(1, 3.0), (2, 4.0)])),
+            (1, HashMap::from([(0, 5.0)])),
+        ]);
+        assert_eq!(nodes, expected_nodes);
+        assert_eq!(edges, expected_edges);
+    }
+
+    #[test]
+    fn test_map_weights() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&0);
+        diff.delete_node(3);
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.add_edge(&0, &2, 3.0).unwrap();
+        diff.add_edge(&1, &2, 4.0).unwrap();
+        diff.delete_edge(&2, &0);
+
+        let mapped = diff.clone().map_weights(|w| w * 0.5);
+        assert_eq!(mapped.nodes, diff.nodes);
+        assert_eq!(mapped.edges.deleted, diff.edges.deleted);
+        for (from, to, weight) in diff.iter_new_or_updated_edges() {
+            assert_eq!(mapped.edge_weight(from, to), Some(&(weight * 0.5)));
+        }
+        assert_eq!(mapped.stats(), diff.stats());
+
+        let rounded: GraphDiff<usize, NodeUpdate, u32> = diff.map_weights(|w| w as u32);
+        assert_eq!(rounded.edge_weight(&1, &2), Some(&4));
+    }
+
+    #[test]
+    fn test_map_ids() {
+        let label = |l: &str| NodeUpdate {
+            label: Some(l.to_string()),
+            ..NodeUpdate::default()
+        };
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(&0, label("a"));
+        diff.add_or_update_node(&1, label("b"));
+        diff.delete_node(2);
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.delete_edge(&1, &0);
+
+        let mapped = diff.clone().map_ids(|id| *id as u64 + 100);
+        let mut expected = GraphDiff::<u64, NodeUpdate>::new();
+        expected.add_or_update_node(&100, label("a"));
+        expected.add_or_update_node(&101, label("b"));
+        expected.delete_node(102);
+        expected.add_edge(&100, &101, 1.0).unwrap();
+        expected.delete_edge(&101, &100);
+        assert_eq!(mapped, expected);
+
+        // 0 and 1 collide, 3 collides with the deleted node 2
+        diff.add_or_update_node(
+            &1,
+            NodeUpdate {
+                size: Some(2.),
+                ..NodeUpdate::default()
+            },
+        );
+        diff.add_node(&3);
+        diff.add_edge(&0, &4, 1.0).unwrap();
+        diff.add_edge(&1, &4, 2.0).unwrap();
+        diff.add_edge(&3, &4, 3.0).unwrap();
+        let mapped = diff.map_ids(|id| id / 2);
+        assert_eq!(
+            mapped.new_or_updated_nodes()[&0],
+            NodeUpdate {
+                label: Some("b".to_string()),
+                size: Some(2.),
+                ..NodeUpdate::default()
+            }
+        );
+        assert_eq!(mapped.edge_weight(&0, &2), Some(&2.0));
+        assert!(mapped.is_edge_deleted(&0, &0));
+        assert!(mapped.is_node_deleted(&1));
+        assert!(mapped.edge_weight(&1, &2).is_none());
+        assert!(mapped.is_internally_consistent());
+    }
+
+    #[test]
+    fn test_delta() {
+        let label = |l: &str| NodeUpdate {
+            label: Some(l.to_string()),
+            ..NodeUpdate::default()
+        };
+        let mut previous = GraphDiff::<usize, NodeUpdate>::new();
+        previous.add_or_update_node(&0, label("kept"));
+        previous.add_or_update_node(&1, label("old"));
+        previous.add_or_update_node(&2, label("removed"));
+        previous.delete_node(3);
+        previous.add_edge(&0, &1, 1.0).unwrap();
+        previous.add_edge(&0, &2, 1.0).unwrap();
+        previous.delete_edge(&1, &0);
+
+        let mut next = GraphDiff::<usize, NodeUpdate>::new();
+        next.add_or_update_node(&0, label("kept"));
+        next.add_or_update_node(&1, label("new"));
+        next.add_or_update_node(&3, label("added"));
+        next.delete_node(4);
+        next.add_edge(&0, &1, 2.0).unwrap();
+        next.add_edge(&1, &3, 1.0).unwrap();
+        next.delete_edge(&1, &0);
+
+        let delta = next.delta_from(&previous);
+        // changed
+        assert_eq!(delta.changed.new_or_updated_nodes()[&1], label("new"));
+        assert_eq!(delta.changed.edge_weight(&0, &1), Some(&2.0));
+        // added
+        assert!(delta.changed.contains_new_or_updated_node(&3));
+        assert!(delta.changed.is_node_deleted(&4));
+        assert_eq!(delta.changed.edge_weight(&1, &3), Some(&1.0));
+        // removed
+        assert!(delta.removed.contains_new_or_updated_node(&2));
+        assert!(delta.removed.is_node_deleted(&3));
+        assert_eq!(delta.removed.edge_weight(&0, &2), Some(&1.0));
+        // unchanged entries are left out
+        assert!(!delta.changed.contains_new_or_updated_node(&0));
+        assert!(!delta.changed.is_edge_deleted(&1, &0));
+        assert!(!delta.removed.is_edge_deleted(&1, &0));
+
+        assert_eq!(GraphDiff::rebase(previous.clone(), delta), next);
+        assert!(previous.delta_from(&previous).changed.is_empty());
+        assert_eq!(
+            GraphDiff::rebase(next.clone(), previous.delta_from(&next)),
+            previous
+        );
+    }
+
+    #[test]
+    fn test_symmetric_difference() {
+        let mut a = GraphDiff::<usize, NodeUpdate>::new();
+        a.add_node(&0);
+        a.add_node(&1);
+        let mut b = a.clone();
+        assert!(a.symmetric_difference(&b).is_empty());
+
+        // nodes only
+        a.add_node(&2);
+        b.delete_node(3);
+        b.get_or_create_mut_node_update(&1).label = Some("b".to_string());
+        let difference = a.symmetric_difference(&b);
+        assert_eq!(difference.nodes_only_in_self, HashSet::from([2]));
+        assert_eq!(difference.nodes_only_in_other, HashSet::from([3]));
+        assert_eq!(difference.nodes_differing, HashSet::from([1]));
+        assert!(difference.edges_only_in_self.is_empty());
+        assert!(difference.edges_differing.is_empty());
+
+        // edges only
+        let (mut a, mut b) = (GraphDiff::<usize, NodeUpdate>::new(), GraphDiff::new());
+        a.add_edge(&0, &1, 1.0).unwrap();
+        a.add_edge(&1, &2, 1.0).unwrap();
+        b.add_edge(&1, &2, 1.0).unwrap();
+        b.delete_edge(&2, &0);
+        let difference = a.symmetric_difference(&b);
+        assert_eq!(difference.edges_only_in_self, HashSet::from([(0, 1)]));
+        assert_eq!(difference.edges_only_in_other, HashSet::from([(2, 0)]));
+        assert!(difference.edges_differing.is_empty());
+        assert!(difference.nodes_only_in_self.is_empty());
+
+        // differing values
+        b.add_edge(&0, &1, 2.0).unwrap();
+        a.delete_edge(&1, &2);
+        b.delete_edge(&2, &0);
+        let difference = b.symmetric_difference(&a);
+        assert_eq!(difference.edges_differing, HashSet::from([(0, 1), (1, 2)]));
+        assert_eq!(difference.edges_only_in_self, HashSet::from([(2, 0)]));
+        assert!(difference.edges_only_in_other.is_empty());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let update = |label: &str| NodeUpdate {
+            label: Some(label.to_string()),
+            size: Some(-0.0),
+            ..NodeUpdate::default()
+        };
+        let mut a = GraphDiff::<usize, NodeUpdate>::new();
+        a.add_or_update_node(&0, update("a"));
+        a.add_or_update_node(&1, update("b"));
+        a.add_edge(&0, &1, 1.0).unwrap();
+        a.add_edge(&1, &0, 0.0).unwrap();
+        a.delete_node(2);
+        a.delete_edge(&1, &3);
+
+        // same contents, built in reverse and with normalized floats
+        let mut b = GraphDiff::<usize, NodeUpdate>::new();
+        b.delete_edge(&1, &3);
+        b.delete_node(2);
+        b.add_edge(&1, &0, -0.0).unwrap();
+        b.add_edge(&0, &1, 1.0).unwrap();
+        b.add_or_update_node(&1, update("b"));
+        b.add_or_update_node(
+            &0,
+            NodeUpdate {
+                size: Some(0.0),
+                ..update("a")
+            },
+        );
+        assert_eq!(a.fingerprint(), b.fingerprint());
+
+        b.add_edge(&0, &1, 2.0).unwrap();
+        assert_ne!(a.fingerprint(), b.fingerprint());
+        b.add_edge(&0, &1, 1.0).unwrap();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+
+        // an updated and a deleted node do not collide
+        let empty = GraphDiff::<usize, NodeUpdate>::new();
+        let (mut a, mut b) = (empty.clone(), empty.clone());
+        a.add_node(&0);
+        b.delete_node(0);
+        assert_ne!(a.fingerprint(), b.fingerprint());
+        assert_ne!(a.fingerprint(), empty.fingerprint());
+    }
+
+    #[test]
+    fn test_invert_node_readd() {
+        let node = NodeUpdate {
+            label: Some("a".to_string()),
+            ..NodeUpdate::default()
+        };
+        let base_nodes = HashMap::from([(0, node.clone()), (1, NodeUpdate::default())]);
+        let base_edges = HashMap::from([
+            (0, HashMap::from([(1, 1.0)])),
+            (1, HashMap::from([(0, 2.0)])),
+        ]);
+
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.delete_node(0);
+        diff.add_node(&2);
+        diff.add_edge(&2, &1, 3.0).unwrap();
+
+        let inverse = diff.invert(&base_nodes, &base_edges);
+        assert_eq!(inverse.nodes.new_or_updated.get(&0), Some(&node));
+        assert!(inverse.nodes.deleted.contains(&2));
+        assert_eq!(
+            inverse.edges.new_or_updated.get(&0).unwrap().get(&1),
+            Some(&1.0)
+        );
+        assert_eq!(
+            inverse.edges.new_or_updated.get(&1).unwrap().get(&0),
+            Some(&2.0)
+        );
+        assert!(inverse.edges.deleted.is_empty());
+        assert!(inverse.is_internally_consistent());
+
+        let (mut nodes, mut edges) = (base_nodes.clone(), base_edges.clone());
+        diff.apply(&mut nodes, &mut edges);
+        inverse.apply(&mut nodes, &mut edges);
+        assert_eq!(nodes, base_nodes);
+        assert_eq!(edges, base_edges);
+    }
+
+    #[test]
+    fn test_invert_node_update() {
+        let base_nodes = HashMap::from([(
+            0,
+            NodeUpdate {
+                label: Some("a".to_string()),
+                size: Some(1.0),
+                ..NodeUpdate::default()
+            },
+        )]);
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_or_update_node(
+            &0,
+            NodeUpdate {
+                label: Some("b".to_string()),
+                url: Some("https://drisk.ai".to_string()),
+                ..NodeUpdate::default()
+            },
+        );
+        diff.get_or_create_mut_node_update(&0)
+            .clear(NodeField::Size);
+
+        let inverse = diff.invert(&base_nodes, &HashMap::new());
+        let mut expected = NodeUpdate {
+            label: Some("a".to_string()),
+            size: Some(1.0),
+            ..NodeUpdate::default()
+        };
+        expected.clear(NodeField::Url);
+        assert_eq!(inverse.nodes.new_or_updated.get(&0), Some(&expected));
+    }
+
+    #[test]
+    fn test_invert_edges() {
+        let base_nodes = (0..3)
+            .map(|i| (i, NodeUpdate::default()))
+            .collect::<HashMap<_, _>>();
+        let base_edges = HashMap::from([(0, HashMap::from([(1, 1.0), (2, 2.0)]))]);
+
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_edge(&0, &1, 5.0).unwrap();
+        diff.add_edge(&1, &2, 6.0).unwrap();
+        diff.delete_edge(&0, &2);
+
+        let inverse = diff.invert(&base_nodes, &base_edges);
+        // weight revert
+        assert_eq!(
+            inverse.edges.new_or_updated.get(&0).unwrap().get(&1),
+            Some(&1.0)
+        );
+        // re-creation
+        assert_eq!(
+            inverse.edges.new_or_updated.get(&0).unwrap().get(&2),
+            Some(&2.0)
+        );
+        // removal of the new edge
+        assert!(inverse.edges.deleted.get(&1).unwrap().contains(&2));
+        assert!(inverse.nodes.new_or_updated.is_empty());
+
+        let (mut nodes, mut edges) = (base_nodes.clone(), base_edges.clone());
+        diff.apply(&mut nodes, &mut edges);
+        inverse.apply(&mut nodes, &mut edges);
+        assert_eq!(edges, base_edges);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&0);
+        diff.delete_node(10);
+        for i in 1..6 {
+            diff.add_edge(&0, &i, 1.0).unwrap();
+        }
+        diff.add_edge(&1, &2, 1.0).unwrap();
+        diff.delete_edge(&6, &7);
+        diff.delete_edge(&6, &0);
+
+        let stats = diff.stats();
+        assert_eq!(
+            stats,
+            DiffStats {
+                new_or_updated_nodes: 1,
+                deleted_nodes: 1,
+                new_or_updated_edges: 6,
+                deleted_edges: 2,
+                distinct_endpoints: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn test_change_counts() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&0);
+        for to in 1..4 {
+            diff.add_edge(&0, &to, 1.0).unwrap();
+            diff.add_edge(&to, &0, 1.0).unwrap();
+        }
+        diff.delete_edge(&4, &0);
+        diff.delete_node(3);
+        diff.delete_node(5);
+
+        assert_eq!(diff.node_change_count(), 3);
+        // 0 -> {1, 2}, {1, 2} -> 0, deleted {0 -> 3, 4 -> 0}
+        assert_eq!(diff.deleted_edge_count(), 2);
+        assert_eq!(diff.edge_change_count(), 6);
     }
-}
 
-impl<Id: Hash + Eq + Copy, T: Default + AddAssign> AddAssign<NodeDiff<Id, T>> for GraphDiff<Id, T> {
-    fn add_assign(&mut self, nodes: NodeDiff<Id, T>) {
-        for (node_id, update) in nodes.new_or_updated {
-            self.add_or_update_node(&node_id, update);
+    #[test]
+    fn test_iterators() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&0);
+        diff.add_node(&1);
+        diff.delete_node(5);
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.add_edge(&0, &2, 2.0).unwrap();
+        diff.add_edge(&1, &2, 3.0).unwrap();
+        diff.delete_edge(&2, &0);
+        diff.delete_edge(&2, &1);
+
+        let nodes = diff
+            .iter_new_or_updated_nodes()
+            .map(|(id, node)| (*id, node.clone()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(&nodes, diff.new_or_updated_nodes());
+        let deleted = diff.iter_deleted_nodes().copied().collect::<HashSet<_>>();
+        assert_eq!(&deleted, diff.deleted_nodes());
+
+        let mut edges: HashMap<usize, HashMap<usize, f32>> = HashMap::new();
+        for (from, to, weight) in diff.iter_new_or_updated_edges() {
+            edges.entry(*from).or_default().insert(*to, *weight);
         }
-        for node_id in nodes.deleted {
-            self.delete_node(node_id);
+        assert_eq!(diff.iter_new_or_updated_edges().count(), 3);
+        assert_eq!(&edges, diff.new_or_updated_edges());
+
+        let mut deleted_edges: HashMap<usize, HashSet<usize>> = HashMap::new();
+        for (from, to) in diff.iter_deleted_edges() {
+            deleted_edges.entry(*from).or_default().insert(*to);
         }
+        assert_eq!(diff.iter_deleted_edges().count(), 2);
+        assert_eq!(&deleted_edges, diff.deleted_edges());
     }
-}
 
-/// A diff between the nodes of a graph.
-#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct NodeDiff<Id: Hash + Eq, T> {
-    new_or_updated: HashMap<Id, T>,
-    deleted: HashSet<Id>,
-}
+    #[test]
+    fn test_contains() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&0);
+        diff.add_node(&1);
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.delete_edge(&1, &0);
+        diff.delete_node(2);
 
-impl<Id: Hash + Eq, T> NodeDiff<Id, T> {
-    pub fn new(new_or_updated: HashMap<Id, T>, deleted: HashSet<Id>) -> NodeDiff<Id, T> {
-        NodeDiff {
-            new_or_updated,
-            deleted,
-        }
-    }
-    pub fn get_new_or_updated(&self) -> &HashMap<Id, T> {
-        &self.new_or_updated
-    }
-    pub fn get_deleted(&self) -> &HashSet<Id> {
-        &self.deleted
+        assert!(diff.contains_new_or_updated_node(&0));
+        assert!(!diff.contains_new_or_updated_node(&3));
+        assert!(!diff.contains_new_or_updated_node(&2));
+        assert!(diff.is_node_deleted(&2));
+        assert!(!diff.is_node_deleted(&0));
+
+        assert_eq!(diff.edge_weight(&0, &1), Some(&1.0));
+        assert_eq!(diff.edge_weight(&0, &3), None);
+        assert_eq!(diff.edge_weight(&1, &0), None);
+        assert!(diff.is_edge_deleted(&1, &0));
+        assert!(!diff.is_edge_deleted(&0, &1));
+        assert!(!diff.is_edge_deleted(&3, &0));
     }
-}
 
-/// A diff between the edges of a graph.
-#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct EdgeDiff<Id: Hash + Eq, W = f32> {
-    new_or_updated: HashMap<Id, HashMap<Id, W>>,
-    deleted: HashMap<Id, HashSet<Id>>,
-}
+    #[test]
+    fn test_outgoing() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.add_edge(&0, &2, 2.0).unwrap();
+        diff.delete_edge(&0, &3);
 
-impl<Id: Hash + Eq> EdgeDiff<Id> {
-    pub fn new(
-        new_or_updated: HashMap<Id, HashMap<Id, f32>>,
-        deleted: HashMap<Id, HashSet<Id>>,
-    ) -> EdgeDiff<Id> {
-        EdgeDiff {
-            new_or_updated,
-            deleted,
-        }
-    }
-    pub fn get_new_or_updated(&self) -> &HashMap<Id, HashMap<Id, f32>> {
-        &self.new_or_updated
-    }
-    pub fn get_deleted(&self) -> &HashMap<Id, HashSet<Id>> {
-        &self.deleted
+        let outgoing = diff.outgoing(&0).unwrap();
+        assert_eq!(outgoing.len(), 2);
+        assert_eq!(outgoing[&2], 2.0);
+        assert_eq!(diff.outgoing_deleted(&0), Some(&HashSet::from([3])));
+
+        assert!(diff.outgoing(&1).is_none());
+        assert!(diff.outgoing_deleted(&1).is_none());
     }
-}
 
-#[cfg(test)]
-mod tests {
+    #[test]
+    fn test_incoming() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for from in 0..5 {
+            for to in 0..5 {
+                diff.add_edge(&from, &to, (from * to) as f32).unwrap();
+            }
+        }
+        diff.delete_node(2);
+        diff.delete_edge(&3, &1);
+        diff.add_edge(&3, &1, 0.5).unwrap();
+        diff.delete_edge(&4, &1);
 
-    use super::*;
-    use crate::node_update::NodeUpdate;
-    use hashbrown::HashMap;
+        let index = diff.reverse_index();
+        for to in 0..6 {
+            let mut brute_force = diff
+                .iter_new_or_updated_edges()
+                .filter(|(_, t, _)| **t == to)
+                .map(|(from, _, w)| (*from, *w))
+                .collect::<Vec<_>>();
+            brute_force.sort_by_key(|(from, _)| *from);
+            for incoming in [index.incoming(&to).to_vec(), diff.incoming(&to)] {
+                let mut incoming = incoming.iter().map(|(f, w)| (**f, **w)).collect::<Vec<_>>();
+                incoming.sort_by_key(|(from, _)| *from);
+                assert_eq!(incoming, brute_force);
+            }
+        }
+        assert!(index.incoming(&2).is_empty());
+        assert_eq!(index.incoming(&1).len(), 3);
+    }
 
     #[test]
-    fn test_node() {
+    fn test_extend() {
         let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.extend_nodes((0..5).map(|i| {
+            (
+                i,
+                NodeUpdate {
+                    size: Some(i as f32),
+                    ..NodeUpdate::default()
+                },
+            )
+        }));
+        assert_eq!(diff.nodes.new_or_updated.len(), 5);
+        assert_eq!(diff.nodes.new_or_updated[&3].size, Some(3.0));
 
-        let id = 1;
-        let mut node = NodeUpdate {
-            label: Some("test".to_string()),
-            ..NodeUpdate::default()
-        };
-
-        diff.add_node(&id);
-        diff.add_or_update_node(&id, node.clone());
-        assert_eq!(diff.nodes.new_or_updated.get(&id).unwrap(), &node);
+        diff.delete_node(2);
+        let rejected = diff
+            .extend_edges((0..5).map(|i| (i, (i + 1) % 5, 1.0)))
+            .unwrap_err();
+        assert_eq!(rejected.len(), 2);
+        assert!(rejected.contains(&(1, 2, 1.0)));
+        assert!(rejected.contains(&(2, 3, 1.0)));
+        assert_eq!(diff.iter_new_or_updated_edges().count(), 3);
 
-        node.size = Some(10.0);
-        diff.add_or_update_node(&id, node.clone());
-        assert_eq!(diff.nodes.new_or_updated.get(&id).unwrap(), &node);
+        assert!(diff.extend_edges(vec![(0, 3, 1.0)]).is_ok());
+    }
 
-        let node2 = NodeUpdate {
-            green: Some(5),
-            ..NodeUpdate::default()
-        };
-        diff.add_or_update_node(&id, node2.clone());
+    #[test]
+    fn test_split_deletions() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&0);
+        diff.add_node(&1);
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.add_edge(&0, &2, 1.0).unwrap();
+        diff.delete_edge(&1, &0);
+        diff.delete_node(2);
 
-        let combined = NodeUpdate {
-            label: Some("test".to_string()),
-            size: Some(10.0),
-            green: Some(5),
-            ..NodeUpdate::default()
-        };
-        assert_eq!(diff.nodes.new_or_updated.get(&id).unwrap(), &combined);
+        let (mut updates, deletions) = diff.clone().split_deletions();
+        assert!(updates.nodes.deleted.is_empty() && updates.edges.deleted.is_empty());
+        assert!(deletions.nodes.new_or_updated.is_empty());
+        assert!(deletions.edges.new_or_updated.is_empty());
 
-        diff.delete_node(id);
-        assert!(diff.nodes.new_or_updated.is_empty());
+        updates += deletions;
+        assert_eq!(updates, diff);
     }
 
     #[test]
-    fn test_edge() {
+    fn test_into_parts() {
         let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&0);
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.delete_edge(&1, &0);
+        diff.delete_node(2);
 
-        let from = 1;
-        let to = 2;
-        let weight = 1.0;
-
-        diff.add_edge(&from, &to, weight).unwrap();
-        assert_eq!(
-            diff.edges
-                .new_or_updated
-                .get(&from)
-                .unwrap()
-                .get(&to)
-                .unwrap(),
-            &weight
+        let expected = (
+            diff.new_or_updated_nodes().clone(),
+            diff.deleted_nodes().clone(),
+            diff.new_or_updated_edges().clone(),
+            diff.deleted_edges().clone(),
         );
+        assert_eq!(diff.into_parts(), expected);
+    }
 
-        let weight2 = 2.0;
-        diff.add_edge(&from, &to, weight2).unwrap();
-        assert_eq!(
-            diff.edges
-                .new_or_updated
-                .get(&from)
-                .unwrap()
-                .get(&to)
-                .unwrap(),
-            &weight2
-        );
+    #[test]
+    fn test_subgraph() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&0);
+        diff.add_node(&1);
+        diff.add_node(&2);
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.add_edge(&1, &2, 2.0).unwrap();
+        diff.delete_edge(&1, &0);
+        diff.delete_edge(&0, &2);
+        diff.delete_node(3);
+        diff.delete_node(4);
 
-        diff.delete_node(from);
-        assert!(diff.edges.new_or_updated.is_empty());
+        let subgraph = diff.subgraph(&HashSet::from([0, 1, 3]));
+        let mut expected = GraphDiff::new();
+        expected.add_node(&0);
+        expected.add_node(&1);
+        expected.add_edge(&0, &1, 1.0).unwrap();
+        expected.delete_edge(&1, &0);
+        expected.delete_node(3);
+        assert_eq!(subgraph, expected);
+        assert!(diff.subgraph(&HashSet::new()).is_empty());
     }
 
     #[test]
-    fn test_add_assign_nodes() {
-        let mut diff1 = GraphDiff::<usize, NodeUpdate>::new();
-        let node = NodeUpdate {
-            label: Some("test".to_string()),
-            ..NodeUpdate::default()
-        };
-        let node_other = NodeUpdate {
-            size: Some(10.0),
-            ..NodeUpdate::default()
-        };
-        diff1.add_node(&1);
-        diff1.add_or_update_node(&1, node.clone());
-        diff1.add_node(&2);
-        diff1.delete_node(3);
+    fn test_deleted_edge_weights() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.delete_edge(&0, &1);
+        assert_eq!(diff.deleted_edge_weights(), None);
 
-        let mut diff2 = GraphDiff::<usize, NodeUpdate>::new();
-        diff2.add_node(&1);
-        diff2.add_or_update_node(&1, node_other.clone());
-        diff2.delete_node(2);
+        diff.record_deleted_weights();
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.add_edge(&1, &2, 2.0).unwrap();
+        diff.add_edge(&2, &3, 3.0).unwrap();
+        diff.add_edge(&3, &0, 4.0).unwrap();
+        diff.delete_edge(&0, &1);
+        diff.delete_edge(&0, &2);
+        diff.delete_node(3);
+        let expected = HashMap::from([
+            (0, HashMap::from([(1, 1.0)])),
+            (2, HashMap::from([(3, 3.0)])),
+            (3, HashMap::from([(0, 4.0)])),
+        ]);
+        assert_eq!(diff.deleted_edge_weights(), Some(&expected));
 
-        diff1 += diff2;
+        // re-added edges are no longer deleted
+        diff.add_edge(&0, &1, 5.0).unwrap();
+        assert!(!diff.deleted_edge_weights().unwrap().contains_key(&0));
 
-        let d1 = diff1.nodes.new_or_updated.get(&1).unwrap();
-        assert_eq!(d1.label.as_ref().unwrap(), "test");
-        assert_eq!(d1.size.unwrap(), 10.0);
-        assert!(!diff1.nodes.new_or_updated.contains_key(&2));
-        assert!(diff1.nodes.deleted.contains(&2));
-        assert!(diff1.nodes.deleted.contains(&3));
+        // not serialized, nor compared
+        let json = crate::graph_diff_to_json(&diff).unwrap();
+        let mut deserialized: GraphDiff<usize, NodeUpdate> =
+            crate::graph_diff_from_json(&json).unwrap();
+        assert_eq!(deserialized.deleted_edge_weights(), None);
+        assert_eq!(deserialized, diff);
+        deserialized.reject_self_loops(true);
+        assert_eq!(deserialized, diff);
     }
 
     #[test]
-    fn test_add_assign_edges() {
-        let mut diff1 = GraphDiff::<usize, NodeUpdate>::new();
-        diff1.add_edge(&1, &2, 1.0).unwrap();
-        diff1.add_edge(&1, &3, 2.0).unwrap();
-        diff1.add_edge(&1, &4, 2.0).unwrap();
-        diff1.add_edge(&2, &3, 3.0).unwrap();
-        diff1.add_edge(&3, &1, 4.0).unwrap();
-
-        let mut diff2 = GraphDiff::<usize, NodeUpdate>::new();
-        diff2.add_edge(&1, &2, 5.0).unwrap();
-        diff2.add_edge(&2, &3, 6.0).unwrap();
-        diff2.add_edge(&3, &1, 7.0).unwrap();
-        diff2.delete_edge(&1, &3);
+    fn test_retain() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        for i in 0..5 {
+            diff.add_node(&i);
+            for j in 0..5 {
+                diff.add_edge(&i, &j, 1.0).unwrap();
+            }
+        }
+        diff.delete_edge(&4, &5);
 
-        diff1 += diff2;
+        let whitelist = HashSet::from([0, 1]);
+        diff.retain_nodes(|id, _| whitelist.contains(id));
+        diff.retain_edges(|from, to, _| whitelist.contains(from) && whitelist.contains(to));
 
         assert_eq!(
-            diff1.edges.new_or_updated.get(&1).unwrap().get(&2).unwrap(),
-            &5.0
-        );
-        assert_eq!(
-            diff1.edges.new_or_updated.get(&2).unwrap().get(&3).unwrap(),
-            &6.0
-        );
-        assert_eq!(
-            diff1.edges.new_or_updated.get(&3).unwrap().get(&1).unwrap(),
-            &7.0
-        );
-        assert_eq!(
-            diff1.edges.new_or_updated.get(&1).unwrap().get(&4).unwrap(),
-            &2.0
+            diff.nodes
+                .new_or_updated
+                .keys()
+                .copied()
+                .collect::<HashSet<_>>(),
+            whitelist
         );
-        assert!(diff1.edges.deleted.get(&1).unwrap().contains(&3));
+        let edges = diff
+            .iter_new_or_updated_edges()
+            .map(|(from, to, _)| (*from, *to))
+            .collect::<HashSet<_>>();
+        assert_eq!(edges, HashSet::from([(0, 0), (0, 1), (1, 0), (1, 1)]));
+        assert_eq!(diff.edges.new_or_updated.len(), 2);
+        assert!(diff.is_edge_deleted(&4, &5));
     }
 
     #[test]
-    fn test_add_edges() {
-        let mut diff = GraphDiff::<usize, usize>::new();
-        for i in 0..50 {
-            diff.add_node(&i);
-        }
-
-        for i in 10..20 {
-            diff.delete_node(i);
-        }
+    fn test_filter_edges_by_weight() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_edge(&0, &1, 0.5).unwrap();
+        diff.add_edge(&0, &2, 1.0).unwrap();
+        diff.add_edge(&1, &2, 1.5).unwrap();
+        diff.add_edge(&2, &0, 2.0).unwrap();
+        diff.add_edge(&2, &1, 2.5).unwrap();
+        diff.add_edge(&3, &0, f32::NAN).unwrap();
+        diff.delete_edge(&3, &1);
 
-        let edges = (0..50usize)
-            .map(|i| {
-                let mut inner = HashMap::new();
-                for j in 0..i {
-                    inner.insert(j, 1f32);
-                }
-                (i, inner)
-            })
-            .collect::<HashMap<usize, HashMap<usize, f32>>>();
+        diff.filter_edges_by_weight(1.0, 2.0);
+        let mut edges = diff
+            .iter_new_or_updated_edges()
+            .map(|(from, to, weight)| (*from, *to, *weight))
+            .collect::<Vec<_>>();
+        edges.sort_by_key(|(from, to, _)| (*from, *to));
+        assert_eq!(edges, vec![(0, 2, 1.0), (1, 2, 1.5), (2, 0, 2.0)]);
+        assert!(diff.outgoing(&3).is_none());
+        assert!(diff.is_edge_deleted(&3, &1));
+    }
 
-        // check can't add if nodes are deleted
-        let mut diff2 = diff.clone();
-        for i in 10..20 {
-            diff2.delete_node(i);
-        }
-        assert!(diff2.add_edges(&edges).is_err());
+    #[test]
+    fn test_prune_dangling_edges() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&0);
+        diff.add_node(&1);
+        diff.delete_node(2);
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.add_edge(&1, &0, 1.0).unwrap();
+        diff.add_edge(&0, &5, 1.0).unwrap();
+        diff.add_edge(&5, &1, 1.0).unwrap();
+        diff.add_edge(&5, &6, 1.0).unwrap();
+        diff.delete_edge(&0, &2);
+        diff.delete_edge(&0, &7);
 
-        for i in 30..40 {
-            diff.delete_node(i);
-        }
+        let mut pruned = diff.clone();
+        assert_eq!(pruned.prune_dangling_edges(false), 3);
+        assert_eq!(pruned.stats().new_or_updated_edges, 2);
+        assert_eq!(pruned.edges.deleted, diff.edges.deleted);
 
-        assert!(diff.is_internally_consistent());
+        assert_eq!(diff.prune_dangling_edges(true), 4);
+        assert!(diff.is_edge_deleted(&0, &2));
+        assert!(!diff.is_edge_deleted(&0, &7));
+        assert_eq!(diff.prune_dangling_edges(true), 0);
     }
 
     #[test]
@@ -597,4 +4414,107 @@ mod tests {
         diff.remove_deleted_edge(&0, &2);
         assert!(!diff.edges.deleted.contains_key(&0));
     }
+
+    #[test]
+    fn test_try_from_diffs() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&0);
+        diff.delete_node(2);
+        diff.add_edge(&0, &1, 1.0).unwrap();
+        diff.delete_edge(&1, &0);
+        // leaves the deleted edge 0 -> 3
+        diff.add_edge(&0, &3, 1.0).unwrap();
+        diff.delete_node(3);
+        let checked = GraphDiff::try_from_diffs(diff.nodes.clone(), diff.edges.clone()).unwrap();
+        assert_eq!(checked, diff);
+
+        let nodes = NodeDiff::<usize, NodeUpdate>::new(HashMap::new(), HashSet::from([1]));
+        let edges = EdgeDiff::new(
+            HashMap::from([(0, HashMap::from([(1, 1.0)]))]),
+            HashMap::new(),
+        );
+        assert!(matches!(
+            GraphDiff::try_from_diffs(nodes, edges),
+            Err(GraphDiffError::InconsistentEdge { from, to }) if from == "0" && to == "1"
+        ));
+    }
+
+    #[test]
+    fn test_validate_against() {
+        let base = HashSet::from([0, 1, 2]);
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        diff.add_node(&3);
+        diff.add_edge(&0, &3, 1.0).unwrap();
+        diff.add_edge(&1, &2, 1.0).unwrap();
+        diff.delete_edge(&2, &0);
+        diff.delete_node(1);
+        assert_eq!(diff.validate_against(&base), Ok(()));
+        assert!(diff.is_consistent_with(&base));
+
+        diff.add_edge(&0, &4, 1.0).unwrap();
+        diff.delete_edge(&5, &0);
+        diff.delete_node(6);
+        let mut issues = diff.validate_against(&base).unwrap_err();
+        issues.sort_by_key(|issue| format!("{:?}", issue));
+        assert_eq!(
+            issues,
+            vec![
+                ValidationIssue::DanglingEdge { from: 0, to: 4 },
+                ValidationIssue::MissingEdge { from: 5, to: 0 },
+                ValidationIssue::MissingNode(6),
+            ]
+        );
+        assert!(!diff.is_consistent_with(&base));
+    }
+
+    #[cfg(feature = "ordered")]
+    #[test]
+    fn test_insertion_order() {
+        let mut diff = GraphDiff::<usize, NodeUpdate>::new();
+        let ids = (0..100).map(|id| (id * 37) % 100).collect::<Vec<_>>();
+        for id in &ids {
+            diff.add_node(id);
+        }
+        for (from, to) in ids.iter().zip(ids.iter().skip(1)) {
+            diff.add_edge(from, to, 1.0).unwrap();
+        }
+        diff.delete_edge(&ids[3], &ids[1]);
+        diff.delete_node(ids[50]);
+        diff.delete_node(ids[10]);
+        // re-adding keeps the first position
+        diff.add_node(&ids[0]);
+
+        let deleted = [ids[50], ids[10]];
+        let kept = ids.iter().filter(|id| !deleted.contains(id));
+        assert!(diff.iter_new_or_updated_nodes().map(|(id, _)| id).eq(kept));
+        assert!(diff.iter_deleted_nodes().eq(&deleted));
+        let edges = ids.iter().zip(ids.iter().skip(1));
+        let kept = edges.filter(|(from, to)| !deleted.contains(from) && !deleted.contains(to));
+        assert!(diff
+            .iter_new_or_updated_edges()
+            .map(|(from, to, _)| (from, to))
+            .eq(kept));
+        let deleted = [(ids[3], ids[1]), (ids[49], ids[50]), (ids[9], ids[10])];
+        assert!(diff
+            .iter_deleted_edges()
+            .map(|(from, to)| (*from, *to))
+            .eq(deleted));
+
+        // the other diff's order follows for entries new to the diff
+        let mut other = GraphDiff::<usize, NodeUpdate>::new();
+        other.add_node(&1000);
+        other.add_node(&ids[1]);
+        other.add_node(&999);
+        diff += other;
+        let last = diff.iter_new_or_updated_nodes().skip(97).map(|(id, _)| *id);
+        assert!(last.eq([ids[99], 1000, 999]));
+
+        diff.clear();
+        diff.add_node(&ids[99]);
+        diff.add_node(&ids[0]);
+        assert!(diff
+            .iter_new_or_updated_nodes()
+            .map(|(id, _)| id)
+            .eq([&ids[99], &ids[0]]));
+    }
 }
@@ -0,0 +1,90 @@
+#[cfg(feature = "std")]
+use crate::bytes::FormatError;
+use alloc::string::String;
+use core::fmt;
+
+/// Error type for `GraphDiff` operations and (de-)serialization.
+///
+/// New variants may be added in minor releases, so matches need a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GraphDiffError {
+    /// The `from` and/or `to` node of an edge is marked as deleted in the diff.
+    EndpointDeleted { from: bool, to: bool },
+    /// A new or updated edge references a node marked as deleted. Holds the `Debug` form of
+    /// the endpoints.
+    InconsistentEdge { from: String, to: String },
+    /// An edge weight is NaN or infinite.
+    NonFiniteWeight,
+    /// An edge from a node to itself was added to a diff rejecting self-loops.
+    SelfLoop,
+    /// The bytes do not start with a supported header.
+    #[cfg(feature = "std")]
+    Format(FormatError),
+    /// Serializing the diff failed.
+    #[cfg(feature = "std")]
+    Serialize(Box<dyn std::error::Error + Send + Sync>),
+    /// Deserializing the diff failed.
+    #[cfg(feature = "std")]
+    Deserialize(Box<dyn std::error::Error + Send + Sync>),
+}
+
+#[cfg(feature = "std")]
+impl GraphDiffError {
+    pub(crate) fn serialize(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        GraphDiffError::Serialize(e.into())
+    }
+
+    pub(crate) fn deserialize(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        GraphDiffError::Deserialize(e.into())
+    }
+}
+
+impl fmt::Display for GraphDiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphDiffError::EndpointDeleted { from, to } => {
+                let endpoints = match (from, to) {
+                    (true, true) => "from and to nodes are",
+                    (true, false) => "from node is",
+                    _ => "to node is",
+                };
+                write!(f, "Edge {} marked to be deleted", endpoints)
+            }
+            GraphDiffError::InconsistentEdge { from, to } => {
+                write!(f, "Edge {} -> {} references a deleted node", from, to)
+            }
+            GraphDiffError::NonFiniteWeight => write!(f, "Edge weight is not finite"),
+            GraphDiffError::SelfLoop => write!(f, "Edge is a self-loop"),
+            #[cfg(feature = "std")]
+            GraphDiffError::Format(e) => write!(f, "{}", e),
+            #[cfg(feature = "std")]
+            GraphDiffError::Serialize(e) => write!(f, "Failed to serialize graph diff: {}", e),
+            #[cfg(feature = "std")]
+            GraphDiffError::Deserialize(e) => {
+                write!(f, "Failed to deserialize graph diff: {}", e)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for GraphDiffError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GraphDiffError::EndpointDeleted { .. }
+            | GraphDiffError::InconsistentEdge { .. }
+            | GraphDiffError::NonFiniteWeight
+            | GraphDiffError::SelfLoop => None,
+            GraphDiffError::Format(e) => Some(e),
+            GraphDiffError::Serialize(e) | GraphDiffError::Deserialize(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<FormatError> for GraphDiffError {
+    fn from(e: FormatError) -> Self {
+        GraphDiffError::Format(e)
+    }
+}
@@ -1,11 +1,21 @@
-/// A Python wrapper to `GraphDiff<Uuid, drisk_api::NodeUpdate>`.
-use crate::{bytes::graph_diff_to_bytes, diff::GraphDiff, node_update::NodeUpdate};
+/// Python wrappers to `GraphDiff<Uuid, drisk_api::NodeUpdate>` with `f32` or `f64` weights and
+/// `GraphDiff<u64, ..>`.
+use crate::{
+    bytes::{
+        bytes_to_graph_diff_tagged, graph_diff_from_json, graph_diff_to_bytes_legacy,
+        graph_diff_to_bytes_tagged, graph_diff_to_json,
+    },
+    diff::{EdgeDiff, GraphDiff, HashMap, HashSet, NodeDiff},
+    node_update::{NodeField, NodeUpdate},
+};
 use pyo3::{
-    exceptions::PyException,
+    exceptions::{PyException, PyOverflowError, PyRuntimeError, PyValueError},
     prelude::*,
-    types::{PyAny, PyBytes, PyDict, PyList},
+    types::{PyAny, PyBool, PyBytes, PyDict, PyFloat, PyList, PyLong, PyString, PyTuple},
 };
-use uuid::Uuid;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use uuid::{Uuid, Variant};
 
 pub struct PyNodeUpdate {
     pub label: Option<String>,
@@ -14,17 +24,127 @@ pub struct PyNodeUpdate {
     pub red: Option<u8>,
     pub green: Option<u8>,
     pub blue: Option<u8>,
+    pub alpha: Option<u8>,
     pub show_label: Option<bool>,
+    pub cleared: HashSet<NodeField>,
+    pub extra: Option<HashMap<String, Value>>,
+}
+
+/// The Python key of a `NodeUpdate` field.
+fn field_key(field: NodeField) -> &'static str {
+    match field {
+        NodeField::Label => "label",
+        NodeField::Size => "size",
+        NodeField::Url => "url",
+        NodeField::Red => "red",
+        NodeField::Green => "green",
+        NodeField::Blue => "blue",
+        NodeField::Alpha => "alpha",
+        NodeField::ShowLabel => "show_label",
+    }
+}
+
+/// Returns `true` if the key is the Python key of a `NodeUpdate` field.
+fn is_field_key(key: &str) -> bool {
+    NodeField::ALL.iter().any(|field| field_key(*field) == key)
+}
+
+/// Raise a `ValueError` listing the keys of a node update dict that are not fields.
+fn check_field_keys(update: &Bound<'_, PyDict>) -> PyResult<()> {
+    let mut unknown = Vec::new();
+    for key in update.keys() {
+        let key = key.str()?.to_str()?.to_owned();
+        if !is_field_key(&key) {
+            unknown.push(key);
+        }
+    }
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    unknown.sort();
+    Err(PyValueError::new_err(format!(
+        "unrecognized node update keys: {}",
+        unknown.join(", ")
+    )))
+}
+
+/// Convert a Python object to a JSON value for `NodeUpdate::extra`.
+fn py_to_json(ob: &PyAny) -> PyResult<Value> {
+    if ob.is_none() {
+        return Ok(Value::Null);
+    }
+    // `bool` is a subclass of `int`, so it has to be checked first
+    if let Ok(b) = ob.downcast::<PyBool>() {
+        return Ok(Value::Bool(b.is_true()));
+    }
+    if ob.is_instance_of::<PyLong>() {
+        return Ok(Value::from(ob.extract::<i64>()?));
+    }
+    if let Ok(f) = ob.downcast::<PyFloat>() {
+        return serde_json::Number::from_f64(f.value())
+            .map(Value::Number)
+            .ok_or_else(|| PyValueError::new_err("extra properties must be finite numbers"));
+    }
+    if let Ok(s) = ob.downcast::<PyString>() {
+        return Ok(Value::String(s.to_str()?.to_owned()));
+    }
+    if ob.is_instance_of::<PyList>() || ob.is_instance_of::<PyTuple>() {
+        return ob.iter()?.map(|item| py_to_json(item?)).collect();
+    }
+    if let Ok(dict) = ob.downcast::<PyDict>() {
+        return dict
+            .iter()
+            .map(|(k, v)| Ok((k.extract::<String>()?, py_to_json(v)?)))
+            .collect();
+    }
+    Err(PyValueError::new_err(format!(
+        "unsupported extra property type {}",
+        ob.get_type().name()?
+    )))
+}
+
+/// Convert a JSON value of `NodeUpdate::extra` to a Python object.
+fn json_to_py(py: Python<'_>, value: &Value) -> PyObject {
+    match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => b.into_py(py),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_py(py),
+            (_, Some(u)) => u.into_py(py),
+            _ => n.as_f64().into_py(py),
+        },
+        Value::String(s) => s.into_py(py),
+        Value::Array(items) => {
+            PyList::new_bound(py, items.iter().map(|item| json_to_py(py, item))).into()
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new_bound(py);
+            for (k, v) in map {
+                let _ = dict.set_item(k, json_to_py(py, v));
+            }
+            dict.into()
+        }
+    }
+}
+
+/// Whether `size` is a valid node size: finite and non-negative, so NaN is rejected.
+fn is_valid_size(size: f32) -> bool {
+    size.is_finite() && size >= 0.0
 }
 
 impl<'s> FromPyObject<'s> for PyNodeUpdate {
     fn extract(ob: &'s PyAny) -> PyResult<Self> {
         let dict = ob.downcast::<PyDict>()?;
+        let mut cleared = HashSet::new();
 
-        // helper macro to reduce code to go from PyAny -> T
+        // helper macro to reduce code to go from PyAny -> T, a `None` value clears the field
         macro_rules! extract_field {
-            ($lab: expr, $ty: ty) => {
-                match dict.get_item($lab) {
+            ($field: expr, $ty: ty) => {
+                match dict.get_item(field_key($field)) {
+                    Ok(Some(item)) if item.is_none() => {
+                        cleared.insert($field);
+                        Ok(None)
+                    }
                     Ok(Some(item)) => item.extract::<$ty>().map(Some).map_err(PyErr::from),
                     Ok(None) => Ok(None),
                     Err(e) => Err(e),
@@ -32,14 +152,55 @@ impl<'s> FromPyObject<'s> for PyNodeUpdate {
             };
         }
 
+        // color channels are extracted as integers first to raise a clear range error, also
+        // for integers too large for an `i64`
+        let range_error = |field: NodeField| {
+            PyValueError::new_err(format!("{} must be between 0 and 255", field_key(field)))
+        };
+        macro_rules! extract_channel {
+            ($field: expr) => {
+                match extract_field!($field, i64) {
+                    Ok(Some(value)) => u8::try_from(value)
+                        .map(Some)
+                        .map_err(|_| range_error($field)),
+                    Ok(None) => Ok(None),
+                    Err(e) if e.is_instance_of::<PyOverflowError>(ob.py()) => {
+                        Err(range_error($field))
+                    }
+                    Err(e) => Err(e),
+                }
+            };
+        }
+
+        // keys other than the `NodeUpdate` fields are kept as extra properties
+        let mut extra = HashMap::new();
+        for (key, value) in dict.iter() {
+            let Ok(key) = key.extract::<String>() else {
+                continue;
+            };
+            if !is_field_key(&key) {
+                extra.insert(key, py_to_json(value)?);
+            }
+        }
+
+        let size = extract_field!(NodeField::Size, f32)?;
+        if size.is_some_and(|size| !is_valid_size(size)) {
+            return Err(PyValueError::new_err(
+                "size must be a finite non-negative number",
+            ));
+        }
+
         Ok(PyNodeUpdate {
-            label: extract_field!("label", String)?,
-            url: extract_field!("url", String)?,
-            size: extract_field!("size", f32)?,
-            red: extract_field!("red", u8)?,
-            green: extract_field!("green", u8)?,
-            blue: extract_field!("blue", u8)?,
-            show_label: extract_field!("show_label", bool)?,
+            label: extract_field!(NodeField::Label, String)?,
+            url: extract_field!(NodeField::Url, String)?,
+            size,
+            red: extract_channel!(NodeField::Red)?,
+            green: extract_channel!(NodeField::Green)?,
+            blue: extract_channel!(NodeField::Blue)?,
+            alpha: extract_channel!(NodeField::Alpha)?,
+            show_label: extract_field!(NodeField::ShowLabel, bool)?,
+            cleared,
+            extra: (!extra.is_empty()).then_some(extra),
         })
     }
 }
@@ -61,7 +222,14 @@ impl ToPyObject for NodeUpdate {
         set_item!("red", self.red);
         set_item!("green", self.green);
         set_item!("blue", self.blue);
+        set_item!("alpha", self.alpha);
         set_item!("show_label", self.show_label);
+        for field in &self.cleared {
+            let _ = dict.set_item(field_key(*field), py.None());
+        }
+        for (key, value) in self.extra.iter().flatten() {
+            let _ = dict.set_item(key, json_to_py(py, value));
+        }
 
         dict.into()
     }
@@ -76,7 +244,10 @@ impl From<PyNodeUpdate> for NodeUpdate {
             red: node_update.red,
             green: node_update.green,
             blue: node_update.blue,
+            alpha: node_update.alpha,
             show_label: node_update.show_label,
+            cleared: node_update.cleared,
+            extra: node_update.extra,
         }
     }
 }
@@ -89,110 +260,902 @@ fn pybytes_to_uuid(bytes: &Bound<'_, PyAny>) -> PyResult<Uuid> {
     Uuid::from_slice(bytes).map_err(|_| PyException::new_err("Failed to parse UUID."))
 }
 
+/// Raise a `ValueError` unless the UUID is of version 4 or 7 with the RFC 4122 variant, the
+/// node ids accepted by a diff created with `strict_uuids=True`.
+fn check_strict_uuid(uuid: &Uuid) -> PyResult<()> {
+    if !matches!(uuid.get_version_num(), 4 | 7) {
+        return Err(PyValueError::new_err(format!(
+            "Expected a version 4 or 7 UUID, got version {} in {}.",
+            uuid.get_version_num(),
+            uuid
+        )));
+    }
+    if uuid.get_variant() != Variant::RFC4122 {
+        return Err(PyValueError::new_err(format!(
+            "Expected an RFC 4122 UUID, got the {:?} variant in {}.",
+            uuid.get_variant(),
+            uuid
+        )));
+    }
+    Ok(())
+}
+
 #[derive(FromPyObject)]
 pub struct PyUuid(#[pyo3(from_py_with = "pybytes_to_uuid")] Uuid);
 
-#[pyclass]
-pub struct PyGraphDiff(GraphDiff<Uuid, NodeUpdate>);
+#[derive(FromPyObject)]
+pub struct PyU64(u64);
+
+/// Conversion of node ids to the Python objects returned by a diff.
+trait PyId: Sized {
+    /// Parse the id from its string form, as found in CSV files.
+    fn parse(s: &str) -> Option<Self>;
+    /// The id as a key of the dicts and lists returned by a diff.
+    fn key(&self, py: Python<'_>) -> PyObject;
+    /// The id as yielded by the edge iterator.
+    fn item(&self, py: Python<'_>) -> PyObject;
+    /// Raise a `ValueError` if the id is not accepted by a diff with `strict_uuids=True`.
+    fn check_strict(&self) -> PyResult<()>;
+}
 
-#[pymethods]
-impl PyGraphDiff {
-    #[new]
-    fn new() -> Self {
-        PyGraphDiff(GraphDiff::<_, _, f32>::new())
+impl PyId for Uuid {
+    fn parse(s: &str) -> Option<Self> {
+        Uuid::parse_str(s).ok()
     }
 
-    fn new_or_updated_nodes<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
-        let dict = PyDict::new_bound(py);
-        for (id, node) in self.0.new_or_updated_nodes() {
-            dict.set_item(id.to_string(), node.to_object(py))?;
-        }
-        PyResult::Ok(dict)
+    fn key(&self, py: Python<'_>) -> PyObject {
+        self.to_string().into_py(py)
     }
 
-    fn deleted_nodes<'a>(&self, py: Python<'a>) -> Bound<'a, PyList> {
-        let ids = self
-            .0
-            .deleted_nodes()
+    fn item(&self, py: Python<'_>) -> PyObject {
+        PyBytes::new_bound(py, self.as_bytes()).into()
+    }
+
+    fn check_strict(&self) -> PyResult<()> {
+        check_strict_uuid(self)
+    }
+}
+
+impl PyId for u64 {
+    fn parse(s: &str) -> Option<Self> {
+        s.parse().ok()
+    }
+
+    fn key(&self, py: Python<'_>) -> PyObject {
+        self.into_py(py)
+    }
+
+    fn item(&self, py: Python<'_>) -> PyObject {
+        self.into_py(py)
+    }
+
+    fn check_strict(&self) -> PyResult<()> {
+        Ok(())
+    }
+}
+
+/// Rows of a CSV file with a header, giving access to fields by column name.
+///
+/// Fields may be quoted with `"`, a quote inside a quoted field is escaped as `""`. Empty
+/// lines are skipped.
+struct CsvTable {
+    name: &'static str,
+    columns: Vec<String>,
+    /// The records with the line they start on.
+    rows: Vec<(u64, csv::StringRecord)>,
+}
+
+impl CsvTable {
+    fn parse(name: &'static str, text: &str, required: &[&str]) -> PyResult<CsvTable> {
+        let csv_error = |e: csv::Error| {
+            let line = e.position().map_or(1, |position| position.line());
+            let message = match e.kind() {
+                csv::ErrorKind::UnequalLengths {
+                    expected_len, len, ..
+                } => format!("expected {} fields, found {}", expected_len, len),
+                _ => e.to_string(),
+            };
+            PyValueError::new_err(format!("{} csv line {}: {}", name, line, message))
+        };
+        let mut reader = csv::Reader::from_reader(text.as_bytes());
+        let columns = reader
+            .headers()
+            .map_err(csv_error)?
             .iter()
-            .map(|n| n.to_string())
+            .map(|c| c.trim().to_owned())
             .collect::<Vec<_>>();
-        let list = PyList::new_bound(py, ids);
-        list
+        for column in required {
+            if !columns.iter().any(|c| c == column) {
+                return Err(PyValueError::new_err(format!(
+                    "{} csv is missing the {} column",
+                    name, column
+                )));
+            }
+        }
+        let rows = reader
+            .into_records()
+            .map(|record| {
+                let record = record.map_err(csv_error)?;
+                let line = record.position().map_or(1, |position| position.line());
+                Ok((line, record))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(CsvTable {
+            name,
+            columns,
+            rows,
+        })
     }
 
-    fn new_or_updated_edges<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
-        let dict = PyDict::new_bound(py);
-        for (from, tos) in self.0.new_or_updated_edges() {
-            if tos.is_empty() {
-                continue;
-            }
-            let tos_dict = PyDict::new_bound(py);
-            for (to, weight) in tos {
-                tos_dict.set_item(to.to_string(), weight)?;
-            }
-            dict.set_item(from.to_string(), tos_dict)?;
+    /// Get the non-empty field of a row in a column, `None` if the column is missing.
+    fn get<'a>(&self, row: &'a csv::StringRecord, column: &str) -> Option<&'a str> {
+        let index = self.columns.iter().position(|c| c == column)?;
+        row.get(index).filter(|field| !field.is_empty())
+    }
+
+    /// Parse the field of a row in a column, raising a `ValueError` naming the line.
+    fn parse_field<V: std::str::FromStr>(
+        &self,
+        (line, row): &(u64, csv::StringRecord),
+        column: &str,
+    ) -> PyResult<Option<V>> {
+        match self.get(row, column) {
+            Some(field) => field.trim().parse().map(Some).map_err(|_| {
+                PyValueError::new_err(format!(
+                    "{} csv line {}: invalid {} {:?}",
+                    self.name, line, column, field
+                ))
+            }),
+            None => Ok(None),
         }
-        PyResult::Ok(dict)
     }
 
-    fn deleted_edges<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
-        let dict = PyDict::new_bound(py);
-        for (from, tos) in self.0.deleted_edges() {
-            if tos.is_empty() {
-                continue;
-            }
-            let tos_list =
-                PyList::new_bound(py, tos.iter().map(|to| to.to_string()).collect::<Vec<_>>());
-            dict.set_item(from.to_string(), tos_list)?;
+    /// Returns `true` if the row has `deleted` in the `status` column.
+    fn is_deleted(&self, (line, row): &(u64, csv::StringRecord)) -> PyResult<bool> {
+        match self.get(row, "status").map(str::trim) {
+            None | Some("updated") => Ok(false),
+            Some("deleted") => Ok(true),
+            Some(status) => Err(PyValueError::new_err(format!(
+                "{} csv line {}: invalid status {:?}",
+                self.name, line, status
+            ))),
         }
-        PyResult::Ok(dict)
     }
 
-    fn num_nodes(&self) -> usize {
-        self.0.nodes.get_new_or_updated().len() + self.0.nodes.get_deleted().len()
+    /// Parse the `cleared` column, the Python keys of the cleared fields separated by `;`.
+    fn parse_cleared(
+        &self,
+        (line, row): &(u64, csv::StringRecord),
+    ) -> PyResult<HashSet<NodeField>> {
+        let Some(field) = self.get(row, "cleared") else {
+            return Ok(HashSet::new());
+        };
+        field
+            .split(';')
+            .map(|key| {
+                let key = key.trim();
+                NodeField::ALL
+                    .into_iter()
+                    .find(|field| field_key(*field) == key)
+                    .ok_or_else(|| {
+                        PyValueError::new_err(format!(
+                            "{} csv line {}: invalid cleared field {:?}",
+                            self.name, line, key
+                        ))
+                    })
+            })
+            .collect()
     }
 
-    fn num_edges(&self) -> usize {
-        self.0.edges.get_new_or_updated().len() + self.0.edges.get_deleted().len()
+    /// Parse the `extra` column, a JSON object of extra properties.
+    fn parse_extra(
+        &self,
+        (line, row): &(u64, csv::StringRecord),
+    ) -> PyResult<Option<HashMap<String, Value>>> {
+        match self.get(row, "extra") {
+            Some(field) => serde_json::from_str(field).map(Some).map_err(|e| {
+                PyValueError::new_err(format!(
+                    "{} csv line {}: invalid extra {:?}: {}",
+                    self.name, line, field, e
+                ))
+            }),
+            None => Ok(None),
+        }
     }
 
-    fn add_node(&mut self, id: PyUuid, update: PyNodeUpdate) {
-        self.0.add_or_update_node(&id.0, update.into());
+    fn parse_id<Id: PyId>(
+        &self,
+        (line, row): &(u64, csv::StringRecord),
+        column: &str,
+    ) -> PyResult<Id> {
+        let field = self.get(row, column).unwrap_or_default();
+        Id::parse(field.trim()).ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "{} csv line {}: invalid {} {:?}",
+                self.name, line, column, field
+            ))
+        })
     }
+}
 
-    fn delete_node(&mut self, id: PyUuid) {
-        self.0.delete_node(id.0);
+/// Build a diff from CSV files of nodes and edges in the dRISK Edge export format.
+///
+/// Nodes have the columns `id,label,url,color_red,color_green,color_blue,size` and edges
+/// `from,to,weight`. Only the id columns are required, edges default to a weight of 1. An
+/// optional `status` column marks rows as `deleted`, as written by `graph_diff_to_csv`.
+fn graph_diff_from_csv<Id, W>(
+    nodes_csv: &str,
+    edges_csv: &str,
+) -> PyResult<GraphDiff<Id, NodeUpdate, W>>
+where
+    Id: PyId + std::hash::Hash + Eq + Copy,
+    W: Copy + PartialEq + std::str::FromStr + From<u8>,
+{
+    let mut diff = GraphDiff::new();
+    let nodes = CsvTable::parse("nodes", nodes_csv, &["id"])?;
+    for row in &nodes.rows {
+        let id = nodes.parse_id(row, "id")?;
+        if nodes.is_deleted(row)? {
+            diff.delete_node(id);
+            continue;
+        }
+        let size = nodes.parse_field::<f32>(row, "size")?;
+        if size.is_some_and(|size| !is_valid_size(size)) {
+            return Err(PyValueError::new_err(format!(
+                "nodes csv line {}: size must be a finite non-negative number",
+                row.0
+            )));
+        }
+        let update = NodeUpdate {
+            label: nodes.get(&row.1, "label").map(str::to_owned),
+            url: nodes.get(&row.1, "url").map(str::to_owned),
+            red: nodes.parse_field(row, "color_red")?,
+            green: nodes.parse_field(row, "color_green")?,
+            blue: nodes.parse_field(row, "color_blue")?,
+            size,
+            alpha: nodes.parse_field(row, "color_alpha")?,
+            show_label: nodes.parse_field(row, "show_label")?,
+            cleared: nodes.parse_cleared(row)?,
+            extra: nodes.parse_extra(row)?,
+        };
+        diff.add_or_update_node(&id, update);
     }
+    let edges = CsvTable::parse("edges", edges_csv, &["from", "to"])?;
+    for row in &edges.rows {
+        let (from, to) = (edges.parse_id(row, "from")?, edges.parse_id(row, "to")?);
+        if edges.is_deleted(row)? {
+            diff.delete_edge(&from, &to);
+            continue;
+        }
+        let weight = edges.parse_field(row, "weight")?.unwrap_or(W::from(1));
+        diff.add_edge(&from, &to, weight)
+            .map_err(|e| PyValueError::new_err(format!("edges csv line {}: {}", row.0, e)))?;
+    }
+    Ok(diff)
+}
+
+/// Columns of the nodes CSV written by `graph_diff_to_csv`.
+const NODE_CSV_COLUMNS: [&str; 12] = [
+    "id",
+    "label",
+    "url",
+    "color_red",
+    "color_green",
+    "color_blue",
+    "size",
+    "color_alpha",
+    "show_label",
+    "extra",
+    "cleared",
+    "status",
+];
 
-    fn add_edge(&mut self, from: PyUuid, to: PyUuid, weight: f32) {
-        let _ = self.0.add_edge(&from.0, &to.0, weight);
+/// Write a diff as CSV files of nodes and edges in the format read by `graph_diff_from_csv`.
+///
+/// Nodes have the columns of the dRISK Edge export, followed by `color_alpha`, `show_label`,
+/// `extra` with the extra properties as a JSON object and `cleared` with the Python keys of
+/// the cleared fields separated by `;`. Deleted nodes and edges are rows with `deleted` in
+/// the trailing `status` column and empty property fields, other rows have an empty status.
+/// Rows are sorted by id.
+fn graph_diff_to_csv<Id, W>(diff: &GraphDiff<Id, NodeUpdate, W>) -> PyResult<(String, String)>
+where
+    Id: std::hash::Hash + Eq + Copy + Ord + std::fmt::Display,
+    W: Copy + PartialEq + std::fmt::Display,
+{
+    let mut node_rows = Vec::new();
+    for (id, update) in diff.iter_new_or_updated_nodes() {
+        let extra = match &update.extra {
+            Some(extra) => serde_json::to_string(&extra.iter().collect::<BTreeMap<_, _>>())
+                .map_err(|e| PyValueError::new_err(e.to_string()))?,
+            None => String::new(),
+        };
+        let mut cleared = update
+            .cleared
+            .iter()
+            .map(|field| field_key(*field))
+            .collect::<Vec<_>>();
+        cleared.sort_unstable();
+        let row = [
+            id.to_string(),
+            update.label.clone().unwrap_or_default(),
+            update.url.clone().unwrap_or_default(),
+            update.red.map(|v| v.to_string()).unwrap_or_default(),
+            update.green.map(|v| v.to_string()).unwrap_or_default(),
+            update.blue.map(|v| v.to_string()).unwrap_or_default(),
+            update.size.map(|v| v.to_string()).unwrap_or_default(),
+            update.alpha.map(|v| v.to_string()).unwrap_or_default(),
+            update.show_label.map(|v| v.to_string()).unwrap_or_default(),
+            extra,
+            cleared.join(";"),
+            String::new(),
+        ];
+        node_rows.push((*id, row));
+    }
+    for id in diff.iter_deleted_nodes() {
+        let mut row: [String; 12] = Default::default();
+        (row[0], row[11]) = (id.to_string(), "deleted".to_owned());
+        node_rows.push((*id, row));
     }
+    node_rows.sort_unstable_by_key(|(id, _)| *id);
+
+    let mut edge_rows = diff
+        .iter_new_or_updated_edges()
+        .map(|(from, to, weight)| {
+            let row = [
+                from.to_string(),
+                to.to_string(),
+                weight.to_string(),
+                String::new(),
+            ];
+            ((*from, *to), row)
+        })
+        .chain(diff.iter_deleted_edges().map(|(from, to)| {
+            let row = [
+                from.to_string(),
+                to.to_string(),
+                String::new(),
+                "deleted".to_owned(),
+            ];
+            ((*from, *to), row)
+        }))
+        .collect::<Vec<_>>();
+    edge_rows.sort_unstable_by_key(|(key, _)| *key);
+
+    let nodes = write_csv(&NODE_CSV_COLUMNS, node_rows.iter().map(|(_, row)| row))?;
+    let edges = write_csv(
+        &["from", "to", "weight", "status"],
+        edge_rows.iter().map(|(_, row)| row),
+    )?;
+    Ok((nodes, edges))
+}
+
+/// Write CSV text with a header and rows, quoting fields as needed.
+fn write_csv<'a, R: AsRef<[String]> + 'a>(
+    header: &[&str],
+    rows: impl Iterator<Item = &'a R>,
+) -> PyResult<String> {
+    let csv_error = |e: csv::Error| PyValueError::new_err(e.to_string());
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(header).map_err(csv_error)?;
+    for row in rows {
+        writer.write_record(row.as_ref()).map_err(csv_error)?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| csv_error(e.into_error().into()))?;
+    Ok(String::from_utf8(bytes).expect("CSV of strings is UTF-8"))
+}
+
+/// Number of edges an edge iterator copies from its diff at a time.
+const EDGE_ITER_CHUNK: usize = 1024;
+
+/// Define a Python wrapper to `GraphDiff<$id, NodeUpdate, $w>` and its edge iterator,
+/// taking ids as `$py_id`. Methods specific to one wrapper can be passed as a trailing block.
+macro_rules! py_graph_diff {
+    ($name: ident, $iter: ident, $id: ty, $py_id: ty, $w: ty $(, { $($methods: tt)* })?) => {
+        #[pyclass(module = "drisk_api.drisk_api")]
+        pub struct $name {
+            diff: GraphDiff<$id, NodeUpdate, $w>,
+            /// Incremented by each change to the edges, so that edge iterators notice it.
+            edge_version: u64,
+            /// Whether the node ids passed to this diff are checked with `PyId::check_strict`.
+            strict_uuids: bool,
+            /// The bytes serialized when the diff last exited a `with` block without error.
+            result: Option<Py<PyBytes>>,
+        }
+
+        impl From<GraphDiff<$id, NodeUpdate, $w>> for $name {
+            fn from(diff: GraphDiff<$id, NodeUpdate, $w>) -> Self {
+                $name {
+                    diff,
+                    edge_version: 0,
+                    strict_uuids: false,
+                    result: None,
+                }
+            }
+        }
+
+        impl $name {
+            /// The node id, checked if the diff was created with `strict_uuids=True`.
+            fn id(&self, id: $py_id) -> PyResult<$id> {
+                if self.strict_uuids {
+                    id.0.check_strict()?;
+                }
+                Ok(id.0)
+            }
+        }
+
+        #[pymethods]
+        impl $name {
+            /// Create an empty diff. With `strict_uuids=True` the methods adding or deleting
+            /// nodes and edges raise a `ValueError` for UUIDs that are not of version 4 or 7
+            /// with the RFC 4122 variant. This is opt-in so that existing callers keep working,
+            /// and has no effect on integer ids.
+            #[new]
+            #[pyo3(signature = (strict_uuids = false))]
+            fn new(strict_uuids: bool) -> Self {
+                $name {
+                    strict_uuids,
+                    ..$name::from(GraphDiff::<_, _, $w>::new())
+                }
+            }
+
+            #[getter]
+            fn strict_uuids(&self) -> bool {
+                self.strict_uuids
+            }
+
+            fn new_or_updated_nodes<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+                let dict = PyDict::new_bound(py);
+                for (id, node) in self.diff.new_or_updated_nodes() {
+                    dict.set_item(id.key(py), node.to_object(py))?;
+                }
+                PyResult::Ok(dict)
+            }
+
+            fn deleted_nodes<'a>(&self, py: Python<'a>) -> Bound<'a, PyList> {
+                let ids = self
+                    .diff
+                    .deleted_nodes()
+                    .iter()
+                    .map(|n| n.key(py))
+                    .collect::<Vec<_>>();
+                let list = PyList::new_bound(py, ids);
+                list
+            }
+
+            fn new_or_updated_edges<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+                let dict = PyDict::new_bound(py);
+                for (from, tos) in self.diff.new_or_updated_edges() {
+                    if tos.is_empty() {
+                        continue;
+                    }
+                    let tos_dict = PyDict::new_bound(py);
+                    for (to, weight) in tos {
+                        tos_dict.set_item(to.key(py), weight)?;
+                    }
+                    dict.set_item(from.key(py), tos_dict)?;
+                }
+                PyResult::Ok(dict)
+            }
+
+            fn deleted_edges<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+                let dict = PyDict::new_bound(py);
+                for (from, tos) in self.diff.deleted_edges() {
+                    if tos.is_empty() {
+                        continue;
+                    }
+                    let tos_list =
+                        PyList::new_bound(py, tos.iter().map(|to| to.key(py)).collect::<Vec<_>>());
+                    dict.set_item(from.key(py), tos_list)?;
+                }
+                PyResult::Ok(dict)
+            }
+
+            /// Iterate over the new or updated edges as `(from, to, weight)` tuples without
+            /// building a dict of all edges.
+            ///
+            /// Like a dict iterator, the iterator raises a `RuntimeError` if the edges of the
+            /// diff change during iteration.
+            fn iter_edges(slf: &Bound<'_, Self>) -> $iter {
+                $iter {
+                    diff: slf.clone().unbind(),
+                    edge_version: slf.borrow().edge_version,
+                    position: (0, 0),
+                    chunk: Vec::new().into_iter(),
+                }
+            }
+
+            /// The new or updated edges as a list of `(from, to, weight)` tuples, e.g. for
+            /// `pandas.DataFrame.from_records`.
+            fn edges_as_list<'a>(&self, py: Python<'a>) -> Bound<'a, PyList> {
+                let edges = self
+                    .diff
+                    .iter_new_or_updated_edges()
+                    .map(|(from, to, weight)| (from.item(py), to.item(py), *weight).into_py(py))
+                    .collect::<Vec<PyObject>>();
+                PyList::new_bound(py, edges)
+            }
+
+            /// The deleted edges as a list of `(from, to)` tuples.
+            fn deleted_edges_as_list<'a>(&self, py: Python<'a>) -> Bound<'a, PyList> {
+                let edges = self
+                    .diff
+                    .iter_deleted_edges()
+                    .map(|(from, to)| (from.item(py), to.item(py)).into_py(py))
+                    .collect::<Vec<PyObject>>();
+                PyList::new_bound(py, edges)
+            }
+
+            fn num_nodes(&self) -> usize {
+                self.diff.node_change_count()
+            }
+
+            fn num_edges(&self) -> usize {
+                self.diff.edge_change_count()
+            }
+
+            fn is_empty(&self) -> bool {
+                self.diff.is_empty()
+            }
+
+            fn __bool__(&self) -> bool {
+                !self.diff.is_empty()
+            }
+
+            fn __len__(&self) -> usize {
+                self.num_nodes() + self.num_edges()
+            }
+
+            fn __eq__(&self, other: &Self) -> bool {
+                self.diff == other.diff
+            }
+
+            fn __repr__(&self) -> String {
+                let stats = self.diff.stats();
+                format!(
+                    "{}(nodes={} new/{} del, edges={} new/{} del)",
+                    stringify!($name),
+                    stats.new_or_updated_nodes,
+                    stats.deleted_nodes,
+                    stats.new_or_updated_edges,
+                    stats.deleted_edges
+                )
+            }
+
+            fn add_node(&mut self, id: $py_id, update: PyNodeUpdate) -> PyResult<()> {
+                let id = self.id(id)?;
+                self.diff.add_or_update_node(&id, update.into());
+                Ok(())
+            }
+
+            /// Like `add_node`, but raises a `ValueError` for keys that are not node fields
+            /// instead of keeping them as extra properties.
+            fn add_node_strict(&mut self, id: $py_id, update: &Bound<'_, PyDict>) -> PyResult<()> {
+                check_field_keys(update)?;
+                let id = self.id(id)?;
+                self.diff
+                    .add_or_update_node(&id, update.extract::<PyNodeUpdate>()?.into());
+                Ok(())
+            }
+
+            /// Add or update nodes in batch from a dict of ids to node properties.
+            fn add_nodes(&mut self, updates: &Bound<'_, PyDict>) -> PyResult<()> {
+                let updates = updates
+                    .iter()
+                    .map(|(id, update)| {
+                        Ok((self.id(id.extract()?)?, update.extract::<PyNodeUpdate>()?))
+                    })
+                    .collect::<PyResult<Vec<_>>>()?;
+                for (id, update) in updates {
+                    self.diff.add_or_update_node(&id, update.into());
+                }
+                Ok(())
+            }
+
+            fn delete_node(&mut self, id: $py_id) -> PyResult<()> {
+                let id = self.id(id)?;
+                self.diff.delete_node(id);
+                self.edge_version += 1;
+                Ok(())
+            }
+
+            /// Build a diff from its components: a dict of ids to node properties, a list of
+            /// deleted ids, a nested dict of `from` ids to `to` ids to weight and a dict of
+            /// `from` ids to lists of deleted `to` ids.
+            ///
+            /// Raises a `ValueError` if a node is both updated and deleted or an edge
+            /// references a deleted node.
+            #[staticmethod]
+            fn from_components(
+                new_nodes: &Bound<'_, PyDict>,
+                deleted_nodes: Vec<$py_id>,
+                new_edges: &Bound<'_, PyDict>,
+                deleted_edges: &Bound<'_, PyDict>,
+            ) -> PyResult<$name> {
+                let mut nodes = HashMap::new();
+                for (id, update) in new_nodes.iter() {
+                    let update: NodeUpdate = update.extract::<PyNodeUpdate>()?.into();
+                    nodes.insert(id.extract::<$py_id>()?.0, update);
+                }
+                let deleted = deleted_nodes
+                    .into_iter()
+                    .map(|id| id.0)
+                    .collect::<HashSet<_>>();
+                if deleted.iter().any(|id| nodes.contains_key(id)) {
+                    return Err(PyValueError::new_err("a node is both updated and deleted"));
+                }
 
-    fn delete_edge(&mut self, from: PyUuid, to: PyUuid) {
-        self.0.delete_edge(&from.0, &to.0);
+                let mut edges = HashMap::new();
+                for (from, tos) in new_edges.iter() {
+                    let mut to_weight = HashMap::new();
+                    for (to, weight) in tos.downcast::<PyDict>()?.iter() {
+                        to_weight.insert(to.extract::<$py_id>()?.0, weight.extract::<$w>()?);
+                    }
+                    if !to_weight.is_empty() {
+                        edges.insert(from.extract::<$py_id>()?.0, to_weight);
+                    }
+                }
+                let mut edges_deleted = HashMap::new();
+                for (from, tos) in deleted_edges.iter() {
+                    let tos = tos.extract::<Vec<$py_id>>()?;
+                    if !tos.is_empty() {
+                        let tos = tos.into_iter().map(|to| to.0).collect::<HashSet<_>>();
+                        edges_deleted.insert(from.extract::<$py_id>()?.0, tos);
+                    }
+                }
+
+                let diff = GraphDiff::try_from_diffs(
+                    NodeDiff::new(nodes, deleted),
+                    EdgeDiff::new(edges, edges_deleted),
+                )
+                .map_err(|e| PyValueError::new_err(e.to_string()))?;
+                Ok($name::from(diff))
+            }
+
+            fn add_edge(&mut self, from: $py_id, to: $py_id, weight: $w) -> PyResult<()> {
+                let (from, to) = (self.id(from)?, self.id(to)?);
+                let _ = self.diff.add_edge(&from, &to, weight);
+                self.edge_version += 1;
+                Ok(())
+            }
+
+            /// Add edges in batch from a nested dict of `from` ids to `to` ids to weight.
+            fn add_edges(&mut self, edges: &Bound<'_, PyDict>) -> PyResult<()> {
+                let mut parsed = Vec::new();
+                for (from, tos) in edges.iter() {
+                    let from = self.id(from.extract()?)?;
+                    for (to, weight) in tos.downcast::<PyDict>()?.iter() {
+                        parsed.push((from, self.id(to.extract()?)?, weight.extract::<$w>()?));
+                    }
+                }
+                for (from, to, weight) in parsed {
+                    let _ = self.diff.add_edge(&from, &to, weight);
+                }
+                self.edge_version += 1;
+                Ok(())
+            }
+
+            fn delete_edge(&mut self, from: $py_id, to: $py_id) -> PyResult<()> {
+                let (from, to) = (self.id(from)?, self.id(to)?);
+                self.diff.delete_edge(&from, &to);
+                self.edge_version += 1;
+                Ok(())
+            }
+
+            fn clear(&mut self) {
+                self.diff.clear();
+                self.edge_version += 1;
+            }
+
+            fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+                let bytes = graph_diff_to_bytes_tagged(&self.diff)
+                    .map_err(|_| PyException::new_err("Failed to serialize graph diff."))?;
+                Ok(PyBytes::new_bound(py, &bytes))
+            }
+
+            /// Serialize the diff to JSON. Uuids are written as hyphenated strings.
+            fn to_json(&self) -> PyResult<String> {
+                graph_diff_to_json(&self.diff).map_err(|e| PyException::new_err(e.to_string()))
+            }
+
+            /// Load a diff from JSON written by `to_json`.
+            #[staticmethod]
+            fn from_json(json: &str) -> PyResult<$name> {
+                let graph_diff =
+                    graph_diff_from_json(json).map_err(|e| PyException::new_err(e.to_string()))?;
+                Ok($name::from(graph_diff))
+            }
+
+            /// Check that the diff is internally consistent and serialize it, raises a
+            /// `ValueError` if an edge references a deleted node.
+            fn finalize<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+                check_consistent(&self.diff)?;
+                self.to_bytes(py)
+            }
+
+            /// Context manager support, `with PyGraphDiff() as diff:` finalizes the diff built
+            /// in the block on exit and keeps its bytes as `result`, see `finalize`.
+            fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+                slf
+            }
+
+            fn __exit__(
+                &mut self,
+                py: Python<'_>,
+                exc_type: Option<&Bound<'_, PyAny>>,
+                _exc_value: Option<&Bound<'_, PyAny>>,
+                _traceback: Option<&Bound<'_, PyAny>>,
+            ) -> PyResult<bool> {
+                self.result = None;
+                if exc_type.is_none() {
+                    self.result = Some(self.finalize(py)?.unbind());
+                }
+                Ok(false)
+            }
+
+            /// The bytes of the diff as of the end of the last `with` block, or `None` if it
+            /// has not been used as a context manager or the block raised.
+            #[getter]
+            fn result(&self, py: Python<'_>) -> Option<Py<PyBytes>> {
+                self.result.as_ref().map(|bytes| bytes.clone_ref(py))
+            }
+
+            /// Pickle support, a diff is pickled to its bytes.
+            fn __reduce__<'py>(
+                slf: &Bound<'py, Self>,
+            ) -> PyResult<(Bound<'py, PyAny>, (Bound<'py, PyBytes>,))> {
+                let from_bytes = slf.get_type().getattr("from_bytes")?;
+                let bytes = slf.borrow().to_bytes(slf.py())?;
+                Ok((from_bytes, (bytes,)))
+            }
+
+            /// Load a diff from CSV text of nodes and edges as exported by dRISK Edge.
+            #[staticmethod]
+            fn from_csv(nodes_csv: &str, edges_csv: &str) -> PyResult<$name> {
+                Ok($name::from(graph_diff_from_csv(nodes_csv, edges_csv)?))
+            }
+
+            /// Export the diff as `(nodes_csv, edges_csv)`, readable by `from_csv`.
+            ///
+            /// Nodes have the dRISK Edge columns followed by `color_alpha`, `show_label`,
+            /// `extra` as a JSON object and `cleared` as `;` separated keys. Deleted nodes and
+            /// edges have `deleted` in the trailing `status` column.
+            fn to_csv(&self) -> PyResult<(String, String)> {
+                graph_diff_to_csv(&self.diff)
+            }
+
+            #[staticmethod]
+            fn from_bytes(bytes: &Bound<'_, PyBytes>) -> PyResult<$name> {
+                let graph_diff =
+                    bytes_to_graph_diff_tagged(bytes.as_bytes()).map_err(|e| {
+                        PyException::new_err(format!("Failed to deserialize graph diff: {}", e))
+                    })?;
+                Ok($name::from(graph_diff))
+            }
+
+            $($($methods)*)?
+        }
+
+        /// Iterator over the edges of a diff.
+        ///
+        /// Holds the diff and the position of the next edge in its edge map. Edges are copied
+        /// `EDGE_ITER_CHUNK` at a time and Python objects only created for one edge at a time,
+        /// so memory use does not grow with the diff.
+        #[pyclass]
+        pub struct $iter {
+            diff: Py<$name>,
+            /// `edge_version` of the diff when the iterator was created.
+            edge_version: u64,
+            /// Index of the current `from` node in the edge map and of the next edge from it.
+            position: (usize, usize),
+            chunk: std::vec::IntoIter<($id, $id, $w)>,
+        }
+
+        #[pymethods]
+        impl $iter {
+            fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+                slf
+            }
+
+            fn __next__<'py>(
+                mut slf: PyRefMut<'_, Self>,
+                py: Python<'py>,
+            ) -> PyResult<Option<(PyObject, PyObject, $w)>> {
+                let diff = slf.diff.clone_ref(py);
+                let diff = diff.borrow(py);
+                if diff.edge_version != slf.edge_version {
+                    return Err(PyRuntimeError::new_err(
+                        "graph diff edges changed during iteration",
+                    ));
+                }
+                if slf.chunk.len() == 0 {
+                    let (mut outer, mut inner) = slf.position;
+                    let mut chunk = Vec::with_capacity(EDGE_ITER_CHUNK);
+                    let edges = diff.diff.new_or_updated_edges().iter();
+                    'fill: for (from, to_weight) in edges.skip(outer) {
+                        for (to, weight) in to_weight.iter().skip(inner) {
+                            if chunk.len() == EDGE_ITER_CHUNK {
+                                break 'fill;
+                            }
+                            chunk.push((*from, *to, *weight));
+                            inner += 1;
+                        }
+                        outer += 1;
+                        inner = 0;
+                    }
+                    slf.position = (outer, inner);
+                    slf.chunk = chunk.into_iter();
+                }
+                Ok(slf
+                    .chunk
+                    .next()
+                    .map(|(from, to, weight)| (from.item(py), to.item(py), weight)))
+            }
+        }
+    };
+}
+
+/// Raise a `ValueError` if an edge of the diff references a deleted node.
+fn check_consistent<Id: Copy + Eq + std::hash::Hash, W: Copy + PartialEq>(
+    diff: &GraphDiff<Id, NodeUpdate, W>,
+) -> PyResult<()> {
+    if diff.is_internally_consistent() {
+        Ok(())
+    } else {
+        Err(PyValueError::new_err(
+            "Graph diff is inconsistent, an edge references a deleted node.",
+        ))
     }
+}
 
-    fn clear(&mut self) {
-        self.0.clear();
+/// The new or updated nodes of a diff keyed by `uuid.UUID` objects.
+fn uuid_keyed_nodes<'a>(
+    py: Python<'a>,
+    nodes: &HashMap<Uuid, NodeUpdate>,
+) -> PyResult<Bound<'a, PyDict>> {
+    let uuid = py.import_bound("uuid")?.getattr("UUID")?;
+    let dict = PyDict::new_bound(py);
+    for (id, node) in nodes {
+        let kwargs = PyDict::new_bound(py);
+        kwargs.set_item("bytes", PyBytes::new_bound(py, id.as_bytes()))?;
+        dict.set_item(uuid.call((), Some(&kwargs))?, node.to_object(py))?;
     }
+    PyResult::Ok(dict)
+}
 
-    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
-        let bytes = graph_diff_to_bytes(&self.0)
+py_graph_diff!(PyGraphDiff, PyEdgeIter, Uuid, PyUuid, f32, {
+    /// Serialize the diff to the headerless byte format of drisk_api 0.0.9, which the dRISK
+    /// Edge server reads. The format has no header, so it is read back by `from_bytes`
+    /// without checks of the id and weight types.
+    fn to_legacy_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let bytes = graph_diff_to_bytes_legacy(&self.diff)
             .map_err(|_| PyException::new_err("Failed to serialize graph diff."))?;
         Ok(PyBytes::new_bound(py, &bytes))
     }
 
-    #[staticmethod]
-    fn from_bytes(bytes: &Bound<'_, PyBytes>) -> PyResult<PyGraphDiff> {
-        let graph_diff = crate::bytes::bytes_to_graph_diff(bytes.as_bytes())
-            .map_err(|_| PyException::new_err("Failed to deserialize graph diff."))?;
-        Ok(PyGraphDiff(graph_diff))
+    /// Like `new_or_updated_nodes`, but keyed by `uuid.UUID` objects built from the id
+    /// bytes instead of strings.
+    fn new_or_updated_nodes_uuid<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+        uuid_keyed_nodes(py, self.diff.new_or_updated_nodes())
     }
-}
+});
+py_graph_diff!(PyGraphDiffF64, PyEdgeIterF64, Uuid, PyUuid, f64, {
+    /// Like `new_or_updated_nodes`, but keyed by `uuid.UUID` objects built from the id
+    /// bytes instead of strings.
+    fn new_or_updated_nodes_uuid<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+        uuid_keyed_nodes(py, self.diff.new_or_updated_nodes())
+    }
+});
+py_graph_diff!(PyGraphDiffU64, PyEdgeIterU64, u64, PyU64, f32);
 
 #[pymodule]
 pub fn drisk_api(_py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyGraphDiff>()?;
+    m.add_class::<PyEdgeIter>()?;
+    m.add_class::<PyGraphDiffF64>()?;
+    m.add_class::<PyEdgeIterF64>()?;
+    m.add_class::<PyGraphDiffU64>()?;
+    m.add_class::<PyEdgeIterU64>()?;
     Ok(())
 }
@@ -2,20 +2,62 @@
 //!
 //! A graph diff is a delta between two graphs. This module provides:
 //! * a generic graph diff implentation,
-//! * a serialization/deserialization API,
+//! * a serialization/deserialization API to bytes and JSON,
 //! * specific types for the dRISK API,
 //! * and a Python extension module for the dRISK API.
 //!
 //! See the documentation for `GraphDiff` for more information.
+//!
+//! Without the default `std` feature the crate is `no_std` and only provides the
+//! `GraphDiff` core, serialization and the dRISK types need `std`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub use crate::{
+    builder::GraphDiffBuilder,
+    diff::{
+        ApplyPlan, CompactReport, Conflict, Conflicts, DiffMeta, DiffStats, EdgeDiff, GraphDiff,
+        GraphDiffDelta, Intersect, Invert, LintWarning, NodeDiff, Resolution, ReverseIndex,
+        SymmetricDifference, ValidationIssue,
+    },
+    error::GraphDiffError,
+};
+
+#[cfg(feature = "std")]
 pub use crate::{
-    bytes::{bytes_to_graph_diff, graph_diff_to_bytes},
-    diff::{EdgeDiff, GraphDiff, NodeDiff},
-    node_update::NodeUpdate,
+    bytes::{
+        bytes_to_graph_diff, bytes_to_graph_diff_compact_ids, bytes_to_graph_diff_tagged,
+        from_bytes_wasm, graph_diff_from_chunks, graph_diff_from_json, graph_diff_to_bytes,
+        graph_diff_to_bytes_canonical, graph_diff_to_bytes_compact_ids, graph_diff_to_bytes_legacy,
+        graph_diff_to_bytes_sorted_deletions, graph_diff_to_bytes_tagged, graph_diff_to_chunks,
+        graph_diff_to_json, graph_diff_to_writer, to_bytes_wasm, CompactId, FormatError, TypeTag,
+        WasmError, COMPACT_ID_MAGIC, FORMAT_VERSION, MAGIC,
+    },
+    node_update::{ColorError, NodeField, NodeUpdate, NodeValueError},
+};
+
+#[cfg(feature = "parallel")]
+pub use crate::bytes::graph_diff_to_bytes_parallel;
+
+#[cfg(feature = "tokio")]
+pub use crate::bytes::{graph_diff_from_async_reader, graph_diff_to_async_writer};
+
+#[cfg(feature = "compression")]
+pub use crate::bytes::{
+    bytes_to_graph_diff_compressed, graph_diff_to_bytes_compressed, COMPRESSED_MAGIC,
 };
 
+mod builder;
+#[cfg(feature = "std")]
 mod bytes;
 mod diff;
+mod error;
+#[cfg(feature = "std")]
 mod node_update;
+mod order;
+#[cfg(feature = "petgraph")]
+mod petgraph;
 
 #[cfg(feature = "extension-module")]
 mod extension;
@@ -1,8 +1,68 @@
-use serde::{Deserialize, Serialize};
+use crate::diff::{float_bits, Conflicts, GraphDiff, HashMap, HashSet, Intersect, Invert};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+/// A property of a `NodeUpdate`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum NodeField {
+    Label,
+    Size,
+    Url,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    ShowLabel,
+}
+
+impl NodeField {
+    /// All fields of a `NodeUpdate`.
+    pub const ALL: [NodeField; 8] = [
+        NodeField::Label,
+        NodeField::Size,
+        NodeField::Url,
+        NodeField::Red,
+        NodeField::Green,
+        NodeField::Blue,
+        NodeField::Alpha,
+        NodeField::ShowLabel,
+    ];
+
+    /// The name of the field in the JSON format.
+    fn json_key(self) -> &'static str {
+        match self {
+            NodeField::Label => "label",
+            NodeField::Size => "size",
+            NodeField::Url => "url",
+            NodeField::Red => "red",
+            NodeField::Green => "green",
+            NodeField::Blue => "blue",
+            NodeField::Alpha => "alpha",
+            NodeField::ShowLabel => "showLabel",
+        }
+    }
+}
 
 /// Update type for the dRISK API.
+///
+/// A field set to `None` is left unchanged by the update. To unset a property, mark the
+/// field as cleared with `NodeUpdate::clear`; clears survive composition and serialization.
+///
+/// Custom properties beyond the fixed fields go in `extra`, keyed by property name.
+///
+/// Human-readable formats such as JSON get an object with the set fields only. Other formats
+/// such as bincode cannot represent skipped fields or arbitrary JSON values, and get a
+/// sequence of the set fields instead, each tagged with its field, with `extra` as a tree of
+/// tagged values. A reader fails on tags added by later versions rather than misreading them.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", remote = "Self")]
 pub struct NodeUpdate {
     #[cfg_attr(
         not(feature = "no-skip-if"),
@@ -38,11 +98,396 @@ pub struct NodeUpdate {
         not(feature = "no-skip-if"),
         serde(skip_serializing_if = "Option::is_none")
     )]
+    pub alpha: Option<u8>,
+    #[cfg_attr(
+        not(feature = "no-skip-if"),
+        serde(skip_serializing_if = "Option::is_none")
+    )]
     pub show_label: Option<bool>,
+    #[cfg_attr(
+        not(feature = "no-skip-if"),
+        serde(skip_serializing_if = "HashSet::is_empty")
+    )]
+    #[serde(default)]
+    pub cleared: HashSet<NodeField>,
+    #[cfg_attr(
+        not(feature = "no-skip-if"),
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub extra: Option<HashMap<String, Value>>,
+}
+
+/// Error returned by `NodeUpdate::set_color_hex` for an invalid hex color.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ColorError {
+    /// The color does not have 6 or 8 hex digits. Holds the number of digits found.
+    InvalidLength(usize),
+    /// The color contains a character that is not a hex digit.
+    InvalidDigit,
+}
+
+impl fmt::Display for ColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorError::InvalidLength(len) => {
+                write!(f, "expected 6 or 8 hex digits, found {}", len)
+            }
+            ColorError::InvalidDigit => write!(f, "color contains a non-hex character"),
+        }
+    }
+}
+
+impl std::error::Error for ColorError {}
+
+/// Error returned by `NodeUpdate::from_json_value` for a value that is not a node update.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NodeValueError {
+    /// The value is not a JSON object.
+    NotAnObject,
+    /// The value of a field has the wrong type or is out of range.
+    InvalidValue(NodeField),
+    /// A key is not a field, with strict parsing.
+    UnknownKey(String),
+}
+
+impl fmt::Display for NodeValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeValueError::NotAnObject => write!(f, "node update must be a JSON object"),
+            NodeValueError::InvalidValue(field) => write!(f, "invalid value for {:?}", field),
+            NodeValueError::UnknownKey(key) => write!(f, "unrecognized node update key {}", key),
+        }
+    }
+}
+
+impl std::error::Error for NodeValueError {}
+
+/// A set field of a `NodeUpdate`, for non-human-readable formats. New variants go at the end.
+#[derive(Serialize, Deserialize)]
+enum CompactField<'a> {
+    Label(Cow<'a, str>),
+    Size(f32),
+    Url(Cow<'a, str>),
+    Red(u8),
+    Green(u8),
+    Blue(u8),
+    Alpha(u8),
+    ShowLabel(bool),
+    Cleared(BTreeSet<NodeField>),
+    Extra(BTreeMap<Cow<'a, str>, CompactValue<'a>>),
+}
+
+/// A JSON value of an extra property, for non-human-readable formats.
+#[derive(Serialize, Deserialize)]
+enum CompactValue<'a> {
+    Null,
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    String(Cow<'a, str>),
+    Array(Vec<CompactValue<'a>>),
+    Object(BTreeMap<Cow<'a, str>, CompactValue<'a>>),
+}
+
+impl<'a> From<&'a Value> for CompactValue<'a> {
+    fn from(value: &'a Value) -> Self {
+        match value {
+            Value::Null => CompactValue::Null,
+            Value::Bool(b) => CompactValue::Bool(*b),
+            Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(n), _) => CompactValue::U64(n),
+                (None, Some(n)) => CompactValue::I64(n),
+                _ => CompactValue::F64(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(s) => CompactValue::String(Cow::Borrowed(s)),
+            Value::Array(values) => CompactValue::Array(values.iter().map(Into::into).collect()),
+            Value::Object(entries) => CompactValue::Object(compact_entries(entries)),
+        }
+    }
+}
+
+impl From<CompactValue<'_>> for Value {
+    fn from(value: CompactValue<'_>) -> Self {
+        match value {
+            CompactValue::Null => Value::Null,
+            CompactValue::Bool(b) => Value::Bool(b),
+            CompactValue::U64(n) => n.into(),
+            CompactValue::I64(n) => n.into(),
+            // non-finite numbers have no JSON representation
+            CompactValue::F64(n) => serde_json::Number::from_f64(n).map_or(Value::Null, Into::into),
+            CompactValue::String(s) => Value::String(s.into_owned()),
+            CompactValue::Array(values) => {
+                Value::Array(values.into_iter().map(Into::into).collect())
+            }
+            CompactValue::Object(entries) => Value::Object(
+                (entries.into_iter())
+                    .map(|(key, value)| (key.into_owned(), value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Sorted, so equal updates serialize to equal bytes.
+fn compact_entries<'a>(
+    entries: impl IntoIterator<Item = (&'a String, &'a Value)>,
+) -> BTreeMap<Cow<'a, str>, CompactValue<'a>> {
+    (entries.into_iter())
+        .map(|(key, value)| (Cow::Borrowed(key.as_str()), value.into()))
+        .collect()
+}
+
+impl Serialize for NodeUpdate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return NodeUpdate::serialize(self, serializer);
+        }
+        let fields = [
+            (self.label.as_deref()).map(|label| CompactField::Label(label.into())),
+            self.size.map(CompactField::Size),
+            (self.url.as_deref()).map(|url| CompactField::Url(url.into())),
+            self.red.map(CompactField::Red),
+            self.green.map(CompactField::Green),
+            self.blue.map(CompactField::Blue),
+            self.alpha.map(CompactField::Alpha),
+            self.show_label.map(CompactField::ShowLabel),
+            (!self.cleared.is_empty())
+                .then(|| CompactField::Cleared(self.cleared.iter().copied().collect())),
+            (self.extra.as_ref()).map(|extra| CompactField::Extra(compact_entries(extra))),
+        ];
+        // collected, bincode needs the length up front
+        let fields: Vec<_> = fields.into_iter().flatten().collect();
+        fields.serialize(serializer)
+    }
+}
+
+/// Hashes `size` by its bit pattern with `-0.0` and NaN normalized, and `cleared` and
+/// `extra` in sorted order, consistently with `PartialEq`.
+impl Hash for NodeUpdate {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.label.hash(state);
+        self.size.map(|size| float_bits(size.into())).hash(state);
+        self.url.hash(state);
+        self.red.hash(state);
+        self.green.hash(state);
+        self.blue.hash(state);
+        self.alpha.hash(state);
+        self.show_label.hash(state);
+        self.cleared.iter().collect::<BTreeSet<_>>().hash(state);
+        self.extra
+            .as_ref()
+            .map(|extra| extra.iter().collect::<BTreeMap<_, _>>())
+            .hash(state);
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeUpdate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            return NodeUpdate::deserialize(deserializer);
+        }
+        let mut update = NodeUpdate::default();
+        for field in Vec::<CompactField>::deserialize(deserializer)? {
+            match field {
+                CompactField::Label(label) => update.label = Some(label.into_owned()),
+                CompactField::Size(size) => update.size = Some(size),
+                CompactField::Url(url) => update.url = Some(url.into_owned()),
+                CompactField::Red(red) => update.red = Some(red),
+                CompactField::Green(green) => update.green = Some(green),
+                CompactField::Blue(blue) => update.blue = Some(blue),
+                CompactField::Alpha(alpha) => update.alpha = Some(alpha),
+                CompactField::ShowLabel(show_label) => update.show_label = Some(show_label),
+                CompactField::Cleared(cleared) => update.cleared = cleared.into_iter().collect(),
+                CompactField::Extra(extra) => {
+                    let extra = extra.into_iter();
+                    let extra = extra.map(|(key, value)| (key.into_owned(), value.into()));
+                    update.extra = Some(extra.collect());
+                }
+            }
+        }
+        Ok(update)
+    }
+}
+
+impl NodeUpdate {
+    /// Returns `true` if the update neither sets nor clears any field or extra property.
+    pub fn is_empty(&self) -> bool {
+        self.cleared.is_empty()
+            && NodeField::ALL.iter().all(|field| !self.is_set(*field))
+            && self.extra.as_ref().is_none_or(|extra| extra.is_empty())
+    }
+
+    /// Mark a field as cleared, unsetting any value for it.
+    pub fn clear(&mut self, field: NodeField) {
+        match field {
+            NodeField::Label => self.label = None,
+            NodeField::Size => self.size = None,
+            NodeField::Url => self.url = None,
+            NodeField::Red => self.red = None,
+            NodeField::Green => self.green = None,
+            NodeField::Blue => self.blue = None,
+            NodeField::Alpha => self.alpha = None,
+            NodeField::ShowLabel => self.show_label = None,
+        }
+        self.cleared.insert(field);
+    }
+
+    /// Returns `true` if the update sets a value for the field.
+    pub fn is_set(&self, field: NodeField) -> bool {
+        match field {
+            NodeField::Label => self.label.is_some(),
+            NodeField::Size => self.size.is_some(),
+            NodeField::Url => self.url.is_some(),
+            NodeField::Red => self.red.is_some(),
+            NodeField::Green => self.green.is_some(),
+            NodeField::Blue => self.blue.is_some(),
+            NodeField::Alpha => self.alpha.is_some(),
+            NodeField::ShowLabel => self.show_label.is_some(),
+        }
+    }
+
+    /// Returns `true` if the update clears the field.
+    pub fn is_cleared(&self, field: NodeField) -> bool {
+        self.cleared.contains(&field)
+    }
+
+    /// Set `red`, `green` and `blue` from a `#RRGGBB` hex string, and `alpha` as well from
+    /// `#RRGGBBAA`. A `#RRGGBB` string unsets `alpha`, so that `color_hex` gives it back.
+    /// The `#` is optional.
+    ///
+    /// Leaves the update unchanged if the string is not a valid color.
+    pub fn set_color_hex(&mut self, hex: &str) -> Result<(), ColorError> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ColorError::InvalidDigit);
+        }
+        if digits.len() != 6 && digits.len() != 8 {
+            return Err(ColorError::InvalidLength(digits.len()));
+        }
+        let channel = |i: usize| u8::from_str_radix(&digits[2 * i..2 * i + 2], 16).ok();
+        self.red = channel(0);
+        self.green = channel(1);
+        self.blue = channel(2);
+        self.alpha = if digits.len() == 8 { channel(3) } else { None };
+        Ok(())
+    }
+
+    /// The color as a lowercase `#rrggbb` hex string, or `#rrggbbaa` if `alpha` is set.
+    /// Returns `None` unless `red`, `green` and `blue` are all set.
+    pub fn color_hex(&self) -> Option<String> {
+        let hex = format!("#{:02x}{:02x}{:02x}", self.red?, self.green?, self.blue?);
+        Some(match self.alpha {
+            Some(alpha) => format!("{}{:02x}", hex, alpha),
+            None => hex,
+        })
+    }
+
+    /// Build an update from a JSON object of node properties, keyed by the field names of
+    /// the JSON format such as `showLabel`.
+    ///
+    /// A `null` value clears the field. Other keys are kept as extra properties, or rejected
+    /// with `NodeValueError::UnknownKey` if `strict` is set.
+    pub fn from_json_value(value: &Value, strict: bool) -> Result<NodeUpdate, NodeValueError> {
+        let Value::Object(object) = value else {
+            return Err(NodeValueError::NotAnObject);
+        };
+        let mut update = NodeUpdate::default();
+        let mut extra = HashMap::new();
+        for (key, value) in object {
+            let Some(field) = NodeField::ALL.into_iter().find(|f| f.json_key() == key) else {
+                if strict {
+                    return Err(NodeValueError::UnknownKey(key.clone()));
+                }
+                extra.insert(key.clone(), value.clone());
+                continue;
+            };
+            if value.is_null() {
+                update.clear(field);
+                continue;
+            }
+            let invalid = || NodeValueError::InvalidValue(field);
+            let string = || value.as_str().map(str::to_owned).ok_or_else(invalid);
+            let channel = || {
+                value
+                    .as_u64()
+                    .and_then(|value| u8::try_from(value).ok())
+                    .ok_or_else(invalid)
+            };
+            match field {
+                NodeField::Label => update.label = Some(string()?),
+                NodeField::Url => update.url = Some(string()?),
+                NodeField::Size => {
+                    let size = value.as_f64().filter(|size| *size >= 0.0);
+                    update.size = Some(size.ok_or_else(invalid)? as f32);
+                }
+                NodeField::Red => update.red = Some(channel()?),
+                NodeField::Green => update.green = Some(channel()?),
+                NodeField::Blue => update.blue = Some(channel()?),
+                NodeField::Alpha => update.alpha = Some(channel()?),
+                NodeField::ShowLabel => {
+                    update.show_label = Some(value.as_bool().ok_or_else(invalid)?)
+                }
+            }
+        }
+        update.extra = (!extra.is_empty()).then_some(extra);
+        Ok(update)
+    }
+
+    /// Replace every field, clear and extra property of `self` with those of `other`.
+    ///
+    /// Unlike `+=`, fields that `other` leaves as `None` end up `None` in `self`. An unset
+    /// field still leaves the node's value unchanged when the update is applied, use
+    /// `clear` to unset it.
+    pub fn overwrite_from(&mut self, other: NodeUpdate) {
+        *self = other;
+    }
+
+    /// Copy the value of a field, or its clear, from another update.
+    fn copy_field(&mut self, other: &NodeUpdate, field: NodeField) {
+        if other.is_cleared(field) {
+            self.cleared.insert(field);
+        }
+        match field {
+            NodeField::Label => self.label = other.label.clone(),
+            NodeField::Size => self.size = other.size,
+            NodeField::Url => self.url = other.url.clone(),
+            NodeField::Red => self.red = other.red,
+            NodeField::Green => self.green = other.green,
+            NodeField::Blue => self.blue = other.blue,
+            NodeField::Alpha => self.alpha = other.alpha,
+            NodeField::ShowLabel => self.show_label = other.show_label,
+        }
+    }
+
+    /// Returns `true` if both updates have the same value for the field.
+    fn field_eq(&self, other: &NodeUpdate, field: NodeField) -> bool {
+        match field {
+            NodeField::Label => self.label == other.label,
+            NodeField::Size => self.size == other.size,
+            NodeField::Url => self.url == other.url,
+            NodeField::Red => self.red == other.red,
+            NodeField::Green => self.green == other.green,
+            NodeField::Blue => self.blue == other.blue,
+            NodeField::Alpha => self.alpha == other.alpha,
+            NodeField::ShowLabel => self.show_label == other.show_label,
+        }
+    }
 }
 
+/// Fields set by `other` overwrite those of `self` and fields cleared by `other` are unset.
+/// If `other` both sets and clears a field, the value wins. Extra properties are merged key
+/// by key with the values of `other` winning.
 impl std::ops::AddAssign for NodeUpdate {
     fn add_assign(&mut self, other: NodeUpdate) {
+        if let Some(extra) = other.extra {
+            self.extra
+                .get_or_insert_with(HashMap::default)
+                .extend(extra);
+        }
+        for field in other.cleared {
+            self.clear(field);
+        }
         if let Some(label) = other.label {
             self.label = Some(label);
         }
@@ -61,8 +506,360 @@ impl std::ops::AddAssign for NodeUpdate {
         if let Some(blue) = other.blue {
             self.blue = Some(blue);
         }
+        if let Some(alpha) = other.alpha {
+            self.alpha = Some(alpha);
+        }
         if let Some(show_label) = other.show_label {
             self.show_label = Some(show_label);
         }
+        let cleared = std::mem::take(&mut self.cleared);
+        self.cleared = cleared.into_iter().filter(|f| !self.is_set(*f)).collect();
+    }
+}
+
+/// Reverts each field set or cleared by `self` to its value in `base`.
+///
+/// Fields that are unset in `base` are cleared by the inverse. Extra properties cannot be
+/// cleared, so those missing from `base` revert to `null`.
+impl Invert for NodeUpdate {
+    fn invert(&self, base: &NodeUpdate) -> NodeUpdate {
+        let mut inverse = NodeUpdate::default();
+        if let Some(extra) = &self.extra {
+            let base_extra = base.extra.as_ref();
+            let reverted = extra.keys().map(|key| {
+                let value = base_extra.and_then(|e| e.get(key)).cloned();
+                (key.clone(), value.unwrap_or(Value::Null))
+            });
+            inverse.extra = Some(reverted.collect());
+        }
+        for field in NodeField::ALL {
+            if !self.is_set(field) && !self.is_cleared(field) {
+                continue;
+            }
+            if base.is_set(field) {
+                inverse.copy_field(base, field);
+            } else {
+                inverse.clear(field);
+            }
+        }
+        inverse
+    }
+}
+
+/// Fields or extra properties conflict if both updates set or clear them, to different values.
+impl Conflicts for NodeUpdate {
+    fn conflict(&self, other: &NodeUpdate) -> Option<(NodeUpdate, NodeUpdate)> {
+        let touches = |update: &NodeUpdate, field| update.is_set(field) || update.is_cleared(field);
+        let mut conflict: Option<(NodeUpdate, NodeUpdate)> = None;
+        for (key, value) in self.extra.iter().flatten() {
+            let other_value = other.extra.as_ref().and_then(|e| e.get(key));
+            if let Some(other_value) = other_value.filter(|v| *v != value) {
+                let (mine, theirs) = conflict.get_or_insert_with(Default::default);
+                let mine = mine.extra.get_or_insert_with(HashMap::default);
+                mine.insert(key.clone(), value.clone());
+                let theirs = theirs.extra.get_or_insert_with(HashMap::default);
+                theirs.insert(key.clone(), other_value.clone());
+            }
+        }
+        for field in NodeField::ALL {
+            if touches(self, field) && touches(other, field) && !self.field_eq(other, field) {
+                let (mine, theirs) = conflict.get_or_insert_with(Default::default);
+                mine.copy_field(self, field);
+                theirs.copy_field(other, field);
+            }
+        }
+        conflict
+    }
+}
+
+/// Keeps the fields both updates set to the same value or both clear, and the extra
+/// properties both set to the same value.
+impl Intersect for NodeUpdate {
+    fn intersect(&self, other: &NodeUpdate) -> NodeUpdate {
+        let mut common = NodeUpdate::default();
+        for field in NodeField::ALL {
+            let both_cleared = self.is_cleared(field) && other.is_cleared(field);
+            let both_set = self.is_set(field) && self.field_eq(other, field);
+            if both_cleared || both_set {
+                common.copy_field(self, field);
+            }
+        }
+        let extra: HashMap<_, _> = (self.extra.iter().flatten())
+            .filter(|(key, value)| other.extra.as_ref().and_then(|e| e.get(*key)) == Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        common.extra = (!extra.is_empty()).then_some(extra);
+        common
+    }
+}
+
+impl<Id: Copy + Eq + Hash, W: Copy + PartialEq> GraphDiff<Id, NodeUpdate, W> {
+    /// Set the label of each node in `labels`, adding nodes that are not new or updated yet
+    /// with an empty update as `get_or_create_mut_node_update` does.
+    pub fn apply_labels(&mut self, labels: &HashMap<Id, String>) {
+        for (id, label) in labels {
+            let update = self.get_or_create_mut_node_update(id);
+            update.label = Some(label.clone());
+            update.cleared.remove(&NodeField::Label);
+        }
+    }
+
+    /// Iterate over the new or updated nodes whose update sets a value for `field`.
+    pub fn nodes_with_field(&self, field: NodeField) -> impl Iterator<Item = (&Id, &NodeUpdate)> {
+        self.iter_new_or_updated_nodes()
+            .filter(move |(_, update)| update.is_set(field))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_is_empty() {
+        assert!(NodeUpdate::default().is_empty());
+
+        let update = NodeUpdate {
+            show_label: Some(false),
+            ..NodeUpdate::default()
+        };
+        assert!(!update.is_empty());
+
+        let mut update = NodeUpdate::default();
+        update.clear(NodeField::Label);
+        assert!(!update.is_empty());
+    }
+
+    #[test]
+    fn test_color_hex() {
+        let mut update = NodeUpdate::default();
+        assert_eq!(update.color_hex(), None);
+
+        update.set_color_hex("#FF8000").unwrap();
+        assert_eq!(
+            (update.red, update.green, update.blue),
+            (Some(255), Some(128), Some(0))
+        );
+        assert_eq!(update.alpha, None);
+        assert_eq!(update.color_hex().as_deref(), Some("#ff8000"));
+
+        update.set_color_hex("0a0b0c80").unwrap();
+        assert_eq!(update.alpha, Some(128));
+        assert_eq!(update.color_hex().as_deref(), Some("#0a0b0c80"));
+
+        assert_eq!(
+            update.set_color_hex("#fff"),
+            Err(ColorError::InvalidLength(3))
+        );
+        assert_eq!(
+            update.set_color_hex("#ff80zz"),
+            Err(ColorError::InvalidDigit)
+        );
+        assert_eq!(
+            update.set_color_hex("#ff80é"),
+            Err(ColorError::InvalidDigit)
+        );
+        assert_eq!(update.color_hex().as_deref(), Some("#0a0b0c80"));
+
+        // without alpha digits the alpha of a previous color is unset
+        update.set_color_hex("#0a0b0c").unwrap();
+        assert_eq!(update.alpha, None);
+        assert_eq!(update.color_hex().as_deref(), Some("#0a0b0c"));
+    }
+
+    #[test]
+    fn test_from_json_value() {
+        let full = json!({
+            "label": "a",
+            "size": 2.5,
+            "url": "https://example.com",
+            "red": 1,
+            "green": 2,
+            "blue": 3,
+            "alpha": 4,
+            "showLabel": true,
+            "weight": [1, 2],
+        });
+        let update = NodeUpdate::from_json_value(&full, false).unwrap();
+        assert_eq!(
+            update,
+            NodeUpdate {
+                label: Some("a".to_string()),
+                size: Some(2.5),
+                url: Some("https://example.com".to_string()),
+                red: Some(1),
+                green: Some(2),
+                blue: Some(3),
+                alpha: Some(4),
+                show_label: Some(true),
+                extra: Some(HashMap::from([("weight".to_string(), json!([1, 2]))])),
+                ..NodeUpdate::default()
+            }
+        );
+        assert_eq!(
+            NodeUpdate::from_json_value(&full, true),
+            Err(NodeValueError::UnknownKey("weight".to_string()))
+        );
+
+        let partial = NodeUpdate::from_json_value(&json!({"size": 1, "url": null}), true).unwrap();
+        assert_eq!(partial.size, Some(1.0));
+        assert!(partial.is_cleared(NodeField::Url));
+        assert!(!partial.is_set(NodeField::Label));
+
+        for (value, field) in [
+            (json!({"label": 1}), NodeField::Label),
+            (json!({"size": -1.0}), NodeField::Size),
+            (json!({"red": 256}), NodeField::Red),
+            (json!({"showLabel": "yes"}), NodeField::ShowLabel),
+        ] {
+            assert_eq!(
+                NodeUpdate::from_json_value(&value, false),
+                Err(NodeValueError::InvalidValue(field))
+            );
+        }
+        assert_eq!(
+            NodeUpdate::from_json_value(&json!([1]), false),
+            Err(NodeValueError::NotAnObject)
+        );
+    }
+
+    #[test]
+    fn test_overwrite_from() {
+        let base = NodeUpdate {
+            label: Some("a".to_string()),
+            size: Some(1.0),
+            ..NodeUpdate::default()
+        };
+        let other = NodeUpdate {
+            size: Some(2.0),
+            ..NodeUpdate::default()
+        };
+
+        // the label left as `None` by `other` survives `+=`
+        let mut added = base.clone();
+        added += other.clone();
+        assert_eq!(added.label.as_deref(), Some("a"));
+        assert_eq!(added.size, Some(2.0));
+
+        // and is dropped by `overwrite_from`
+        let mut overwritten = base.clone();
+        overwritten.overwrite_from(other.clone());
+        assert_eq!(overwritten.label, None);
+        assert_eq!(overwritten, other);
+    }
+
+    #[test]
+    fn test_extra() {
+        let extra = |entries: &[(&str, Value)]| NodeUpdate {
+            extra: Some(
+                entries
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.clone()))
+                    .collect(),
+            ),
+            ..NodeUpdate::default()
+        };
+
+        // disjoint keys
+        let mut update = extra(&[("a", Value::from(1))]);
+        update += extra(&[("b", Value::from("x"))]);
+        assert_eq!(
+            update,
+            extra(&[("a", Value::from(1)), ("b", Value::from("x"))])
+        );
+
+        // overlapping keys, the right side wins
+        update += extra(&[("a", Value::from(2))]);
+        assert_eq!(
+            update,
+            extra(&[("a", Value::from(2)), ("b", Value::from("x"))])
+        );
+
+        // updates without extra leave it untouched
+        update += NodeUpdate::default();
+        assert_eq!(update.extra.as_ref().unwrap().len(), 2);
+        assert!(!update.is_empty());
+
+        let json = serde_json::to_string(&update).unwrap();
+        assert_eq!(serde_json::from_str::<NodeUpdate>(&json).unwrap(), update);
+    }
+
+    #[test]
+    fn test_compact_layout() {
+        let mut update = NodeUpdate {
+            label: Some("a".to_string()),
+            red: Some(1),
+            show_label: Some(false),
+            extra: Some(HashMap::from([
+                ("n".to_string(), json!([1, -2, 0.5, null])),
+                ("o".to_string(), json!({"k": "v", "b": true})),
+            ])),
+            ..NodeUpdate::default()
+        };
+        update.clear(NodeField::Url);
+        let bytes = bincode::serialize(&update).unwrap();
+        assert_eq!(bincode::deserialize::<NodeUpdate>(&bytes).unwrap(), update);
+
+        // only set fields are written, each with its tag
+        let empty = bincode::serialize(&NodeUpdate::default()).unwrap();
+        assert_eq!(empty, 0u64.to_le_bytes());
+        let size = NodeUpdate {
+            size: Some(2.0),
+            ..NodeUpdate::default()
+        };
+        let bytes = bincode::serialize(&size).unwrap();
+        assert_eq!(bytes.len(), 8 + 4 + 4);
+        assert_eq!(bytes[8..12], 1u32.to_le_bytes());
+
+        // a field tag of a later version is rejected rather than misread
+        let mut bytes = 1u64.to_le_bytes().to_vec();
+        bytes.extend(99u32.to_le_bytes());
+        assert!(bincode::deserialize::<NodeUpdate>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_apply_labels() {
+        let mut diff = GraphDiff::<u32, NodeUpdate>::new();
+        diff.add_or_update_node(
+            &1,
+            NodeUpdate {
+                size: Some(2.),
+                ..NodeUpdate::default()
+            },
+        );
+        diff.get_or_create_mut_node_update(&2)
+            .clear(NodeField::Label);
+
+        let labels = HashMap::from([(1, "one".into()), (2, "two".into()), (3, "three".into())]);
+        diff.apply_labels(&labels);
+        assert_eq!(diff.node_change_count(), 3);
+        for (id, label) in labels.iter() {
+            assert_eq!(diff.node_update(id).unwrap().label.as_ref(), Some(label));
+        }
+        assert_eq!(diff.node_update(&1).unwrap().size, Some(2.));
+        assert!(!diff.node_update(&2).unwrap().is_cleared(NodeField::Label));
+    }
+
+    #[test]
+    fn test_nodes_with_field() {
+        // any weight type
+        let mut diff = GraphDiff::<u32, NodeUpdate, f64>::new();
+        diff.apply_labels(&HashMap::from([(1, "one".into()), (2, "two".into())]));
+        diff.get_or_create_mut_node_update(&3)
+            .clear(NodeField::Label);
+        diff.get_or_create_mut_node_update(&4).red = Some(255);
+
+        let mut labelled: Vec<_> = diff.nodes_with_field(NodeField::Label).collect();
+        labelled.sort_by_key(|(id, _)| **id);
+        assert_eq!(labelled.len(), 2);
+        assert_eq!(labelled[0].0, &1);
+        assert_eq!(labelled[1].1.label.as_deref(), Some("two"));
+
+        let recolored: Vec<_> = diff.nodes_with_field(NodeField::Red).collect();
+        assert_eq!(recolored.len(), 1);
+        assert_eq!(recolored[0].0, &4);
+        assert_eq!(diff.nodes_with_field(NodeField::Url).count(), 0);
     }
 }
@@ -0,0 +1,182 @@
+use crate::diff::{HashMap, HashSet};
+use core::hash::Hash;
+#[cfg(not(feature = "ordered"))]
+use core::marker::PhantomData;
+#[cfg(feature = "ordered")]
+use indexmap::IndexSet;
+
+/// The order in which nodes and edges were first added to or deleted from a diff.
+///
+/// Recorded by the mutating methods of `GraphDiff` next to its hash maps and followed by
+/// its `iter_*` methods under the `ordered` feature. Without the feature it is zero-sized
+/// and the iterators are the plain map iterators.
+///
+/// Entries keep the position of their first insertion, also when they are removed and
+/// added again. Recorded entries that are no longer in the maps are skipped when
+/// iterating, entries that were never recorded follow in the order of the maps.
+#[derive(Clone, Debug)]
+pub(crate) struct InsertionOrder<Id> {
+    #[cfg(feature = "ordered")]
+    updated_nodes: IndexSet<Id>,
+    #[cfg(feature = "ordered")]
+    deleted_nodes: IndexSet<Id>,
+    #[cfg(feature = "ordered")]
+    updated_edges: IndexSet<(Id, Id)>,
+    #[cfg(feature = "ordered")]
+    deleted_edges: IndexSet<(Id, Id)>,
+    #[cfg(not(feature = "ordered"))]
+    marker: PhantomData<Id>,
+}
+
+impl<Id> Default for InsertionOrder<Id> {
+    fn default() -> InsertionOrder<Id> {
+        InsertionOrder {
+            #[cfg(feature = "ordered")]
+            updated_nodes: IndexSet::new(),
+            #[cfg(feature = "ordered")]
+            deleted_nodes: IndexSet::new(),
+            #[cfg(feature = "ordered")]
+            updated_edges: IndexSet::new(),
+            #[cfg(feature = "ordered")]
+            deleted_edges: IndexSet::new(),
+            #[cfg(not(feature = "ordered"))]
+            marker: PhantomData,
+        }
+    }
+}
+
+/// The order is not part of the content of a diff, diffs with the same entries are equal.
+impl<Id> PartialEq for InsertionOrder<Id> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "ordered")]
+impl<Id: Hash + Eq + Copy> InsertionOrder<Id> {
+    pub(crate) fn updated_node(&mut self, id: &Id) {
+        self.updated_nodes.insert(*id);
+    }
+
+    pub(crate) fn deleted_node(&mut self, id: &Id) {
+        self.deleted_nodes.insert(*id);
+    }
+
+    pub(crate) fn updated_edge(&mut self, from: &Id, to: &Id) {
+        self.updated_edges.insert((*from, *to));
+    }
+
+    pub(crate) fn deleted_edge(&mut self, from: &Id, to: &Id) {
+        self.deleted_edges.insert((*from, *to));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.updated_nodes.clear();
+        self.deleted_nodes.clear();
+        self.updated_edges.clear();
+        self.deleted_edges.clear();
+    }
+
+    /// Append the order of `other`, for entries not recorded here yet.
+    pub(crate) fn extend(&mut self, other: InsertionOrder<Id>) {
+        self.updated_nodes.extend(other.updated_nodes);
+        self.deleted_nodes.extend(other.deleted_nodes);
+        self.updated_edges.extend(other.updated_edges);
+        self.deleted_edges.extend(other.deleted_edges);
+    }
+
+    pub(crate) fn iter_updated_nodes<'a, T>(
+        &'a self,
+        nodes: &'a HashMap<Id, T>,
+    ) -> impl Iterator<Item = (&'a Id, &'a T)> {
+        let recorded = (self.updated_nodes.iter()).filter_map(|id| nodes.get_key_value(id));
+        let rest = (nodes.iter()).filter(|(id, _)| !self.updated_nodes.contains(*id));
+        recorded.chain(rest)
+    }
+
+    pub(crate) fn iter_deleted_nodes<'a>(
+        &'a self,
+        nodes: &'a HashSet<Id>,
+    ) -> impl Iterator<Item = &'a Id> {
+        let recorded = (self.deleted_nodes.iter()).filter_map(|id| nodes.get(id));
+        let rest = (nodes.iter()).filter(|id| !self.deleted_nodes.contains(*id));
+        recorded.chain(rest)
+    }
+
+    pub(crate) fn iter_updated_edges<'a, W>(
+        &'a self,
+        edges: &'a HashMap<Id, HashMap<Id, W>>,
+    ) -> impl Iterator<Item = (&'a Id, &'a Id, &'a W)> {
+        let recorded = self.updated_edges.iter().filter_map(|(from, to)| {
+            let (from, to_weight) = edges.get_key_value(from)?;
+            let (to, weight) = to_weight.get_key_value(to)?;
+            Some((from, to, weight))
+        });
+        let rest = edges.iter().flat_map(move |(from, to_weight)| {
+            (to_weight.iter())
+                .filter(move |(to, _)| !self.updated_edges.contains(&(*from, **to)))
+                .map(move |(to, weight)| (from, to, weight))
+        });
+        recorded.chain(rest)
+    }
+
+    pub(crate) fn iter_deleted_edges<'a>(
+        &'a self,
+        edges: &'a HashMap<Id, HashSet<Id>>,
+    ) -> impl Iterator<Item = (&'a Id, &'a Id)> {
+        let recorded = self.deleted_edges.iter().filter_map(|(from, to)| {
+            let (from, to_set) = edges.get_key_value(from)?;
+            Some((from, to_set.get(to)?))
+        });
+        let rest = edges.iter().flat_map(move |(from, to_set)| {
+            (to_set.iter())
+                .filter(move |to| !self.deleted_edges.contains(&(*from, **to)))
+                .map(move |to| (from, to))
+        });
+        recorded.chain(rest)
+    }
+}
+
+#[cfg(not(feature = "ordered"))]
+impl<Id: Hash + Eq + Copy> InsertionOrder<Id> {
+    pub(crate) fn updated_node(&mut self, _: &Id) {}
+
+    pub(crate) fn deleted_node(&mut self, _: &Id) {}
+
+    pub(crate) fn updated_edge(&mut self, _: &Id, _: &Id) {}
+
+    pub(crate) fn deleted_edge(&mut self, _: &Id, _: &Id) {}
+
+    pub(crate) fn clear(&mut self) {}
+
+    pub(crate) fn extend(&mut self, _: InsertionOrder<Id>) {}
+
+    pub(crate) fn iter_updated_nodes<'a, T>(
+        &'a self,
+        nodes: &'a HashMap<Id, T>,
+    ) -> impl Iterator<Item = (&'a Id, &'a T)> {
+        nodes.iter()
+    }
+
+    pub(crate) fn iter_deleted_nodes<'a>(
+        &'a self,
+        nodes: &'a HashSet<Id>,
+    ) -> impl Iterator<Item = &'a Id> {
+        nodes.iter()
+    }
+
+    pub(crate) fn iter_updated_edges<'a, W>(
+        &'a self,
+        edges: &'a HashMap<Id, HashMap<Id, W>>,
+    ) -> impl Iterator<Item = (&'a Id, &'a Id, &'a W)> {
+        (edges.iter())
+            .flat_map(|(from, to_weight)| to_weight.iter().map(move |(to, w)| (from, to, w)))
+    }
+
+    pub(crate) fn iter_deleted_edges<'a>(
+        &'a self,
+        edges: &'a HashMap<Id, HashSet<Id>>,
+    ) -> impl Iterator<Item = (&'a Id, &'a Id)> {
+        (edges.iter()).flat_map(|(from, to_set)| to_set.iter().map(move |to| (from, to)))
+    }
+}
@@ -0,0 +1,110 @@
+use crate::diff::{GraphDiff, HashMap};
+use ::petgraph::{graph::NodeIndex, visit::EdgeRef, Graph};
+use core::{hash::Hash, ops::AddAssign};
+
+impl<Id: Hash + Eq + Copy, T: Default + AddAssign + Clone, W: Copy + PartialEq>
+    GraphDiff<Id, T, W>
+{
+    /// Create an "all new" diff adding every node and edge of a `petgraph` graph, e.g. for
+    /// an initial upload. Node ids are given by `id_of`.
+    ///
+    /// The diff has no deletions. Of parallel edges between the same two nodes only the
+    /// weight of the last one is kept.
+    pub fn from_petgraph(g: &Graph<T, W>, id_of: impl Fn(NodeIndex) -> Id) -> Self {
+        let mut diff = GraphDiff::with_capacity(g.node_count(), g.node_count());
+        diff.extend_nodes(g.node_indices().map(|i| (id_of(i), g[i].clone())));
+        let rejected = diff.extend_edges(
+            g.edge_references()
+                .map(|e| (id_of(e.source()), id_of(e.target()), *e.weight())),
+        );
+        debug_assert!(rejected.is_ok(), "a new diff rejects no edges");
+        diff
+    }
+
+    /// Materialize the new or updated nodes and edges into a new `petgraph` graph. Returns
+    /// the graph and the index of the node of each id, to relate it back to the diff.
+    ///
+    /// Deletions are ignored, they have no counterpart in a new graph. Endpoints of edges
+    /// that are not new or updated nodes of the diff get a node with `T::default()`, use
+    /// `prune_dangling_edges` first to drop such edges instead.
+    pub fn to_petgraph(&self) -> (Graph<T, W>, HashMap<Id, NodeIndex>) {
+        let mut graph = Graph::with_capacity(self.new_or_updated_nodes().len(), 0);
+        let mut index: HashMap<Id, NodeIndex> = HashMap::new();
+        for (id, update) in self.iter_new_or_updated_nodes() {
+            index.insert(*id, graph.add_node(update.clone()));
+        }
+        for (from, to, weight) in self.iter_new_or_updated_edges() {
+            let from = *index
+                .entry(*from)
+                .or_insert_with(|| graph.add_node(T::default()));
+            let to = *index
+                .entry(*to)
+                .or_insert_with(|| graph.add_node(T::default()));
+            graph.add_edge(from, to, *weight);
+        }
+        (graph, index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_petgraph() {
+        let mut g = Graph::<u32, f32>::new();
+        let a = g.add_node(1);
+        let b = g.add_node(2);
+        let c = g.add_node(3);
+        g.add_edge(a, b, 0.5);
+        g.add_edge(b, c, 1.5);
+        g.add_edge(c, c, 2.0);
+
+        let diff = GraphDiff::from_petgraph(&g, |i| i.index() as u64 * 10);
+        assert_eq!(diff.new_or_updated_nodes().len(), 3);
+        assert_eq!(diff.new_or_updated_nodes()[&20], 3);
+        assert_eq!(diff.iter_new_or_updated_edges().count(), 3);
+        assert_eq!(diff.edge_weight(&0, &10), Some(&0.5));
+        assert_eq!(diff.edge_weight(&20, &20), Some(&2.0));
+        assert!(diff.deleted_nodes().is_empty());
+        assert!(diff.deleted_edges().is_empty());
+    }
+
+    #[test]
+    fn test_to_petgraph() {
+        let mut diff = GraphDiff::<u64, u32>::new();
+        diff.add_or_update_node(&1, 10);
+        diff.add_or_update_node(&2, 20);
+        diff.add_edge(&1, &2, 0.5).unwrap();
+        diff.add_edge(&2, &1, 1.5).unwrap();
+        // an endpoint that is not a node of the diff
+        diff.add_edge(&2, &3, 2.0).unwrap();
+        diff.delete_node(4);
+        diff.delete_edge(&1, &5);
+
+        let (g, index) = diff.to_petgraph();
+        assert_eq!(g.node_count(), 3);
+        assert_eq!(g.edge_count(), 3);
+        assert_eq!(index.len(), 3);
+        assert_eq!((g[index[&1]], g[index[&2]], g[index[&3]]), (10, 20, 0));
+        for (from, to, weight) in diff.iter_new_or_updated_edges() {
+            let edge = g.find_edge(index[from], index[to]).unwrap();
+            assert_eq!(g[edge], *weight);
+        }
+
+        // and back
+        let ids: HashMap<_, _> = index.iter().map(|(id, i)| (*i, *id)).collect();
+        let mut roundtrip = GraphDiff::from_petgraph(&g, |i| ids[&i]);
+        roundtrip.add_or_update_node(&3, 0);
+        let mut expected = diff.clone();
+        expected.add_or_update_node(&3, 0);
+        assert_eq!(
+            roundtrip.new_or_updated_nodes(),
+            expected.new_or_updated_nodes()
+        );
+        assert_eq!(
+            roundtrip.new_or_updated_edges(),
+            expected.new_or_updated_edges()
+        );
+    }
+}